@@ -1,23 +1,87 @@
-use crate::{Error, Result};
+use crate::{Error, RecentPaste, Result};
 use reqwasm::http::{Request, Response};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 
 #[derive(Debug, Deserialize)]
 pub struct PasteResponse {
     pub id: String,
+    #[serde(default)]
+    pub view_key: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ErrorResponse {
     pub code: u16,
+    /// Stable identifier of the error, e.g. `invalid_base64`.
+    #[serde(default)]
+    pub error: Option<String>,
     pub message: String,
+    #[serde(default)]
+    pub details: ErrorDetails,
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+/// Where parsing a build failed, as far as the server knows.
+#[derive(Debug, Default, Deserialize)]
+pub struct ErrorDetails {
+    pub offset: Option<usize>,
+    pub path: Option<String>,
+    pub field: Option<String>,
+}
+
+/// A public paste found by a search, see [`search`].
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchEntry {
+    pub id: String,
+    pub title: String,
+    pub ascendancy_or_class: String,
+    #[serde(default)]
+    pub main_skill: Option<String>,
+    #[serde(default)]
+    pub keystones: Vec<String>,
+    pub level: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchResponse {
+    pub results: Vec<SearchEntry>,
+    /// Cursor of the next page, `None` if there are no more results.
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreatePaste<'a> {
+    content: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    tags: Vec<&'a str>,
+    private: bool,
 }
 
 #[allow(dead_code)] // Only used in !SSR
-pub async fn create_paste(content: Rc<String>) -> Result<PasteResponse> {
+pub async fn create_paste(
+    content: Rc<String>,
+    title: Rc<String>,
+    tags: Rc<String>,
+    private: bool,
+) -> Result<PasteResponse> {
+    let body = CreatePaste {
+        content: &content,
+        title: Some(title.trim()).filter(|title| !title.is_empty()),
+        tags: tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+        private,
+    };
+    let body = serde_json::to_string(&body).map_err(anyhow::Error::from)?;
+
     let resp = Request::post("/api/v1/paste/")
-        .body(&*content)
+        .header("Content-Type", "application/json")
+        .body(body)
         .send()
         .await?;
 
@@ -28,8 +92,17 @@ pub async fn create_paste(content: Rc<String>) -> Result<PasteResponse> {
     Ok(resp.json::<PasteResponse>().await?)
 }
 
-pub async fn get_paste(id: String) -> Result<String> {
-    let path = format!("/{}/raw", id);
+/// Fetches the content of a paste, `key` is the view key of a private paste.
+pub async fn get_paste(id: String, key: Option<String>) -> Result<String> {
+    let path = match key {
+        Some(key) => {
+            let params = web_sys::UrlSearchParams::new()
+                .map_err(|_| anyhow::anyhow!("failed to encode the view key"))?;
+            params.append("key", &key);
+            format!("/{}/raw?{}", id, String::from(params.to_string()))
+        }
+        None => format!("/{}/raw", id),
+    };
     let resp = Request::get(&path).send().await?;
 
     if resp.status() == 404 {
@@ -43,10 +116,101 @@ pub async fn get_paste(id: String) -> Result<String> {
     Ok(resp.text().await?)
 }
 
+pub async fn get_recent() -> Result<Vec<RecentPaste>> {
+    let resp = Request::get("/api/v1/recent").send().await?;
+
+    if !resp.ok() {
+        return Err(handle_error_response(resp).await);
+    }
+
+    Ok(resp.json::<Vec<RecentPaste>>().await?)
+}
+
+/// Searches public pastes, `query` is the already encoded query string.
+#[allow(dead_code)] // Only used in !SSR
+pub async fn search(query: &str) -> Result<SearchResponse> {
+    let path = format!("/api/v1/search?{}", query);
+    let resp = Request::get(&path).send().await?;
+
+    if !resp.ok() {
+        return Err(handle_error_response(resp).await);
+    }
+
+    Ok(resp.json::<SearchResponse>().await?)
+}
+
 async fn handle_error_response(resp: Response) -> Error {
     if let Ok(err) = resp.json::<ErrorResponse>().await {
-        Error::ApiError(err.code, err.message)
+        let mut message = friendly_message(&err).unwrap_or(err.message);
+        if let Some(request_id) = err.request_id {
+            message.push_str(&format!(" (request id {})", request_id));
+        }
+        Error::ApiError(err.code, message)
     } else {
         Error::UnhandledStatus(resp.status(), resp.status_text())
     }
 }
+
+/// A message for errors caused by the submitted build, which users can act on.
+fn friendly_message(err: &ErrorResponse) -> Option<String> {
+    let details = &err.details;
+    let at = |prefix: &str, value: Option<String>| {
+        value
+            .map(|value| format!(" {} {}", prefix, value))
+            .unwrap_or_default()
+    };
+
+    let message = match err.error.as_deref()? {
+        "invalid_base64" => format!(
+            "This is not a Path of Building build code, it contains an invalid character{}.",
+            at(
+                "at position",
+                details.offset.map(|offset| offset.to_string())
+            )
+        ),
+        "invalid_compression" => {
+            "The build code is incomplete, make sure to copy the entire code.".to_owned()
+        }
+        "invalid_xml" => format!(
+            "The build code contains a broken build{}, try exporting it again.",
+            at("in", details.path.clone())
+        ),
+        "missing_xml_element" => format!(
+            "The build is missing required data{}, try exporting it again.",
+            at("for", details.field.clone())
+        ),
+        "invalid_xml_content" => format!(
+            "The build contains unexpected data{}, make sure it is up to date.",
+            at("in", details.path.clone())
+        ),
+        _ => return None,
+    };
+
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(json: &str) -> Option<String> {
+        friendly_message(&serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn test_friendly_message() {
+        assert_eq!(
+            message(r#"{"code": 400, "error": "invalid_base64", "message": "", "details": {"offset": 3}}"#).as_deref(),
+            Some("This is not a Path of Building build code, it contains an invalid character at position 3.")
+        );
+        assert_eq!(
+            message(r#"{"code": 400, "error": "invalid_xml", "message": "", "details": {"offset": 25, "path": "PathOfBuilding/Build"}}"#).as_deref(),
+            Some("The build code contains a broken build in PathOfBuilding/Build, try exporting it again.")
+        );
+        assert_eq!(
+            message(r#"{"code": 404, "error": "not_found", "message": ""}"#),
+            None
+        );
+        assert_eq!(message(r#"{"code": 500, "message": ""}"#), None);
+    }
+}
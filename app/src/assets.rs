@@ -1,3 +1,29 @@
+macro_rules! ascendancies {
+    ($callback:ident) => {
+        $callback!(
+            Ascendant,
+            Assassin,
+            Berserker,
+            Champion,
+            Chieftain,
+            Deadeye,
+            Elementalist,
+            Gladiator,
+            Guardian,
+            Hierophant,
+            Inquisitor,
+            Juggernaut,
+            Necromancer,
+            Occultist,
+            Pathfinder,
+            Raider,
+            Saboteur,
+            Slayer,
+            Trickster
+        )
+    };
+}
+
 pub fn ascendancy_image(ascendancy_or_class: &str) -> Option<&'static str> {
     macro_rules! assets {
         ($($name:ident),+) => {
@@ -8,32 +34,47 @@ pub fn ascendancy_image(ascendancy_or_class: &str) -> Option<&'static str> {
         };
     }
 
-    assets!(
-        Ascendant,
-        Assassin,
-        Berserker,
-        Champion,
-        Chieftain,
-        Deadeye,
-        Elementalist,
-        Gladiator,
-        Guardian,
-        Hierophant,
-        Inquisitor,
-        Juggernaut,
-        Necromancer,
-        Occultist,
-        Pathfinder,
-        Raider,
-        Saboteur,
-        Slayer,
-        Trickster
-    )
+    ascendancies!(assets)
+}
+
+/// Width and height of the ascendancy image, read from the images when compiling.
+pub fn ascendancy_image_size(ascendancy_or_class: &str) -> Option<(u32, u32)> {
+    macro_rules! sizes {
+        ($($name:ident),+) => {
+            match ascendancy_or_class {
+                $(stringify!($name) => {
+                    const SIZE: (u32, u32) = png_size(include_bytes!(concat!(
+                        "../assets/asc/",
+                        stringify!($name),
+                        ".png"
+                    )));
+                    Some(SIZE)
+                })+
+                _ => None,
+            }
+        };
+    }
+
+    ascendancies!(sizes)
+}
+
+/// Dimensions from the header of a PNG, the `IHDR` chunk directly follows the signature.
+const fn png_size(png: &[u8]) -> (u32, u32) {
+    const fn be_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    }
+
+    (be_u32(png, 16), be_u32(png, 20))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::assets::ascendancy_image;
+    use crate::assets::{ascendancy_image, ascendancy_image_size};
 
     #[test]
     fn test_ascendancy_images() {
@@ -51,4 +92,11 @@ mod tests {
         );
         assert_eq!(None, ascendancy_image("Oops"));
     }
+
+    #[test]
+    fn test_ascendancy_image_sizes() {
+        assert_eq!(Some((84, 65)), ascendancy_image_size("Ascendant"));
+        assert_eq!(Some((93, 93)), ascendancy_image_size("Slayer"));
+        assert_eq!(None, ascendancy_image_size("Oops"));
+    }
 }
@@ -1,5 +1,6 @@
 use lazycell::LazyCell;
 use pob::SerdePathOfBuilding;
+use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 
 use crate::Route;
@@ -13,6 +14,8 @@ struct ContextInner {
 #[derive(Clone)]
 pub struct Context {
     inner: Rc<ContextInner>,
+    /// Id of the request the page is rendered for, shown on error pages.
+    request_id: Option<Rc<str>>,
 }
 
 impl Context {
@@ -23,6 +26,7 @@ impl Context {
                 host: "".to_owned(),
                 inner: Inner::None,
             }),
+            request_id: None,
         }
     }
 
@@ -33,6 +37,29 @@ impl Context {
                 host,
                 inner: Inner::None,
             }),
+            request_id: None,
+        }
+    }
+
+    pub fn search(host: String) -> Self {
+        Self {
+            inner: Rc::new(ContextInner {
+                route: Some(Route::Search),
+                host,
+                inner: Inner::None,
+            }),
+            request_id: None,
+        }
+    }
+
+    pub fn recent(host: String, pastes: Vec<RecentPaste>) -> Self {
+        Self {
+            inner: Rc::new(ContextInner {
+                route: Some(Route::Recent),
+                host,
+                inner: Inner::Recent(Rc::new(pastes)),
+            }),
+            request_id: None,
         }
     }
 
@@ -43,22 +70,46 @@ impl Context {
                 host,
                 inner: Inner::None,
             }),
+            request_id: None,
+        }
+    }
+
+    /// A paste which was removed by a moderator.
+    pub fn gone(host: String) -> Self {
+        Self {
+            inner: Rc::new(ContextInner {
+                route: Some(Route::NotFound),
+                host,
+                inner: Inner::Gone,
+            }),
+            request_id: None,
         }
     }
 
-    pub fn paste(host: String, name: String, content: String) -> Self {
+    pub fn paste(host: String, name: String, content: String, metadata: PasteMetadata) -> Self {
         Self {
             inner: Rc::new(ContextInner {
                 route: Some(Route::Paste(name)),
                 host,
                 inner: Inner::Paste(Paste {
                     content,
+                    metadata: Rc::new(metadata),
                     pob: LazyCell::new(),
                 }),
             }),
+            request_id: None,
         }
     }
 
+    pub fn with_request_id(mut self, request_id: Option<&str>) -> Self {
+        self.request_id = request_id.map(Rc::from);
+        self
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
     pub fn route(&self) -> Option<&Route> {
         self.inner.route.as_ref()
     }
@@ -74,10 +125,46 @@ impl Context {
             _ => None,
         }
     }
+
+    pub fn is_gone(&self) -> bool {
+        matches!(self.inner.inner, Inner::Gone)
+    }
+
+    pub fn get_recent(&self) -> Option<Rc<Vec<RecentPaste>>> {
+        match self.inner.inner {
+            Inner::Recent(ref pastes) => Some(pastes.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// User provided information about a paste.
+#[derive(Debug, Default, Clone)]
+pub struct PasteMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub private: bool,
+}
+
+/// A recently uploaded public paste.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentPaste {
+    pub id: String,
+    pub title: String,
+    pub ascendancy_or_class: String,
+    #[serde(default)]
+    pub main_skill: Option<String>,
+    /// Summary lines as rendered in the page description.
+    #[serde(default)]
+    pub summary: Vec<String>,
+    /// Upload time in milliseconds since the epoch.
+    pub created: u64,
 }
 
 pub struct Paste {
     content: String,
+    metadata: Rc<PasteMetadata>,
     pob: LazyCell<Rc<SerdePathOfBuilding>>,
 }
 
@@ -86,6 +173,10 @@ impl Paste {
         &self.content
     }
 
+    pub fn metadata(&self) -> Rc<PasteMetadata> {
+        self.metadata.clone()
+    }
+
     pub fn path_of_building(&self) -> anyhow::Result<Rc<SerdePathOfBuilding>> {
         self.pob
             .try_borrow_with(|| Ok(Rc::new(SerdePathOfBuilding::from_export(&self.content)?)))
@@ -95,5 +186,7 @@ impl Paste {
 
 enum Inner {
     None,
+    Gone,
     Paste(Paste),
+    Recent(Rc<Vec<RecentPaste>>),
 }
@@ -2,7 +2,7 @@ use crate::Meta;
 use sycamore::prelude::*;
 
 #[component(Head<G>)]
-pub fn head(meta: Meta) -> View<G> {
+pub fn head((meta, oembed): (Meta, String)) -> View<G> {
     let title = meta.title.clone();
     view! {
         title { (title) }
@@ -10,6 +10,6 @@ pub fn head(meta: Meta) -> View<G> {
         meta(property="og:description", content=meta.description)
         meta(property="og:image", content=meta.image)
         meta(name="theme-color", content=meta.color)
-        link(type="application/json+oembed", href="/oembed.json")
+        link(rel="alternate", type="application/json+oembed", href=oembed)
     }
 }
@@ -16,9 +16,9 @@ mod utils;
 #[cfg(feature = "ssr")]
 mod head;
 
-pub use context::Context;
+pub use context::{Context, PasteMetadata, RecentPaste};
 pub use error::{Error, Result};
-pub use meta::Meta;
+pub use meta::{Meta, PasteInfo};
 pub use response_context::ResponseContext;
 pub use router::Route;
 
@@ -30,8 +30,13 @@ pub fn render_to_string(context: Context) -> (String, ResponseContext) {
 }
 
 #[cfg(feature = "ssr")]
-pub fn render_head(meta: Meta) -> String {
-    let mut result = sycamore::render_to_string(|| view! { head::Head(meta) });
+pub fn render_head(mut meta: Meta, origin: &str, oembed: String) -> String {
+    // Crawlers only understand absolute image urls
+    if meta.image.starts_with('/') {
+        meta.image = format!("{}{}", origin, meta.image).into();
+    }
+
+    let mut result = sycamore::render_to_string(|| view! { head::Head((meta, oembed)) });
 
     // workaround to replace data-hk with data-xx to not interfer with hydration
     let bytes = unsafe { result.as_bytes_mut() };
@@ -53,11 +58,21 @@ pub fn app(ctx: Option<Context>) -> View<G> {
         sycamore_router::navigate("/");
         ev.prevent_default();
     };
+    let navigate_recent = |ev: web_sys::Event| {
+        sycamore_router::navigate("/recent");
+        ev.prevent_default();
+    };
+    let navigate_search = |ev: web_sys::Event| {
+        sycamore_router::navigate("/search");
+        ev.prevent_default();
+    };
 
     view! {
         div {
             nav(class="flex p-4 lg:px-8 mb-10 bg-slate-200 dark:bg-slate-900 dark:drop-shadow-lg") {
                 a(class="flex-auto", href="/", on:click=navigate_index) { "POB B.in" }
+                a(class="mx-3", href="/recent", on:click=navigate_recent) { "Recent" }
+                a(class="mx-3", href="/search", on:click=navigate_search) { "Search" }
                 ThemeToggle()
             }
             div(class="max-w-screen-xl mx-auto px-5 xl:px-0") {
@@ -1,9 +1,12 @@
 use crate::pob;
-use ::pob::SerdePathOfBuilding;
+use ::pob::{PathOfBuilding, PathOfBuildingExt, SerdePathOfBuilding};
 use std::borrow::Cow;
 
 const TITLE_INDEX: &str = "POB B.in - Share your Path of Exile build";
+const TITLE_RECENT: &str = "POB B.in - Recent Builds";
+const TITLE_SEARCH: &str = "POB B.in - Search Builds";
 const TITLE_NOT_FOUND: &str = "POB B.in - Not Found";
+const TITLE_GONE: &str = "POB B.in - Removed";
 const TITLE_SERVER_ERROR: &str = "POB B.in - Server Error";
 const DESCRIPTION: &str = "POB B.in is a website to share your Path of Building builds online";
 const DEFAULT_COLOR: &str = "#0ea5e9";
@@ -26,6 +29,24 @@ impl Meta {
         }
     }
 
+    pub(crate) fn recent() -> Self {
+        Self {
+            title: TITLE_RECENT.into(),
+            description: DESCRIPTION.into(),
+            image: "".into(),
+            color: DEFAULT_COLOR,
+        }
+    }
+
+    pub(crate) fn search() -> Self {
+        Self {
+            title: TITLE_SEARCH.into(),
+            description: DESCRIPTION.into(),
+            image: "".into(),
+            color: DEFAULT_COLOR,
+        }
+    }
+
     pub(crate) fn not_found() -> Self {
         Self {
             title: TITLE_NOT_FOUND.into(),
@@ -35,6 +56,15 @@ impl Meta {
         }
     }
 
+    pub(crate) fn gone() -> Self {
+        Self {
+            title: TITLE_GONE.into(),
+            description: DESCRIPTION.into(),
+            image: "".into(),
+            color: DEFAULT_COLOR,
+        }
+    }
+
     pub(crate) fn server_error() -> Self {
         Self {
             title: TITLE_SERVER_ERROR.into(),
@@ -51,6 +81,40 @@ impl Default for Meta {
     }
 }
 
+/// Information about a paste for consumers outside of the app, e.g. oEmbed.
+#[derive(Debug)]
+pub struct PasteInfo {
+    /// The full build title, see [`pob::title`].
+    pub title: String,
+    pub ascendancy_or_class: String,
+    pub main_skill: Option<String>,
+    /// Summary lines as rendered in the page description.
+    pub summary: Vec<String>,
+    /// Key stats (life, ES, resistances, DPS), one line per stat as spans of
+    /// text and their tailwind classes.
+    pub stats: Vec<Vec<(&'static str, String)>>,
+    /// Path to the ascendancy image and its dimensions.
+    pub image: Option<(&'static str, u32, u32)>,
+    pub color: &'static str,
+}
+
+impl PasteInfo {
+    pub fn new(pob: &SerdePathOfBuilding) -> Self {
+        let ascendancy_or_class = pob.ascendancy_or_class_name();
+        Self {
+            title: pob::title(pob),
+            ascendancy_or_class: ascendancy_or_class.to_owned(),
+            main_skill: pob.main_skill_name().map(ToOwned::to_owned),
+            summary: get_paste_summary(pob),
+            stats: get_card_stats(pob),
+            image: crate::assets::ascendancy_image(ascendancy_or_class)
+                .zip(crate::assets::ascendancy_image_size(ascendancy_or_class))
+                .map(|(image, (width, height))| (image, width, height)),
+            color: get_color(ascendancy_or_class),
+        }
+    }
+}
+
 pub(crate) fn get_paste_summary(pob: &SerdePathOfBuilding) -> Vec<String> {
     let core_stats = pob::summary::core_stats(pob);
     let defense = pob::summary::defense(pob);
@@ -69,6 +133,20 @@ pub(crate) fn get_paste_summary(pob: &SerdePathOfBuilding) -> Vec<String> {
         .collect()
 }
 
+fn get_card_stats(pob: &SerdePathOfBuilding) -> Vec<Vec<(&'static str, String)>> {
+    let core_stats = pob::summary::core_stats(pob);
+    let defense = pob::summary::defense(pob);
+    let offense = pob::summary::offense(pob);
+
+    core_stats
+        .into_iter()
+        .chain(defense)
+        .chain(offense)
+        .filter(|stat| matches!(stat.name(), "Life" | "ES" | "Resistances" | "DPS"))
+        .filter_map(|stat| stat.render_to_spans())
+        .collect()
+}
+
 pub(crate) fn get_color(ascendancy_name: &str) -> &'static str {
     match ascendancy_name {
         "Slayer" => "#96afc8",
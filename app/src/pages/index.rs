@@ -13,14 +13,24 @@ Creating ...
 #[component(IndexPage<G>)]
 pub fn index_page() -> View<G> {
     let value = Signal::new(String::new());
+    let custom_title = Signal::new(String::new());
+    let tags = Signal::new(String::new());
+    let private = Signal::new(false);
     let loading = Signal::new(false);
     let error = Signal::new("".to_owned());
 
-    let pob = memo!(value, error, {
+    // Links to supported paste sites are imported by the server
+    let import = memo!(value, {
+        let value = value.get();
+        let value = value.trim();
+        (value.starts_with("https://") || value.starts_with("http://")).then(|| value.to_owned())
+    });
+
+    let pob = memo!(value, error, import, {
         let _ = error.get(); // register signal
 
         let value = &*value.get();
-        if value.trim().is_empty() {
+        if value.trim().is_empty() || import.get().is_some() {
             return None;
         }
 
@@ -34,16 +44,19 @@ pub fn index_page() -> View<G> {
         }
     });
 
-    let title = memo!(
-        pob,
-        (*pob.get())
-            .as_ref()
-            .map(crate::pob::title)
-            .unwrap_or_else(|| "Share your Build".to_owned())
-    );
+    let title = memo!(pob, import, {
+        if let Some(pob) = &*pob.get() {
+            return crate::pob::title(pob);
+        }
+
+        match &*import.get() {
+            Some(url) => format!("Import from {}", crate::utils::host(url)),
+            None => "Share your Build".to_owned(),
+        }
+    });
 
     #[cfg(not(feature = "ssr"))]
-    let btn_submit = cloned!((loading, value, error) => move |_| {
+    let btn_submit = cloned!((loading, value, custom_title, tags, private, error) => move |_| {
         use wasm_bindgen_futures::spawn_local;
 
         if *loading.get() {
@@ -53,9 +66,10 @@ pub fn index_page() -> View<G> {
 
         error.set("".to_owned());
 
-        let value = value.get();
+        let (value, custom_title, tags) = (value.get(), custom_title.get(), tags.get());
+        let private = *private.get();
         let future = cloned!((loading, error) => async move {
-            match crate::api::create_paste(value).await {
+            match crate::api::create_paste(value, custom_title, tags, private).await {
                 Err(err) => {
                     loading.set(false);
                     error.set(err.to_string());
@@ -72,7 +86,12 @@ pub fn index_page() -> View<G> {
     let btn_submit = |_| {};
 
     // TODO: allow pasting of PoBs that cannot be properly parsed but appear to be valid
-    let btn_submit_disabled = memo!(loading, pob, *loading.get() || pob.get().is_none());
+    let btn_submit_disabled = memo!(
+        loading,
+        pob,
+        import,
+        *loading.get() || (pob.get().is_none() && import.get().is_none())
+    );
     let btn_content = memo_cond!(loading, SPINNER, "Create");
 
     let on_input = cloned!(error => move |_| error.set("".to_owned()));
@@ -80,6 +99,21 @@ pub fn index_page() -> View<G> {
     view! {
         div(class="flex flex-col gap-y-3") {
             h1(class="dark:text-slate-100 text-slate-900") { (title.get()) }
+            div(class="flex flex-col sm:flex-row gap-3") {
+                input(
+                    bind:value=custom_title,
+                    type="text",
+                    maxlength=100,
+                    placeholder="Title (optional)",
+                    class="flex-auto dark:bg-slate-500 bg-slate-200 py-2 px-3 rounded-sm shadow-sm focus:outline-none dark:text-slate-300 text-slate-700 text-sm"
+                )
+                input(
+                    bind:value=tags,
+                    type="text",
+                    placeholder="Tags, comma separated (optional)",
+                    class="flex-auto dark:bg-slate-500 bg-slate-200 py-2 px-3 rounded-sm shadow-sm focus:outline-none dark:text-slate-300 text-slate-700 text-sm"
+                )
+            }
             textarea(
                 bind:value=value,
                 on:input=on_input,
@@ -87,8 +121,12 @@ pub fn index_page() -> View<G> {
                 class="dark:bg-slate-500 bg-slate-200 block w-full mt-1 py-2 px-3 rounded-sm shadow-sm focus:outline-none dark:text-slate-300 text-slate-700 resize-none text-sm break-all",
                 style="min-height: 60vh"
             )
-            div(class="flex") {
+            div(class="flex gap-x-3") {
                 div(class="flex-auto flex items-center text-red-500") { (*error.get()) }
+                label(class="flex items-center gap-x-2 text-sm dark:text-slate-300 text-slate-700") {
+                    input(type="checkbox", bind:checked=private)
+                    "Private"
+                }
                 button(
                     on:click=btn_submit,
                     disabled=*btn_submit_disabled.get(),
@@ -1,5 +1,9 @@
 pub(crate) mod index;
 pub(crate) mod paste;
+pub(crate) mod recent;
+pub(crate) mod search;
 
 pub use self::index::IndexPage;
 pub use self::paste::PastePage;
+pub use self::recent::RecentPage;
+pub use self::search::SearchPage;
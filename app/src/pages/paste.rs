@@ -5,7 +5,7 @@ use crate::{
     memo, meta,
     pob::{self, Element},
     router::RoutedComponent,
-    Meta, Result,
+    Meta, PasteMetadata, Result,
 };
 use ::pob::{PathOfBuilding, PathOfBuildingExt, SerdePathOfBuilding};
 use std::rc::Rc;
@@ -14,7 +14,10 @@ use wasm_bindgen::JsCast;
 use web_sys::HtmlTextAreaElement;
 
 pub struct Data {
+    id: String,
     content: String,
+    /// Only available when rendered on the server.
+    metadata: Option<Rc<PasteMetadata>>,
     pob: Rc<SerdePathOfBuilding>,
 }
 
@@ -22,14 +25,19 @@ impl<G: Html> RoutedComponent<G> for PastePage<G> {
     type RouteArg = String;
 
     fn from_context(ctx: crate::Context) -> Result<Data> {
-        let paste = ctx.get_paste().unwrap();
+        let (id, paste) = match (ctx.route(), ctx.get_paste()) {
+            (Some(crate::Route::Paste(id)), Some(paste)) => (id.clone(), paste),
+            _ => return Err(anyhow::anyhow!("context does not contain a paste").into()),
+        };
         Ok(Data {
+            id,
             content: paste.content().to_owned(),
+            metadata: Some(paste.metadata()),
             pob: paste.path_of_building()?,
         })
     }
 
-    fn from_hydration(element: web_sys::Element) -> Result<Data> {
+    fn from_hydration(id: Self::RouteArg, element: web_sys::Element) -> Result<Data> {
         let content = element
             .query_selector("textarea")
             .unwrap()
@@ -37,28 +45,58 @@ impl<G: Html> RoutedComponent<G> for PastePage<G> {
             .inner_html();
 
         let pob = Rc::new(SerdePathOfBuilding::from_export(&content)?);
-        Ok(Data { content, pob })
+        Ok(Data {
+            id,
+            content,
+            metadata: None,
+            pob,
+        })
     }
 
     fn from_dynamic<'a>(id: Self::RouteArg) -> LocalBoxFuture<'a, Result<Data>> {
         Box::pin(async move {
-            let content = crate::api::get_paste(id).await?;
+            let key = crate::utils::query_param("key");
+            let content = crate::api::get_paste(id.clone(), key).await?;
             let pob = Rc::new(SerdePathOfBuilding::from_export(&content)?);
-            Ok(Data { content, pob })
+            Ok(Data {
+                id,
+                content,
+                metadata: None,
+                pob,
+            })
         })
     }
 
     fn meta(arg: &Data) -> Result<Meta> {
         let pob: &SerdePathOfBuilding = &*arg.pob;
 
-        let config = pob::TitleConfig { no_title: true };
-        let title = pob::title_with_config(pob, &config).into();
+        let metadata = arg.metadata.as_deref();
+
+        let title = match metadata.and_then(|m| m.title.as_ref()) {
+            Some(title) => title.clone().into(),
+            None => {
+                let config = pob::TitleConfig { no_title: true };
+                pob::title_with_config(pob, &config).into()
+            }
+        };
 
-        let description = meta::get_paste_summary(pob).join("\n").into();
+        let mut description = Vec::new();
+        if let Some(d) = metadata.and_then(|m| m.description.as_ref()) {
+            description.push(d.clone());
+        }
+        description.extend(meta::get_paste_summary(pob));
+        if let Some(metadata) = metadata.filter(|m| !m.tags.is_empty()) {
+            description.push(format!("Tags: {}", metadata.tags.join(", ")));
+        }
+        let description = description.join("\n").into();
 
-        let image = crate::assets::ascendancy_image(pob.ascendancy_or_class_name())
-            .unwrap_or("")
-            .into();
+        // Previews of private pastes are not accessible without the view key
+        let image = match metadata.map_or(false, |m| m.private) {
+            true => crate::assets::ascendancy_image(pob.ascendancy_or_class_name())
+                .unwrap_or("")
+                .into(),
+            false => format!("/{}/preview.png", arg.id).into(),
+        };
         let color = meta::get_color(pob.ascendancy_or_class_name());
 
         Ok(Meta {
@@ -91,7 +129,7 @@ impl CopyState {
 }
 
 #[component(PastePage<G>)]
-pub fn paste_page(Data { content, pob }: Data) -> View<G> {
+pub fn paste_page(Data { content, pob, .. }: Data) -> View<G> {
     let title = pob::title(&*pob);
 
     let notes = pob.notes().to_owned();
@@ -0,0 +1,88 @@
+use crate::{future::LocalBoxFuture, router::RoutedComponent, Meta, RecentPaste, Result};
+use std::rc::Rc;
+use sycamore::prelude::*;
+
+pub type Data = Rc<Vec<RecentPaste>>;
+
+impl<G: Html> RoutedComponent<G> for RecentPage<G> {
+    type RouteArg = ();
+
+    fn from_context(ctx: crate::Context) -> Result<Data> {
+        Ok(ctx.get_recent().unwrap_or_default())
+    }
+
+    fn from_hydration(_: Self::RouteArg, element: web_sys::Element) -> Result<Data> {
+        let recent = element
+            .query_selector("[data-recent]")
+            .unwrap()
+            .and_then(|element| element.get_attribute("data-recent"))
+            .unwrap_or_default();
+
+        let recent = serde_json::from_str(&recent).map_err(anyhow::Error::from)?;
+        Ok(Rc::new(recent))
+    }
+
+    fn from_dynamic<'a>(_: Self::RouteArg) -> LocalBoxFuture<'a, Result<Data>> {
+        Box::pin(async move { Ok(Rc::new(crate::api::get_recent().await?)) })
+    }
+
+    fn meta(_: &Data) -> Result<Meta> {
+        Ok(Meta::recent())
+    }
+}
+
+#[component(RecentPage<G>)]
+pub fn recent_page(recent: Data) -> View<G> {
+    // Stored for hydration, the page has no other source for the list
+    let json = serde_json::to_string(&*recent).unwrap_or_default();
+
+    let list = match recent.is_empty() {
+        true => vec![view! { li(class="text-slate-500") { "No builds uploaded yet" } }],
+        false => recent.iter().cloned().map(render_paste).collect(),
+    };
+    let list = View::new_fragment(list);
+
+    view! {
+        div(class="flex flex-col gap-y-3", data-recent=json) {
+            div(class="flex items-center") {
+                h1(class="flex-auto dark:text-slate-100 text-slate-900") { "Recent Builds" }
+                a(href="/recent.atom", rel="external", class="text-sm text-sky-500 hover:underline") { "Feed" }
+            }
+            ul(class="flex flex-col gap-y-4") {
+                (list)
+            }
+        }
+    }
+}
+
+fn render_paste<G: Html>(paste: RecentPaste) -> View<G> {
+    let href = format!("/{}", paste.id);
+    let src = crate::assets::ascendancy_image(&paste.ascendancy_or_class).unwrap_or("");
+    let subtitle = std::iter::once(paste.ascendancy_or_class)
+        .chain(paste.main_skill)
+        .collect::<Vec<_>>()
+        .join(" · ");
+    let summary = View::new_fragment(
+        paste
+            .summary
+            .into_iter()
+            .map(|line| view! { div { (line) } })
+            .collect(),
+    );
+
+    view! {
+        li {
+            a(href=href, class="flex items-center gap-x-3 group") {
+                img(src=src,
+                    width=50, height=50,
+                    class="rounded-full",
+                    onerror="this.style.display='none'") {}
+                div(class="flex flex-col") {
+                    span(class="dark:text-slate-100 text-slate-900 group-hover:underline") { (paste.title) }
+                    span(class="text-sm text-slate-500") { (subtitle) }
+                    div(class="text-xs text-slate-500") { (summary) }
+                }
+            }
+        }
+    }
+}
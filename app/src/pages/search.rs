@@ -0,0 +1,226 @@
+use crate::{api::SearchEntry, memo};
+use sycamore::prelude::*;
+
+const INPUT_CLASS: &str = "flex-auto dark:bg-slate-500 bg-slate-200 py-2 px-3 rounded-sm shadow-sm focus:outline-none dark:text-slate-300 text-slate-700 text-sm";
+const BUTTON_CLASS: &str = "bg-sky-500 hover:bg-sky-700 hover:cursor-pointer px-6 py-2 text-sm rounded-lg font-semibold text-white disabled:opacity-50 disabled:cursor-not-allowed";
+
+#[derive(Clone)]
+struct State {
+    ascendancy: Signal<String>,
+    skill: Signal<String>,
+    keystone: Signal<String>,
+    min_level: Signal<String>,
+    results: Signal<Vec<SearchEntry>>,
+    /// Cursors of the current and all previous pages, `None` is the first page.
+    pages: Signal<Vec<Option<String>>>,
+    next: Signal<Option<String>>,
+    loading: Signal<bool>,
+    error: Signal<String>,
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            ascendancy: Signal::new(String::new()),
+            skill: Signal::new(String::new()),
+            keystone: Signal::new(String::new()),
+            min_level: Signal::new(String::new()),
+            results: Signal::new(Vec::new()),
+            pages: Signal::new(vec![None]),
+            next: Signal::new(None),
+            loading: Signal::new(false),
+            error: Signal::new(String::new()),
+        }
+    }
+
+    #[cfg(not(feature = "ssr"))]
+    fn filters(&self) -> [(&'static str, &Signal<String>); 4] {
+        [
+            ("ascendancy", &self.ascendancy),
+            ("skill", &self.skill),
+            ("keystone", &self.keystone),
+            ("min_level", &self.min_level),
+        ]
+    }
+
+    /// Restores the filters from the query string of the current page.
+    #[cfg(not(feature = "ssr"))]
+    fn restore(&self) {
+        let search = web_sys::window().unwrap().location().search().unwrap();
+        let params = web_sys::UrlSearchParams::new_with_str(&search).unwrap();
+
+        for (name, signal) in self.filters() {
+            if let Some(value) = params.get(name) {
+                signal.set(value);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "ssr"))]
+    fn query(&self, cursor: Option<&str>) -> String {
+        let params = web_sys::UrlSearchParams::new().unwrap();
+        for (name, signal) in self.filters() {
+            let value = signal.get();
+            if !value.trim().is_empty() {
+                params.append(name, value.trim());
+            }
+        }
+        if let Some(cursor) = cursor {
+            params.append("cursor", cursor);
+        }
+        params.to_string().into()
+    }
+
+    /// Loads the page of the last cursor in `pages`.
+    #[cfg(not(feature = "ssr"))]
+    fn load(&self) {
+        let cursor = self.pages.get().last().cloned().flatten();
+        let query = self.query(cursor.as_deref());
+
+        // Keep the filters in the url, so searches can be shared
+        let _ = web_sys::window()
+            .unwrap()
+            .history()
+            .unwrap()
+            .replace_state_with_url(
+                &wasm_bindgen::JsValue::NULL,
+                "",
+                Some(&format!("/search?{}", self.query(None))),
+            );
+
+        self.loading.set(true);
+        let state = self.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match crate::api::search(&query).await {
+                Ok(response) => {
+                    state.error.set(String::new());
+                    state.results.set(response.results);
+                    state.next.set(response.cursor);
+                }
+                Err(err) => {
+                    log::info!("{:?}", err);
+                    state.error.set(err.to_string());
+                }
+            }
+            state.loading.set(false);
+        });
+    }
+}
+
+#[component(SearchPage<G>)]
+pub fn search_page() -> View<G> {
+    let state = State::new();
+
+    #[cfg(not(feature = "ssr"))]
+    {
+        state.restore();
+        state.load();
+    }
+
+    #[cfg(not(feature = "ssr"))]
+    let (on_search, on_previous, on_next) = (
+        cloned!(state => move |_| {
+            state.pages.set(vec![None]);
+            state.load();
+        }),
+        cloned!(state => move |_| {
+            let mut pages = (*state.pages.get()).clone();
+            if pages.len() > 1 {
+                pages.pop();
+                state.pages.set(pages);
+                state.load();
+            }
+        }),
+        cloned!(state => move |_| {
+            if let Some(next) = (*state.next.get()).clone() {
+                let mut pages = (*state.pages.get()).clone();
+                pages.push(Some(next));
+                state.pages.set(pages);
+                state.load();
+            }
+        }),
+    );
+    #[cfg(feature = "ssr")]
+    let (on_search, on_previous, on_next) = (|_| {}, |_| {}, |_| {});
+
+    let State {
+        ascendancy,
+        skill,
+        keystone,
+        min_level,
+        results,
+        pages,
+        next,
+        loading,
+        error,
+    } = state;
+
+    let previous_disabled = memo!(loading, pages, *loading.get() || pages.get().len() <= 1);
+    let next_disabled = memo!(loading, next, *loading.get() || next.get().is_none());
+
+    let list = memo!(loading, results, {
+        let results = results.get();
+        if results.is_empty() && !*loading.get() {
+            return View::new_fragment(vec![
+                view! { li(class="text-slate-500") { "No builds found" } },
+            ]);
+        }
+
+        View::new_fragment(results.iter().cloned().map(render_entry).collect())
+    });
+
+    let keystones = View::new_fragment(
+        ::pob::Keystone::ALL
+            .iter()
+            .map(|keystone| view! { option(value=keystone.name()) })
+            .collect(),
+    );
+
+    view! {
+        div(class="flex flex-col gap-y-3") {
+            h1(class="dark:text-slate-100 text-slate-900") { "Search Builds" }
+            div(class="flex flex-col sm:flex-row gap-3") {
+                input(bind:value=ascendancy, type="text", placeholder="Class or Ascendancy", class=INPUT_CLASS)
+                input(bind:value=skill, type="text", placeholder="Main Skill", class=INPUT_CLASS)
+                input(bind:value=keystone, type="text", list="keystones", placeholder="Keystone", class=INPUT_CLASS)
+                datalist(id="keystones") { (keystones) }
+                input(bind:value=min_level, type="number", min=1, max=100, placeholder="Min. Level", class=INPUT_CLASS)
+                button(on:click=on_search, disabled=*loading.get(), class=BUTTON_CLASS) { "Search" }
+            }
+            div(class="text-red-500") { (*error.get()) }
+            ul(class="flex flex-col gap-y-2") {
+                (list.get().as_ref().clone())
+            }
+            div(class="flex justify-between") {
+                button(on:click=on_previous, disabled=*previous_disabled.get(), class=BUTTON_CLASS) { "Previous" }
+                button(on:click=on_next, disabled=*next_disabled.get(), class=BUTTON_CLASS) { "Next" }
+            }
+        }
+    }
+}
+
+fn render_entry<G: Html>(entry: SearchEntry) -> View<G> {
+    let href = format!("/{}", entry.id);
+    // Unknown classes have no image, keep the space so the titles line up
+    let image = match crate::assets::ascendancy_image(&entry.ascendancy_or_class) {
+        Some(src) => view! { img(src=src, width=40, height=40, class="rounded-full") {} },
+        None => view! { div(class="w-10 h-10 shrink-0") {} },
+    };
+    let details = std::iter::once(format!("Level {}", entry.level))
+        .chain(entry.main_skill)
+        .chain(entry.keystones)
+        .collect::<Vec<_>>()
+        .join(" · ");
+
+    view! {
+        li {
+            a(href=href, class="flex items-center gap-x-3 hover:underline") {
+                (image)
+                div(class="flex flex-col") {
+                    span(class="dark:text-slate-100 text-slate-900") { (entry.title) }
+                    span(class="text-sm text-slate-500") { (details) }
+                }
+            }
+        }
+    }
+}
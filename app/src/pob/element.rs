@@ -85,6 +85,10 @@ impl<'a> Element<'a> {
         self
     }
 
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
     pub fn add_to(self, v: &mut Vec<Self>) {
         v.push(self);
     }
@@ -93,6 +97,11 @@ impl<'a> Element<'a> {
         self.render_priv(StringRenderer::new())
     }
 
+    /// Renders the element into text spans with their formatting.
+    pub fn render_to_spans(self) -> Option<Vec<(&'static str, String)>> {
+        self.render_priv(SpanRenderer::new(""))
+    }
+
     pub fn render_to_view<G: GenericNode>(self) -> Option<View<G>> {
         self.render_priv(ViewRenderer::new())
     }
@@ -258,6 +267,52 @@ impl Renderer for StringRenderer {
     }
 }
 
+struct SpanRenderer {
+    formatting: &'static str,
+    spans: Vec<(&'static str, String)>,
+}
+
+impl SpanRenderer {
+    fn new(formatting: &'static str) -> Self {
+        Self {
+            formatting,
+            spans: Vec::new(),
+        }
+    }
+}
+
+impl Renderer for SpanRenderer {
+    type Output = Vec<(&'static str, String)>;
+
+    fn push<T>(&mut self, fragment: T)
+    where
+        T: Into<Fragment>,
+    {
+        let fragment = fragment.into();
+        let formatting = match fragment.formatting {
+            "" => self.formatting,
+            formatting => formatting,
+        };
+        let value = match fragment.typ {
+            FragmentType::Text => fragment.value,
+            FragmentType::Super => format!(" [{}]", fragment.value),
+        };
+        self.spans.push((formatting, value));
+    }
+
+    fn push_sub(&mut self, element: Self) {
+        self.spans.extend(element.spans);
+    }
+
+    fn sub(&mut self, formatting: &'static str) -> Self {
+        SpanRenderer::new(formatting)
+    }
+
+    fn finish(self) -> Self::Output {
+        self.spans
+    }
+}
+
 struct ViewRenderer<G: GenericNode> {
     formatting: &'static str,
     views: Vec<View<G>>,
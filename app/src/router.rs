@@ -15,6 +15,10 @@ use web_sys::Element;
 pub enum Route {
     #[to("/")]
     Index,
+    #[to("/recent")]
+    Recent,
+    #[to("/search")]
+    Search,
     #[to("/<id>")]
     Paste(<pages::paste::PastePage<DomNode> as RoutedComponent<DomNode>>::RouteArg),
     #[not_found]
@@ -26,13 +30,27 @@ impl Route {
         use sycamore_router::Route;
         Self::match_path(path)
     }
+
+    /// The route pattern without any parameters, e.g. to group requests by route.
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            Self::Index => "/",
+            Self::Recent => "/recent",
+            Self::Search => "/search",
+            Self::Paste(_) => "/<id>",
+            Self::NotFound => "<not_found>",
+        }
+    }
 }
 
 pub trait RoutedComponent<G: Html>: Component<G> {
     type RouteArg: Clone;
 
     fn from_context(ctx: Context) -> Result<<Self as Component<G>>::Props>;
-    fn from_hydration(element: Element) -> Result<<Self as Component<G>>::Props>;
+    fn from_hydration(
+        arg: Self::RouteArg,
+        element: Element,
+    ) -> Result<<Self as Component<G>>::Props>;
     fn from_dynamic<'a>(
         args: Self::RouteArg,
     ) -> LocalBoxFuture<'a, Result<<Self as Component<G>>::Props>>;
@@ -66,7 +84,7 @@ fn switch_none<G: Html>(route: ReadSignal<Route>) -> View<G> {
 fn switch<G: Html>(route: ReadSignal<Route>, ctx: Option<Context>) -> View<G> {
     // TODO: loading view?
     let view = Signal::new(View::empty());
-    let store = Signal::new("");
+    let store = Signal::new(String::new());
 
     effect!(view, store, {
         let route = route.get();
@@ -89,31 +107,44 @@ fn switch<G: Html>(route: ReadSignal<Route>, ctx: Option<Context>) -> View<G> {
         }
     });
 
-    view! { div(data-route=*store.get()) { (view.get().as_ref().clone()) } }
+    view! { div(data-route=(*store.get()).clone()) { (view.get().as_ref().clone()) } }
 }
 
 enum Page<G: Html> {
     Index,
+    Recent(<pages::RecentPage<G> as Component<G>>::Props),
+    Search,
     Paste(<pages::PastePage<G> as Component<G>>::Props),
     NotFound,
-    ServerError,
+    /// The paste was removed by a moderator.
+    Gone,
+    /// Unexpected error, with the id of the request if it was rendered on the server.
+    ServerError(Option<String>),
 }
 
 impl<G: Html> Page<G> {
     fn from_context(ctx: Context) -> Self {
+        let request_id = ctx.request_id().map(str::to_owned);
         let page = try_block! {
             Ok::<_, Error>(match ctx.route().unwrap() {
                 Route::Index => Self::Index,
+                Route::Recent => Self::Recent(pages::RecentPage::<G>::from_context(ctx)?),
+                Route::Search => Self::Search,
                 Route::Paste(_) => Self::Paste(pages::PastePage::<G>::from_context(ctx)?),
+                Route::NotFound if ctx.is_gone() => Self::Gone,
                 Route::NotFound => Self::NotFound,
             })
         };
 
-        let page = Self::resolve(page);
+        let page = match Self::resolve(page) {
+            Self::ServerError(_) => Self::ServerError(request_id),
+            page => page,
+        };
 
         match page {
             Self::NotFound => ResponseContext::set_status_code(404),
-            Self::ServerError => ResponseContext::set_status_code(500),
+            Self::Gone => ResponseContext::set_status_code(410),
+            Self::ServerError(_) => ResponseContext::set_status_code(500),
             _ => (),
         }
 
@@ -142,10 +173,7 @@ impl<G: Html> Page<G> {
         // Recover a page that was stored during SSR.
         // This usually happens when we were supposed to render a page, but the page threw an error
         // and it was in place redirected to an error page.
-        let stored_page = element
-            .get_attribute("data-route")
-            .as_deref()
-            .and_then(Self::restore);
+        let stored_page = element.get_attribute("data-route").and_then(Self::restore);
 
         if let Some(page) = stored_page {
             return page;
@@ -154,7 +182,14 @@ impl<G: Html> Page<G> {
         let page = try_block! {
             Ok::<_, Error>(match route {
                 Route::Index => Self::Index,
-                Route::Paste(_) => Self::Paste(pages::PastePage::<G>::from_hydration(element)?),
+                Route::Recent => {
+                    Self::Recent(pages::RecentPage::<G>::from_hydration((), element)?)
+                }
+                Route::Search => Self::Search,
+                Route::Paste(id) => Self::Paste(pages::PastePage::<G>::from_hydration(
+                    id.clone(),
+                    element,
+                )?),
                 Route::NotFound => Self::NotFound,
             })
         };
@@ -169,6 +204,8 @@ impl<G: Html> Page<G> {
         let page = try_block_async! {
             Ok::<_, Error>(match route {
                 Route::Index => Self::Index,
+                Route::Recent => Self::Recent(pages::RecentPage::<G>::from_dynamic(()).await?),
+                Route::Search => Self::Search,
                 Route::Paste(arg) => {
                     Self::Paste(pages::PastePage::<G>::from_dynamic(arg.clone()).await?)
                 }
@@ -206,32 +243,40 @@ impl<G: Html> Page<G> {
         // e.g. not found page displaying the resource type
         match err {
             Error::NotFound(_, _) => Self::NotFound,
-            _ => Self::ServerError,
+            _ => Self::ServerError(None),
         }
     }
 
     fn meta(&self) -> Result<Meta> {
         match self {
             Self::Index => Ok(Meta::index()),
+            Self::Recent(ref props) => pages::RecentPage::<G>::meta(props),
+            Self::Search => Ok(Meta::search()),
             Self::Paste(ref props) => pages::PastePage::<G>::meta(props),
             Self::NotFound => Ok(Meta::not_found()),
-            Self::ServerError => Ok(Meta::server_error()),
+            Self::Gone => Ok(Meta::gone()),
+            Self::ServerError(_) => Ok(Meta::server_error()),
         }
     }
 
-    fn store(&self) -> Option<&'static str> {
+    fn store(&self) -> Option<String> {
         match self {
-            Self::NotFound => Some("not_found"),
-            Self::ServerError => Some("server_error"),
+            Self::NotFound => Some("not_found".to_owned()),
+            Self::Gone => Some("gone".to_owned()),
+            Self::ServerError(None) => Some("server_error".to_owned()),
+            Self::ServerError(Some(request_id)) => Some(format!("server_error:{}", request_id)),
             _ => None,
         }
     }
 
-    fn restore<'a>(previous: impl Into<Option<&'a str>>) -> Option<Self> {
-        match previous.into()? {
+    fn restore(previous: String) -> Option<Self> {
+        match previous.as_str() {
             "not_found" => Some(Self::NotFound),
-            "server_error" => Some(Self::ServerError),
-            _ => None,
+            "gone" => Some(Self::Gone),
+            "server_error" => Some(Self::ServerError(None)),
+            previous => previous
+                .strip_prefix("server_error:")
+                .map(|request_id| Self::ServerError(Some(request_id.to_owned()))),
         }
     }
 }
@@ -241,14 +286,36 @@ fn render<G: Html>(page: Page<G>) -> View<G> {
         Page::Index => view! {
             pages::IndexPage()
         },
+        Page::Recent(props) => view! {
+            pages::RecentPage(props)
+        },
+        Page::Search => view! {
+            pages::SearchPage()
+        },
         Page::Paste(props) => view! {
             pages::PastePage(props)
         },
         Page::NotFound => view! {
             "404 Not Found"
         },
-        Page::ServerError => view! {
-            "Unknown Error"
+        Page::Gone => view! {
+            "410 Gone"
         },
+        Page::ServerError(request_id) => {
+            let request_id = match request_id {
+                Some(request_id) => view! {
+                    div(class="mt-2 text-sm text-slate-500") {
+                        "Please include the request id "
+                        span(class="font-mono select-all") { (request_id) }
+                        " when reporting this error."
+                    }
+                },
+                None => View::empty(),
+            };
+            view! {
+                div { "Unknown Error" }
+                (request_id)
+            }
+        }
     }
 }
@@ -15,6 +15,11 @@ macro_rules! memo {
             $x
         }))
     };
+    ($signal1:ident, $signal2:ident, $signal3:ident, $x:expr) => {
+        create_memo(cloned!(($signal1, $signal2, $signal3) => move || {
+            $x
+        }))
+    };
 }
 
 #[macro_export]
@@ -77,6 +82,21 @@ macro_rules! async_callback {
     }};
 }
 
+/// The host of an url, e.g. `pastebin.com` for `https://www.pastebin.com/foo`.
+pub fn host(url: &str) -> &str {
+    let url = url.split_once("://").map(|(_, url)| url).unwrap_or(url);
+    let host = url.split(&['/', '?', '#'][..]).next().unwrap_or(url);
+    host.strip_prefix("www.").unwrap_or(host)
+}
+
+/// Value of a query parameter of the current page, `None` outside of a browser.
+pub fn query_param(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get(name)
+}
+
 pub fn is_hydrating() -> bool {
     sycamore::utils::hydrate::get_current_id().is_some()
 }
@@ -21,6 +21,15 @@ else
 fi
 
 trunk build $TRUNK_ARGS
+
+if [[ "$1" == "--release" ]]; then
+    # Precompressed variants of the app bundle, see worker/src/assets.rs
+    find app/dist -type f \( -name '*.js' -o -name '*.wasm' -o -name '*.css' -o -name '*.svg' \) -exec gzip -9 -k -f {} \;
+    if command -v brotli > /dev/null; then
+        find app/dist -type f \( -name '*.js' -o -name '*.wasm' -o -name '*.css' -o -name '*.svg' \) -exec brotli -q 11 -k -f {} \;
+    fi
+fi
+
 cd worker
 worker-build $WORKER_BUILD_ARGS
 
@@ -33,6 +42,33 @@ export function get_asset(name) {
 }
 EOF
 
+# Precompressed bodies must be sent with encodeBody "manual", which workers-rs does not expose,
+# see ENCODED_BODY_HEADER in worker/src/platform/cloudflare.rs
+cat <<EOF > build/worker/entry.mjs
+import worker from "./shim.mjs";
+export * from "./shim.mjs";
+
+const ENCODED_BODY_HEADER = "X-Encoded-Body";
+
+export default {
+    ...worker,
+    async fetch(request, env, ctx) {
+        const response = await worker.fetch(request, env, ctx);
+        if (!response.headers.has(ENCODED_BODY_HEADER)) {
+            return response;
+        }
+
+        const headers = new Headers(response.headers);
+        headers.delete(ENCODED_BODY_HEADER);
+        return new Response(response.body, {
+            status: response.status,
+            headers,
+            encodeBody: "manual",
+        });
+    },
+};
+EOF
+
 cat <<EOF > build/worker/export_wasm.mjs
 import * as index_bg from "./index_bg.mjs";
 import _wasm from "./index_bg.wasm";
@@ -1,3 +1,4 @@
+use quick_xml::{de::DeError, events::Event, Reader};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -11,5 +12,94 @@ pub enum Error {
     Deflate(#[from] std::io::Error),
 
     #[error("failed to parse input XML: {0:?}")]
-    ParseXml(#[from] quick_xml::de::DeError),
+    ParseXml(#[source] DeError, XmlLocation),
+}
+
+impl Error {
+    /// Stable identifier of the error, e.g. for API clients.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Base64Decode(..) => "invalid_base64",
+            Self::Deflate(..) => "invalid_compression",
+            Self::ParseXml(DeError::Xml(..) | DeError::Eof, _) => "invalid_xml",
+            Self::ParseXml(_, location) if location.field.is_some() => "missing_xml_element",
+            Self::ParseXml(..) => "invalid_xml_content",
+        }
+    }
+
+    /// Byte offset into the input at which the error occurred, if known.
+    ///
+    /// The offset refers to the base64 input for decoding errors and to the decompressed
+    /// XML for XML errors.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::Base64Decode(
+                base64::DecodeError::InvalidByte(offset, _)
+                | base64::DecodeError::InvalidLastSymbol(offset, _),
+            ) => Some(*offset),
+            Self::ParseXml(_, location) => location.offset,
+            _ => None,
+        }
+    }
+
+    pub fn xml_location(&self) -> Option<&XmlLocation> {
+        match self {
+            Self::ParseXml(_, location) => Some(location),
+            _ => None,
+        }
+    }
+}
+
+/// Where parsing the XML failed, as far as it can be determined.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmlLocation {
+    /// Byte offset of a syntax error or the end of a truncated document.
+    pub offset: Option<usize>,
+    /// Open elements at the error, e.g. `PathOfBuilding/Skills/Skill`.
+    pub path: Option<String>,
+    /// Name of a missing element or attribute.
+    pub field: Option<String>,
+}
+
+impl XmlLocation {
+    /// Locates the error in the XML document.
+    ///
+    /// The deserializer does not expose its position, the document is scanned again to find
+    /// syntax errors and unclosed elements.
+    pub(crate) fn locate(xml: &str, err: &DeError) -> Self {
+        let field = match err {
+            DeError::Custom(message) => message
+                .strip_prefix("missing field `")
+                .and_then(|field| field.strip_suffix('`'))
+                .map(str::to_owned),
+            _ => None,
+        };
+
+        let mut reader = Reader::from_str(xml);
+        let mut buf = Vec::new();
+        let mut path = Vec::new();
+        let offset = loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(start)) => {
+                    path.push(String::from_utf8_lossy(start.name()).into_owned())
+                }
+                Ok(Event::End(_)) => {
+                    path.pop();
+                }
+                Ok(Event::Eof) if path.is_empty() => break None,
+                Ok(Event::Eof) => break Some(xml.len()),
+                Err(_) => break Some(reader.buffer_position()),
+                _ => (),
+            }
+            buf.clear();
+        };
+
+        Self {
+            offset,
+            path: offset
+                .map(|_| path.join("/"))
+                .filter(|path| !path.is_empty()),
+            field,
+        }
+    }
 }
@@ -8,7 +8,7 @@ mod stats;
 mod utils;
 
 pub use self::config::{Config, ConfigValue};
-pub use self::error::{Error, Result};
+pub use self::error::{Error, Result, XmlLocation};
 pub use self::passives::Keystone;
 pub use self::serde::SerdePathOfBuilding;
 pub use self::stats::Stat;
@@ -8,6 +8,23 @@ pub enum Keystone {
 }
 
 impl Keystone {
+    /// All supported keystones.
+    pub const ALL: [Keystone; 4] = [
+        Self::ChaosInoculation,
+        Self::EldritchBattery,
+        Self::ElementalOverload,
+        Self::MindOverMatter,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ChaosInoculation => "Chaos Inoculation",
+            Self::EldritchBattery => "Eldritch Battery",
+            Self::ElementalOverload => "Elemental Overload",
+            Self::MindOverMatter => "Mind Over Matter",
+        }
+    }
+
     pub(crate) fn node(&self) -> u32 {
         match self {
             Self::ChaosInoculation => 11455,
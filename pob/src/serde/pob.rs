@@ -1,5 +1,5 @@
 use crate::serde::model::*;
-use crate::{Config, ConfigValue, Error, Keystone, Result, Stat};
+use crate::{error::XmlLocation, Config, ConfigValue, Error, Keystone, Result, Stat};
 
 #[derive(Debug)]
 pub struct SerdePathOfBuilding {
@@ -9,7 +9,10 @@ pub struct SerdePathOfBuilding {
 
 impl SerdePathOfBuilding {
     pub fn from_xml(s: &str) -> Result<Self> {
-        let pob = quick_xml::de::from_str(s).map_err(Error::ParseXml)?;
+        let pob = quick_xml::de::from_str(s).map_err(|err| {
+            let location = XmlLocation::locate(s, &err);
+            Error::ParseXml(err, location)
+        })?;
         Ok(Self { pob })
     }
 
@@ -253,4 +256,27 @@ mod tests {
 
         // TODO: test configs
     }
+
+    #[test]
+    fn parse_errors() {
+        let err = SerdePathOfBuilding::from_export("abc!").unwrap_err();
+        assert_eq!("invalid_base64", err.code());
+        assert_eq!(Some(3), err.offset());
+
+        let err = SerdePathOfBuilding::from_export("YWJj").unwrap_err();
+        assert_eq!("invalid_compression", err.code());
+
+        let xml = "<PathOfBuilding><Build level=\"1\"></Skills></PathOfBuilding>";
+        let err = SerdePathOfBuilding::from_xml(xml).unwrap_err();
+        assert_eq!("invalid_xml", err.code());
+        let location = err.xml_location().unwrap();
+        assert_eq!(Some(35), location.offset);
+        assert_eq!(Some("PathOfBuilding/Build"), location.path.as_deref());
+
+        let err = SerdePathOfBuilding::from_xml("<PathOfBuilding></PathOfBuilding>").unwrap_err();
+        assert_eq!("missing_xml_element", err.code());
+        let location = err.xml_location().unwrap();
+        assert!(location.field.is_some());
+        assert_eq!(None, location.offset);
+    }
 }
@@ -0,0 +1,189 @@
+use crate::{
+    api_key::{self, ApiKey, IssueRequest, Usage},
+    cache::{self, Namespace},
+    consts, crypto,
+    moderation::{self, Action},
+    storage,
+    store::{Store, WorkerKv},
+    utils::{self, ResponseExt},
+    Error, Result,
+};
+use serde::Serialize;
+use worker::{Env, Method, Request, Response};
+
+const PREFIX: &str = "/api/v1/admin/";
+
+pub async fn try_handle(req: &mut Request, env: &Env) -> Result<Option<Response>> {
+    let path = req.path();
+    let segments = match path.strip_prefix(PREFIX) {
+        Some(path) => path.split('/').collect::<Vec<_>>(),
+        None => return Ok(None),
+    };
+
+    authorize(req, env)?;
+
+    let moderation = WorkerKv::new(env, consts::KV_MODERATION)?;
+    let index = WorkerKv::new(env, consts::KV_PASTE_INDEX)?;
+    let generations = WorkerKv::new(env, consts::KV_CACHE_GENERATIONS)?;
+    let url = req.url()?;
+
+    let response = match (req.method(), segments.as_slice()) {
+        (Method::Get, ["reports"]) => {
+            let cursor = utils::query_param(&url, "cursor").filter(|c| !c.is_empty());
+            let reports = moderation::reports(&moderation, cursor, 100).await?;
+            return Response::from_json(&reports)?.cache_private().map(Some);
+        }
+        (Method::Delete, ["reports", id]) => {
+            moderation::dismiss(&moderation, id).await?;
+            Done::new(id, "dismissed")
+        }
+        (Method::Post, ["paste", id, "hide"]) => {
+            moderation::block(&moderation, id, Action::Hidden).await?;
+            moderation::unlist(&moderation, &index, id, Action::Hidden).await?;
+            purge(&generations).await?;
+            Done::new(id, "hidden")
+        }
+        (Method::Post, ["paste", id, "unhide"]) => {
+            moderation::unblock(&moderation, id).await?;
+            moderation::relist(&moderation, &index, id).await?;
+            purge(&generations).await?;
+            Done::new(id, "unhidden")
+        }
+        (Method::Delete, ["paste", id]) => {
+            storage::delete(env, &utils::to_path(id)?).await?;
+            moderation::block(&moderation, id, Action::Deleted).await?;
+            moderation::unlist(&moderation, &index, id, Action::Deleted).await?;
+            purge(&generations).await?;
+            Done::new(id, "deleted")
+        }
+        (Method::Post, ["paste", id, "purge"]) => {
+            purge(&generations).await?;
+            Done::new(id, "purged")
+        }
+        (Method::Post, ["cache", namespace, "purge"]) => {
+            let namespace = Namespace::parse(namespace)
+                .ok_or_else(|| Error::NotFound("cache namespace", namespace.to_string()))?;
+            let generation = cache::purge(&generations, namespace).await?;
+            let purged = PurgedNamespace {
+                namespace: namespace.name(),
+                generation,
+            };
+            return Response::from_json(&purged)?.cache_private().map(Some);
+        }
+        (Method::Get, ["keys"]) => {
+            let cursor = utils::query_param(&url, "cursor").filter(|c| !c.is_empty());
+            let keys = api_key::list(&moderation, cursor, 100).await?;
+            return Response::from_json(&keys)?.cache_private().map(Some);
+        }
+        (Method::Post, ["keys"]) => {
+            let request: IssueRequest = req.json().await?;
+            let issued = issue(&moderation, request).await?;
+            return Response::from_json(&issued)?.cache_private().map(Some);
+        }
+        (Method::Delete, ["keys", id]) => {
+            api_key::revoke(&moderation, id).await?;
+            Done::new(id, "revoked")
+        }
+        _ => return Err(Error::NotFound("route", path.to_owned())),
+    };
+
+    Response::from_json(&response)?.cache_private().map(Some)
+}
+
+/// The pattern of the admin route handling the request, `None` if it is not an admin request.
+pub fn route(req: &Request) -> Option<&'static str> {
+    let path = req.path();
+    let segments = path.strip_prefix(PREFIX)?.split('/').collect::<Vec<_>>();
+
+    let route = match (req.method(), segments.as_slice()) {
+        (Method::Get, ["reports"]) => "/api/v1/admin/reports",
+        (Method::Delete, ["reports", _]) => "/api/v1/admin/reports/<id>",
+        (Method::Post, ["paste", _, "hide"]) => "/api/v1/admin/paste/<id>/hide",
+        (Method::Post, ["paste", _, "unhide"]) => "/api/v1/admin/paste/<id>/unhide",
+        (Method::Delete, ["paste", _]) => "/api/v1/admin/paste/<id>",
+        (Method::Post, ["paste", _, "purge"]) => "/api/v1/admin/paste/<id>/purge",
+        (Method::Post, ["cache", _, "purge"]) => "/api/v1/admin/cache/<namespace>/purge",
+        (Method::Get, ["keys"]) => "/api/v1/admin/keys",
+        (Method::Post, ["keys"]) => "/api/v1/admin/keys",
+        (Method::Delete, ["keys", _]) => "/api/v1/admin/keys/<id>",
+        _ => "/api/v1/admin/<unknown>",
+    };
+
+    Some(route)
+}
+
+#[derive(Serialize)]
+struct Done<'a> {
+    id: &'a str,
+    action: &'static str,
+}
+
+impl<'a> Done<'a> {
+    fn new(id: &'a str, action: &'static str) -> Self {
+        Self { id, action }
+    }
+}
+
+#[derive(Serialize)]
+struct PurgedNamespace {
+    namespace: &'static str,
+    generation: u64,
+}
+
+/// A newly issued API key, the key itself is not stored and can not be shown again.
+#[derive(Serialize)]
+struct Issued {
+    key: String,
+    #[serde(flatten)]
+    entry: api_key::Entry,
+}
+
+async fn issue(moderation: &dyn Store, request: IssueRequest) -> Result<Issued> {
+    let api_key = ApiKey::new(request, worker::Date::now().as_millis())?;
+
+    let mut random = [0; consts::API_KEY_BYTES];
+    crypto::fill_random(&mut random)?;
+    let key = base64::encode_config(random, base64::URL_SAFE_NO_PAD);
+
+    let entry = api_key::Entry {
+        id: utils::hex(&crypto::sha1(&mut key.clone().into_bytes()).await?),
+        key: api_key,
+        usage: Usage::default(),
+    };
+    api_key::put(moderation, &entry).await?;
+
+    Ok(Issued { key, entry })
+}
+
+/// Verifies the bearer token against the configured admin token.
+///
+/// The admin API is disabled if no token is configured.
+fn authorize(req: &Request, env: &Env) -> Result<()> {
+    let expected = env
+        .secret(consts::ENV_ADMIN_TOKEN)
+        .map(|token| token.to_string())
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or(Error::Unauthorized)?;
+
+    let token = req.headers().get("Authorization")?.unwrap_or_default();
+    let token = token.strip_prefix("Bearer ").unwrap_or_default();
+
+    match utils::constant_time_eq(token.as_bytes(), expected.as_bytes()) {
+        true => Ok(()),
+        false => Err(Error::Unauthorized),
+    }
+}
+
+/// Invalidates all cached responses of a paste.
+///
+/// Deleting entries from the edge cache only affects the current colo and misses urls with
+/// query parameters, e.g. view keys or oEmbed, a new generation invalidates them everywhere.
+/// Assets stay cached, they are only purged by `/cache/static/purge`.
+async fn purge(generations: &dyn Store) -> Result<()> {
+    for namespace in Namespace::PASTES {
+        cache::purge(generations, namespace).await?;
+    }
+
+    Ok(())
+}
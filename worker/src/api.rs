@@ -1,40 +1,136 @@
 use crate::{
-    consts, crypto, storage,
+    api_key, consts,
+    http::{Method, Request, Response},
+    import,
+    memo::Memo,
+    metrics,
+    moderation::{self, Report},
+    platform::Platform,
+    ratelimit::{self, Limit},
+    recent, search, status,
+    storage::{self, Pastes},
     utils::{self, ResponseExt},
-    Error, Result,
+    webhook, Error, Result,
 };
-use pob::SerdePathOfBuilding;
-use serde::Serialize;
-use worker::{Env, Headers, Method, Request, Response};
+use pob::{PathOfBuildingExt, SerdePathOfBuilding};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+
+thread_local! {
+    /// Ids of public pastes by their hex encoded SHA-1, see [`resolve_id`].
+    static RESOLVED: Rc<Memo<String>> = Rc::new(Memo::new(
+        consts::RESOLVED_IDS_TTL,
+        consts::RESOLVED_IDS_CAPACITY,
+    ));
+    static REPORTS: Limit = Limit::new(
+        consts::REPORTS_PER_WINDOW,
+        consts::REPORTS_WINDOW,
+        consts::REPORTS_CLIENTS_CAPACITY,
+    );
+    static UPLOADS: Limit = Limit::new(
+        consts::UPLOADS_PER_WINDOW,
+        consts::UPLOADS_WINDOW,
+        consts::UPLOADS_CLIENTS_CAPACITY,
+    );
+}
 
 #[derive(Serialize)]
-struct Upload {
+struct UploadResponse {
     id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    view_key: Option<String>,
+}
+
+/// Structured upload, either as JSON or as multipart form.
+#[derive(Deserialize)]
+struct UploadRequest {
+    content: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    private: bool,
+    /// Generate a key required to view the paste, implies `private`.
+    #[serde(default)]
+    view_key: bool,
+}
+
+#[derive(Deserialize)]
+struct ReportRequest {
+    reason: String,
 }
 
-pub async fn try_handle(req: &mut Request, env: &Env) -> Result<Option<Response>> {
+struct Upload {
+    data: Vec<u8>,
+    metadata: storage::Metadata,
+    view_key: bool,
+}
+
+pub async fn try_handle(platform: &dyn Platform, req: &Request) -> Result<Option<Response>> {
     // TODO: use a sycamore router for this?
     if req.path() == "/api/v1/paste/" && req.method() == Method::Post {
-        return handle_upload(req, env, true).await.map(Some);
+        return handle_upload(platform, req, true).await.map(Some);
     }
     if req.path() == "/pob/" && req.method() == Method::Post {
-        return handle_upload(req, env, false).await.map(Some);
+        return handle_upload(platform, req, false).await.map(Some);
+    }
+    if req.path() == "/api/v1/recent" && req.method() == Method::Get {
+        return handle_recent(platform).await.map(Some);
+    }
+    if req.path() == "/api/v1/search" && req.method() == Method::Get {
+        return handle_search(platform, req).await.map(Some);
+    }
+    if req.path() == "/api/v1/status" && req.method() == Method::Get {
+        return status::handle(platform).await.map(Some);
+    }
+    if let Some(id) = is_report_url(req.method(), req.path()) {
+        return handle_report(platform, req, id).await.map(Some);
     }
-    if let Some(id) = is_download_url(&req.path(), req) {
-        return handle_download(env, id).await.map(Some);
+    if let Some(id) = is_download_url(req.method(), req.path()) {
+        return handle_download(platform, req, id).await.map(Some);
     }
 
     Ok(None)
 }
 
-fn is_download_url<'a>(path: &'a str, req: &Request) -> Option<&'a str> {
-    if req.method() != Method::Get {
+/// The pattern of the API route handling the request, `None` if it is not an API request.
+pub fn route(method: Method, path: &str) -> Option<&'static str> {
+    match (method, path) {
+        (Method::Post, "/api/v1/paste/") => Some("/api/v1/paste/"),
+        (Method::Post, "/pob/") => Some("/pob/"),
+        (Method::Get, "/api/v1/recent") => Some("/api/v1/recent"),
+        (Method::Get, "/api/v1/search") => Some("/api/v1/search"),
+        (Method::Get, "/api/v1/status") => Some("/api/v1/status"),
+        (Method::Post, path) if is_report_url(method, path).is_some() => {
+            Some("/api/v1/paste/<id>/report")
+        }
+        (Method::Get, path) if is_pob_download_url(path).is_some() => Some("/pob/<id>"),
+        (Method::Get, path) if is_raw_download_url(path).is_some() => Some("/<id>/raw"),
+        _ => None,
+    }
+}
+
+fn is_download_url(method: Method, path: &str) -> Option<&str> {
+    if method != Method::Get {
         return None;
     }
 
     is_pob_download_url(path).or_else(|| is_raw_download_url(path))
 }
 
+fn is_report_url(method: Method, path: &str) -> Option<&str> {
+    if method != Method::Post {
+        return None;
+    }
+
+    path.strip_prefix("/api/v1/paste/")?
+        .strip_suffix("/report")
+        .filter(|id| !id.is_empty() && !id.contains('/'))
+}
+
 fn is_raw_download_url(path: &str) -> Option<&str> {
     path.trim_start_matches('/')
         .split_once('/')
@@ -48,54 +144,503 @@ fn is_pob_download_url(path: &str) -> Option<&str> {
         .map(|(_, id)| id)
 }
 
-async fn handle_download(env: &Env, id: &str) -> Result<Response> {
-    let path = utils::to_path(id)?;
+async fn handle_download(platform: &dyn Platform, req: &Request, id: &str) -> Result<Response> {
+    utils::to_path(id)?;
+    moderation::ensure_available(platform.moderation(), id).await?;
 
-    let response = storage::get(env, &path)
+    let paste = platform
+        .pastes()
+        .get(id)
         .await?
         .ok_or_else(|| Error::NotFound("paste", id.to_owned()))?;
 
-    response
-        .with_headers(Headers::new())
-        .with_content_type("text/plain")?
-        .cache_for(31536000)
+    let key = req.query_param("key");
+    paste.verify_access(platform, id, key.as_deref()).await?;
+
+    // Always respond with the exact stored export, the response is cached by url only and
+    // must not depend on the encoding of the request, the edge compresses plain text itself.
+    let response = Response::from_bytes(paste.data).with_content_type("text/plain")?;
+
+    match paste.metadata.private {
+        true => response.cache_private(),
+        false => response.cache_for(31536000),
+    }
+}
+
+async fn handle_report(platform: &dyn Platform, req: &Request, id: &str) -> Result<Response> {
+    let id = id.to_owned();
+    utils::to_path(&id).map_err(|_| Error::NotFound("paste", id.clone()))?;
+
+    let request = req
+        .json::<ReportRequest>()
+        .map_err(|e| Error::BadRequest(format!("Invalid report: {}", e)))?;
+    let reason = request.reason.trim();
+    if reason.is_empty() {
+        return Err(Error::BadRequest("Missing reason".to_owned()));
+    }
+    if reason.chars().count() > consts::MAX_REPORT_LENGTH {
+        return Err(Error::BadRequest(format!(
+            "Reason must not be longer than {} characters",
+            consts::MAX_REPORT_LENGTH
+        )));
+    }
+
+    if let Some(client) = ratelimit::client(req) {
+        REPORTS.with(|limit| limit.check(client, platform.now()))?;
+    }
+    if !platform.pastes().exists(&id).await? {
+        return Err(Error::NotFound("paste", id));
+    }
+
+    let report = Report {
+        id,
+        reason: reason.to_owned(),
+        created: platform.now(),
+    };
+    moderation::report(platform.moderation(), &report).await?;
+    log::info!("paste '{}' reported", report.id);
+
+    Ok(Response::empty().with_status(204))
 }
 
-async fn handle_upload(req: &mut Request, env: &Env, return_json: bool) -> Result<Response> {
-    let mut data = req.bytes().await?;
+async fn handle_upload(
+    platform: &dyn Platform,
+    req: &Request,
+    return_json: bool,
+) -> Result<Response> {
+    // The PoB client only ever sends the plain export
+    let Upload {
+        mut data,
+        mut metadata,
+        view_key,
+    } = match return_json {
+        true => read_upload(req)?,
+        false => Upload {
+            data: req.body().to_vec(),
+            metadata: Default::default(),
+            view_key: false,
+        },
+    };
 
-    if data.len() > consts::MAX_UPLOAD_SIZE {
+    // Keyed uploads have their own size limit and are limited by the daily quota instead
+    let mut api_key = api_key::authenticate(platform, req).await?;
+    let max_upload_size = match &api_key {
+        Some(entry) => {
+            // Exhausted keys are rejected right away, the upload is counted once it is valid
+            entry.check_quota(platform.now())?;
+            entry.key.max_upload_size
+        }
+        None => {
+            if let Some(client) = ratelimit::client(req) {
+                UPLOADS.with(|limit| limit.check(client, platform.now()))?;
+            }
+            consts::MAX_UPLOAD_SIZE
+        }
+    };
+
+    if data.len() > max_upload_size {
         return Err(Error::BadRequest("Paste too large".to_owned()));
     }
+    metadata.uploader = api_key.as_ref().map(|entry| entry.key.name.clone());
+
+    if import::is_import(&data) {
+        let url =
+            String::from_utf8(data).map_err(|_| Error::BadRequest("Invalid url".to_owned()))?;
+        data = import::fetch(platform.fetcher(), &url).await?.into_bytes();
+        metadata.source = Some(url.trim().to_owned());
+    }
 
     let s = std::str::from_utf8(&data)
         .map_err(|_| "invalid content".to_owned())
         .map_err(Error::BadRequest)?;
 
-    // Generic 401, probably just actually bad data
-    let s = pob::decompress(s).map_err(|e| Error::BadRequest(e.to_string()))?;
-    // More specific error for a separate Sentry categoy
-    let _ =
-        SerdePathOfBuilding::from_xml(&s).map_err(move |e| Error::InvalidPoB(e.to_string(), s))?;
+    // Generic 400, probably just actually bad data
+    let s = pob::decompress(s).map_err(Error::Pob)?;
+    // More specific error for a separate Sentry categoy, the message is only sent to Sentry
+    let pob = SerdePathOfBuilding::from_xml(&s).map_err(move |e| {
+        metrics::emit(metrics::Event::ParseFailure { code: e.code() });
+        Error::InvalidPoB(e, s)
+    })?;
+
+    let view_key = match view_key {
+        true => {
+            let key = utils::random_id::<{ consts::VIEW_KEY_BYTES }>(platform)?;
+            let hash = platform.sha1(key.as_bytes()).await?;
+            metadata.view_key = Some(utils::hex(&hash));
+            metadata.private = true;
+            Some(key)
+        }
+        false => None,
+    };
+
+    let sha1 = platform.sha1(&storage::identity(&data, &metadata)?).await?;
+    let paste_id = match metadata.private {
+        true => random_id(platform).await?,
+        false => {
+            let resolved = RESOLVED.with(Rc::clone);
+            resolve_id(platform.pastes(), &resolved, platform.now(), &sha1).await?
+        }
+    };
+
+    // Uploads of an existing paste count as well, they cost the same parsing and lookups
+    if let Some(entry) = &mut api_key {
+        api_key::reserve_upload(entry, platform.now())?;
+        if let Err(err) = api_key::put_usage(platform.moderation(), entry).await {
+            log::warn!(
+                "failed to record usage of api key '{}': {}",
+                entry.key.name,
+                err
+            );
+        }
+    }
+    let (id, stored) = match paste_id {
+        PasteId::Existing(id) => {
+            log::debug!("paste '{}' already exists, skipping upload", id);
+            (id, false)
+        }
+        PasteId::New(id) => {
+            log::debug!("--> uploading paste '{}'", id);
+            platform
+                .pastes()
+                .put(&id, &sha1, &mut data, &metadata)
+                .await?;
+            log::debug!("<-- paste uploaded");
+
+            if !metadata.private {
+                let resolved = RESOLVED.with(Rc::clone);
+                resolved.insert(&utils::hex(&sha1), id.clone(), platform.now());
+            }
+
+            (id, true)
+        }
+    };
 
-    let sha1 = crypto::sha1(&mut data).await?;
-    let id = utils::hash_to_short_id(&sha1, 9)?;
-    let filename = utils::to_path(&id)?;
+    if stored && !metadata.private {
+        index(platform, &id, &pob, metadata.title.clone());
+
+        let url = format!("{}/{}", req.url().origin().ascii_serialization(), id);
+        let paste = webhook::Paste::new(&id, url, metadata.title.clone(), platform.now(), &pob);
+        webhook::notify(platform, &paste);
+    }
 
-    log::debug!("--> uploading paste '{}' to '{}'", id, filename);
-    storage::put(env, &filename, &sha1, &mut data).await?;
-    log::debug!("<-- paste uploaded");
+    metrics::emit(metrics::Event::Upload {
+        class: pob.ascendancy_or_class_name(),
+        bytes: data.len(),
+        stored,
+    });
 
     if !return_json {
         // for pob API
-        return Ok(Response::ok(id)?);
+        return Ok(Response::ok(id));
     }
 
-    // Weird garbage data bug, but this seems to make it better
-    let response = serde_json::to_string(&Upload { id })?;
-    let mut response = Response::from_bytes(response.into_bytes())?;
-    response
-        .headers_mut()
-        .set("Content-Type", "application/json")?;
-    Ok(response)
+    Response::from_json(&UploadResponse { id, view_key })
+}
+
+/// Adds a public paste to the search index and the list of recent pastes,
+/// without delaying the response.
+fn index(platform: &dyn Platform, id: &str, pob: &SerdePathOfBuilding, title: Option<String>) {
+    let created = platform.now();
+    let info = app::PasteInfo::new(pob);
+    let title = title.unwrap_or(info.title);
+
+    let entry = search::Entry::new(id.to_owned(), created, pob, title.clone());
+    let paste = app::RecentPaste {
+        id: id.to_owned(),
+        title,
+        ascendancy_or_class: info.ascendancy_or_class,
+        main_skill: info.main_skill,
+        summary: info.summary,
+        created,
+    };
+
+    let store = platform.shared_index();
+    platform.wait_until(Box::pin(async move {
+        let result: Result<()> = async {
+            search::add(&*store, &entry).await?;
+            recent::add(&*store, paste).await
+        }
+        .await;
+        if let Err(err) = result {
+            log::warn!("failed to index paste '{}': {}", entry.id, err);
+        }
+    }));
+}
+
+async fn handle_recent(platform: &dyn Platform) -> Result<Response> {
+    let recent = recent::list(platform.index()).await?;
+    Response::from_json(&recent)?.cache_for(consts::CACHE_RECENT)
+}
+
+async fn handle_search(platform: &dyn Platform, req: &Request) -> Result<Response> {
+    let query = search::Query::from_pairs(req.url().query_pairs())?;
+
+    let limit = match req.query_param("limit") {
+        Some(limit) => limit
+            .parse::<usize>()
+            .map_err(|_| Error::BadRequest(format!("Invalid limit '{}'", limit)))?
+            .clamp(1, consts::SEARCH_MAX_PAGE_SIZE),
+        None => consts::SEARCH_PAGE_SIZE,
+    };
+    let cursor = req
+        .query_param("cursor")
+        .filter(|cursor| !cursor.is_empty());
+
+    let page = search::search(platform.index(), &query, cursor, limit).await?;
+
+    Response::from_json(&page)?.cache_for(consts::CACHE_SEARCH)
+}
+
+/// Reads an upload, which is either a plain export, JSON or a multipart form.
+fn read_upload(req: &Request) -> Result<Upload> {
+    let upload = match req.content_type().as_str() {
+        "application/json" => req
+            .json::<UploadRequest>()
+            .map_err(|e| Error::BadRequest(format!("Invalid upload: {}", e)))?,
+        "multipart/form-data" => {
+            let form = req.form()?;
+            let field = |name: &str| form.text(name);
+
+            let content = match form.get("content") {
+                Some(content) => String::from_utf8(content.to_vec())
+                    .map_err(|_| Error::BadRequest("invalid content".to_owned()))?,
+                None => return Err(Error::BadRequest("Missing content".to_owned())),
+            };
+
+            let flag = |name: &str| matches!(field(name).as_deref(), Some("true" | "on" | "1"));
+
+            UploadRequest {
+                content,
+                title: field("title"),
+                description: field("description"),
+                tags: field("tags")
+                    .map(|tags| tags.split(',').map(ToOwned::to_owned).collect())
+                    .unwrap_or_default(),
+                private: flag("private"),
+                view_key: flag("view_key"),
+            }
+        }
+        _ => {
+            return Ok(Upload {
+                data: req.body().to_vec(),
+                metadata: Default::default(),
+                view_key: false,
+            })
+        }
+    };
+
+    let mut metadata = to_metadata(upload.title, upload.description, upload.tags)?;
+    metadata.private = upload.private;
+
+    Ok(Upload {
+        data: upload.content.into_bytes(),
+        metadata,
+        view_key: upload.view_key,
+    })
+}
+
+fn to_metadata(
+    title: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
+) -> Result<storage::Metadata> {
+    fn limit(value: Option<String>, max: usize, name: &str) -> Result<Option<String>> {
+        let value = value
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty());
+
+        match value {
+            Some(value) if value.chars().count() > max => Err(Error::BadRequest(format!(
+                "{} must not be longer than {} characters",
+                name, max
+            ))),
+            value => Ok(value),
+        }
+    }
+
+    let mut result = Vec::with_capacity(tags.len());
+    for tag in tags {
+        if let Some(tag) = limit(Some(tag), consts::MAX_TAG_LENGTH, "Tag")? {
+            if !result.contains(&tag) {
+                result.push(tag);
+            }
+        }
+    }
+    if result.len() > consts::MAX_TAGS {
+        return Err(Error::BadRequest(format!(
+            "Too many tags, at most {} are allowed",
+            consts::MAX_TAGS
+        )));
+    }
+
+    Ok(storage::Metadata {
+        title: limit(title, consts::MAX_TITLE_LENGTH, "Title")?,
+        description: limit(description, consts::MAX_DESCRIPTION_LENGTH, "Description")?,
+        tags: result,
+        ..Default::default()
+    })
+}
+
+enum PasteId {
+    /// The exact same paste is already stored with this id.
+    Existing(String),
+    /// The id is not taken yet.
+    New(String),
+}
+
+/// Derives the id for a paste from its SHA-1.
+///
+/// The id is only a prefix of the hash, if the id is already taken by a different paste,
+/// the id is extended until there is no more collision. This guarantees an existing link
+/// never changes its content.
+///
+/// Every attempt is a storage lookup, ids of existing pastes are remembered in `resolved`.
+/// A remembered id is still looked up once, the paste may have been deleted since.
+async fn resolve_id(
+    pastes: &dyn Pastes,
+    resolved: &Memo<String>,
+    now: u64,
+    sha1: &[u8],
+) -> Result<PasteId> {
+    let hex = utils::hex(sha1);
+    if let Some(id) = resolved.get(&hex, now) {
+        match pastes.sha1(&id).await? {
+            Some(existing) if existing.eq_ignore_ascii_case(&hex) => {
+                return Ok(PasteId::Existing(id))
+            }
+            _ => resolved.remove(&hex),
+        }
+    }
+
+    for bytes in (consts::PASTE_ID_MIN_BYTES..=consts::PASTE_ID_MAX_BYTES).step_by(3) {
+        let id = utils::hash_to_short_id(sha1, bytes)?;
+
+        match pastes.sha1(&id).await? {
+            None => return Ok(PasteId::New(id)),
+            Some(existing) if existing.eq_ignore_ascii_case(&hex) => {
+                resolved.insert(&hex, id.clone(), now);
+                return Ok(PasteId::Existing(id));
+            }
+            Some(_) => log::warn!("id collision for '{}', extending id", id),
+        }
+    }
+
+    Err(format!("unable to find a free id for {}", hex).into())
+}
+
+/// Generates a random, not guessable, id for a private paste.
+async fn random_id(platform: &dyn Platform) -> Result<PasteId> {
+    for _ in 0..3 {
+        let id = utils::random_id::<{ consts::PASTE_ID_PRIVATE_BYTES }>(platform)?;
+
+        match platform.pastes().sha1(&id).await? {
+            None => return Ok(PasteId::New(id)),
+            Some(_) => log::warn!("random id collision for '{}'", id),
+        }
+    }
+
+    Err("unable to find a free random id".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::future::block_on;
+
+    const SHA1: [u8; 20] = [1; 20];
+
+    fn resolve(pastes: &dyn Pastes, resolved: &Memo<String>) -> PasteId {
+        block_on(resolve_id(pastes, resolved, 0, &SHA1)).unwrap()
+    }
+
+    fn store(pastes: &dyn Pastes, bytes: usize, sha1: &[u8]) {
+        let id = utils::hash_to_short_id(&SHA1, bytes).unwrap();
+        let metadata = storage::Metadata::default();
+        block_on(pastes.put(&id, sha1, &mut [], &metadata)).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_id() {
+        let pastes = storage::Memory::default();
+        let resolved = Memo::new(1_000, 10);
+        let short = utils::hash_to_short_id(&SHA1, consts::PASTE_ID_MIN_BYTES).unwrap();
+
+        // Not stored yet
+        assert!(matches!(resolve(&pastes, &resolved), PasteId::New(id) if id == short));
+
+        // The same paste is already stored
+        store(&pastes, consts::PASTE_ID_MIN_BYTES, &SHA1);
+        assert!(matches!(resolve(&pastes, &resolved), PasteId::Existing(id) if id == short));
+        assert_eq!(resolved.get(&utils::hex(&SHA1), 0), Some(short));
+    }
+
+    #[test]
+    fn test_resolve_id_collision() {
+        let pastes = storage::Memory::default();
+        let resolved = Memo::new(1_000, 10);
+        let extended = utils::hash_to_short_id(&SHA1, consts::PASTE_ID_MIN_BYTES + 3).unwrap();
+
+        // A different paste has the same short id
+        store(&pastes, consts::PASTE_ID_MIN_BYTES, &[2; 20]);
+        assert!(matches!(resolve(&pastes, &resolved), PasteId::New(id) if id == extended));
+
+        store(&pastes, consts::PASTE_ID_MIN_BYTES + 3, &SHA1);
+        assert!(matches!(resolve(&pastes, &resolved), PasteId::Existing(id) if id == extended));
+
+        // Every id length is taken
+        let pastes = storage::Memory::default();
+        for bytes in (consts::PASTE_ID_MIN_BYTES..=consts::PASTE_ID_MAX_BYTES).step_by(3) {
+            store(&pastes, bytes, &[2; 20]);
+        }
+        assert!(block_on(resolve_id(&pastes, &Memo::new(1_000, 10), 0, &SHA1)).is_err());
+    }
+
+    #[test]
+    fn test_resolve_id_remembered() {
+        // Remembered ids skip the collision lookups
+        let resolved = Memo::new(1_000, 10);
+        resolved.insert(&utils::hex(&SHA1), "remembered".to_owned(), 0);
+        let pastes = storage::Memory::default();
+        block_on(pastes.put("remembered", &SHA1, &mut [], &Default::default())).unwrap();
+        assert!(matches!(resolve(&pastes, &resolved), PasteId::Existing(id) if id == "remembered"));
+
+        // The remembered paste was deleted, e.g. by an admin
+        block_on(pastes.delete("remembered")).unwrap();
+        let short = utils::hash_to_short_id(&SHA1, consts::PASTE_ID_MIN_BYTES).unwrap();
+        assert!(matches!(resolve(&pastes, &resolved), PasteId::New(id) if id == short));
+        assert_eq!(resolved.get(&utils::hex(&SHA1), 0), None);
+    }
+
+    #[test]
+    fn test_to_metadata() {
+        let metadata = to_metadata(
+            Some("  Budget PConc League Starter ".to_owned()),
+            Some("".to_owned()),
+            vec![
+                "league starter".to_owned(),
+                " ".to_owned(),
+                "budget".to_owned(),
+                "budget ".to_owned(),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            metadata,
+            storage::Metadata {
+                title: Some("Budget PConc League Starter".to_owned()),
+                tags: vec!["league starter".to_owned(), "budget".to_owned()],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_metadata_limits() {
+        let title = "x".repeat(consts::MAX_TITLE_LENGTH + 1);
+        assert!(to_metadata(Some(title), None, Vec::new()).is_err());
+
+        let tags = (0..=consts::MAX_TAGS).map(|i| i.to_string()).collect();
+        assert!(to_metadata(None, None, tags).is_err());
+    }
 }
@@ -0,0 +1,365 @@
+//! API keys for bots and tools which upload pastes programmatically.
+//!
+//! Keys are sent as `Authorization: Bearer <key>` and raise the upload size limit up to a
+//! daily quota, keyed uploads are not limited per client. Other authorization schemes, e.g.
+//! basic auth of a proxy in front of the worker, are anonymous. Only the SHA-1 of a key is
+//! stored, in the moderation namespace, the key itself is shown once when it is issued.
+//!
+//! Usage is stored under its own key, uploads never write the key itself and revoking a key
+//! is final. Workers KV has no atomic increments, usage is also remembered per isolate.
+//! Concurrent uploads with the same key in one isolate never exceed the quota, uploads spread
+//! over several isolates may exceed it slightly.
+
+use crate::{
+    consts, http::Request, memo::Memo, platform::Platform, store::Store, utils, Error, Result,
+};
+use serde::{Deserialize, Serialize};
+
+const PREFIX: &str = "apikey:";
+/// Not below [`PREFIX`], listing keys does not list their usage.
+const USAGE_PREFIX: &str = "apikey-usage:";
+const DAY_MS: u64 = 24 * 3_600 * 1000;
+
+thread_local! {
+    /// Usage of keys by their id, ahead of the stored usage until the store catches up.
+    static USAGE: Memo<Usage> = Memo::new(consts::API_KEY_USAGE_TTL, consts::API_KEY_USAGE_CAPACITY);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// Who the key was issued to, stored as uploader of the pastes.
+    pub name: String,
+    /// Uploads per day.
+    pub daily_quota: u32,
+    /// Upload size limit in bytes, replaces [`consts::MAX_UPLOAD_SIZE`].
+    pub max_upload_size: usize,
+    /// Time the key was issued in milliseconds since the epoch.
+    pub created: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Uploads since the key was issued.
+    pub total: u64,
+    /// Day of `today` in days since the epoch.
+    pub day: u64,
+    /// Uploads on `day`.
+    pub today: u32,
+    /// Time of the last upload in milliseconds since the epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
+}
+
+/// Key settings chosen by the admin when issuing a key.
+#[derive(Debug, Deserialize)]
+pub struct IssueRequest {
+    pub name: String,
+    #[serde(default)]
+    pub daily_quota: Option<u32>,
+    #[serde(default)]
+    pub max_upload_size: Option<usize>,
+}
+
+/// A stored key, the id is the hex encoded SHA-1 of the key.
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub id: String,
+    #[serde(flatten)]
+    pub key: ApiKey,
+    pub usage: Usage,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Entries {
+    pub keys: Vec<Entry>,
+    pub cursor: Option<String>,
+}
+
+impl ApiKey {
+    pub fn new(request: IssueRequest, now: u64) -> Result<Self> {
+        let name = request.name.trim();
+        if name.is_empty() || name.chars().count() > consts::API_KEY_MAX_NAME_LENGTH {
+            return Err(Error::BadRequest(format!(
+                "Name must have 1 to {} characters",
+                consts::API_KEY_MAX_NAME_LENGTH
+            )));
+        }
+
+        let max_upload_size = request
+            .max_upload_size
+            .unwrap_or(consts::API_KEY_UPLOAD_SIZE);
+        if max_upload_size > consts::API_KEY_MAX_UPLOAD_SIZE {
+            return Err(Error::BadRequest(format!(
+                "Upload size limit must be at most {} bytes",
+                consts::API_KEY_MAX_UPLOAD_SIZE
+            )));
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            daily_quota: request.daily_quota.unwrap_or(consts::API_KEY_DAILY_QUOTA),
+            max_upload_size,
+            created: now,
+        })
+    }
+}
+
+impl Entry {
+    /// Fails with [`Error::QuotaExceeded`] if the key has no uploads left today.
+    pub fn check_quota(&self, now: u64) -> Result<()> {
+        let today = match self.usage.day == now / DAY_MS {
+            true => self.usage.today,
+            false => 0,
+        };
+
+        match today < self.key.daily_quota {
+            true => Ok(()),
+            false => Err(Error::QuotaExceeded(self.key.daily_quota)),
+        }
+    }
+
+    fn record_upload(&mut self, now: u64) {
+        let day = now / DAY_MS;
+        if self.usage.day != day {
+            self.usage.day = day;
+            self.usage.today = 0;
+        }
+
+        self.usage.today = self.usage.today.saturating_add(1);
+        self.usage.total += 1;
+        self.usage.last_used = Some(now);
+    }
+
+    /// Takes the usage remembered by the isolate, if it is ahead of the stored usage.
+    fn remembered(&mut self, now: u64) {
+        if let Some(usage) = USAGE.with(|usage| usage.get(&self.id, now)) {
+            if usage.total > self.usage.total {
+                self.usage = usage;
+            }
+        }
+    }
+}
+
+fn key(id: &str) -> String {
+    format!("{}{}", PREFIX, id)
+}
+
+fn usage_key(id: &str) -> String {
+    format!("{}{}", USAGE_PREFIX, id)
+}
+
+/// The token of a `Bearer` authorization header, `None` for other schemes.
+fn bearer_token(header: &str) -> Option<&str> {
+    let header = header.trim();
+    let (scheme, token) = header.split_once(' ').unwrap_or((header, ""));
+    scheme.eq_ignore_ascii_case("Bearer").then(|| token.trim())
+}
+
+/// The key of the request, `None` for anonymous requests.
+///
+/// Fails with [`Error::Unauthorized`] if the request has a key, but it is unknown or revoked.
+pub async fn authenticate(platform: &dyn Platform, req: &Request) -> Result<Option<Entry>> {
+    let token = match req.header("Authorization").and_then(bearer_token) {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+    if token.is_empty() {
+        return Err(Error::Unauthorized);
+    }
+
+    let id = utils::hex(&platform.sha1(token.as_bytes()).await?);
+    let key = get(platform.moderation(), &id)
+        .await?
+        .ok_or(Error::Unauthorized)?;
+    let usage = get_usage(platform.moderation(), &id).await?;
+
+    let mut entry = Entry { id, key, usage };
+    entry.remembered(platform.now());
+    Ok(Some(entry))
+}
+
+pub async fn get(store: &dyn Store, id: &str) -> Result<Option<ApiKey>> {
+    match store.get(&key(id)).await? {
+        Some(key) => Ok(Some(serde_json::from_str(&key)?)),
+        None => Ok(None),
+    }
+}
+
+/// Stores a newly issued key, the usage is stored separately by [`put_usage`].
+pub async fn put(store: &dyn Store, entry: &Entry) -> Result<()> {
+    // The key doubles as listing metadata, listing keys does not need a read per key
+    let metadata = serde_json::to_value(&entry.key)?;
+    store
+        .put(
+            &key(&entry.id),
+            serde_json::to_string(&entry.key)?,
+            Some(metadata),
+        )
+        .await
+}
+
+/// The stored usage of a key, the default if it was never used.
+pub async fn get_usage(store: &dyn Store, id: &str) -> Result<Usage> {
+    match store.get(&usage_key(id)).await? {
+        Some(usage) => Ok(serde_json::from_str(&usage)?),
+        None => Ok(Usage::default()),
+    }
+}
+
+/// Stores the usage after [`reserve_upload`], a revoked key stays revoked.
+pub async fn put_usage(store: &dyn Store, entry: &Entry) -> Result<()> {
+    store
+        .put(
+            &usage_key(&entry.id),
+            serde_json::to_string(&entry.usage)?,
+            None,
+        )
+        .await
+}
+
+/// Counts a valid upload against the quota of the key, before the paste is stored.
+///
+/// Fails with [`Error::QuotaExceeded`] if the key has no uploads left today. The usage is
+/// remembered by the isolate right away, it still has to be stored with [`put_usage`].
+pub fn reserve_upload(entry: &mut Entry, now: u64) -> Result<()> {
+    entry.remembered(now);
+    entry.check_quota(now)?;
+    entry.record_upload(now);
+    USAGE.with(|usage| usage.insert(&entry.id, entry.usage.clone(), now));
+
+    Ok(())
+}
+
+pub async fn revoke(store: &dyn Store, id: &str) -> Result<()> {
+    if get(store, id).await?.is_none() {
+        return Err(Error::NotFound("api key", id.to_owned()));
+    }
+    store.delete(&key(id)).await?;
+    store.delete(&usage_key(id)).await
+}
+
+/// Lists the issued keys, the usage is read per key.
+pub async fn list(store: &dyn Store, cursor: Option<String>, limit: u64) -> Result<Entries> {
+    let page = store.list(PREFIX, cursor, limit).await?;
+
+    let mut keys = Vec::new();
+    for key in page.keys {
+        let id = key.name.strip_prefix(PREFIX).map(ToOwned::to_owned);
+        let key = key
+            .metadata
+            .and_then(|key| serde_json::from_value(key).ok());
+        if let (Some(id), Some(key)) = (id, key) {
+            let usage = get_usage(store, &id).await?;
+            keys.push(Entry { id, key, usage });
+        }
+    }
+
+    Ok(Entries {
+        keys,
+        cursor: page.cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{future::block_on, store::Memory};
+
+    fn request(name: &str) -> IssueRequest {
+        IssueRequest {
+            name: name.to_owned(),
+            daily_quota: Some(2),
+            max_upload_size: None,
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let key = ApiKey::new(request(" pob "), 1).unwrap();
+        assert_eq!(key.name, "pob");
+        assert_eq!(key.daily_quota, 2);
+        assert_eq!(key.max_upload_size, consts::API_KEY_UPLOAD_SIZE);
+
+        assert!(ApiKey::new(request(" "), 1).is_err());
+        let too_large = IssueRequest {
+            max_upload_size: Some(consts::API_KEY_MAX_UPLOAD_SIZE + 1),
+            ..request("pob")
+        };
+        assert!(ApiKey::new(too_large, 1).is_err());
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+        assert_eq!(bearer_token(" bearer  abc "), Some("abc"));
+        assert_eq!(bearer_token("Bearer"), Some(""));
+        assert_eq!(bearer_token("Basic dXNlcjpwYXNz"), None);
+        assert_eq!(bearer_token(""), None);
+    }
+
+    #[test]
+    fn test_quota() {
+        let store = Memory::default();
+        let now = 10 * DAY_MS + 1;
+        let mut entry = Entry {
+            id: "ABC".to_owned(),
+            key: ApiKey::new(request("pob"), now).unwrap(),
+            usage: Usage::default(),
+        };
+        block_on(put(&store, &entry)).unwrap();
+        let mut stale = Entry {
+            id: entry.id.clone(),
+            key: entry.key.clone(),
+            usage: entry.usage.clone(),
+        };
+
+        for _ in 0..2 {
+            reserve_upload(&mut entry, now).unwrap();
+            block_on(put_usage(&store, &entry)).unwrap();
+        }
+        let err = entry.check_quota(now).unwrap_err();
+        assert!(matches!(err, Error::QuotaExceeded(2)));
+
+        // A concurrent upload which read the usage before it was stored
+        let err = reserve_upload(&mut stale, now).unwrap_err();
+        assert!(matches!(err, Error::QuotaExceeded(2)));
+
+        // The quota resets the next day, the total keeps counting
+        let tomorrow = now + DAY_MS;
+        reserve_upload(&mut entry, tomorrow).unwrap();
+        block_on(put_usage(&store, &entry)).unwrap();
+        let usage = block_on(get_usage(&store, "ABC")).unwrap();
+        assert_eq!(usage.today, 1);
+        assert_eq!(usage.total, 3);
+        assert_eq!(usage.last_used, Some(tomorrow));
+
+        // The key itself is never written by uploads
+        let stored = block_on(get(&store, "ABC")).unwrap().unwrap();
+        assert_eq!(stored, entry.key);
+    }
+
+    #[test]
+    fn test_list_and_revoke() {
+        let store = Memory::default();
+        for id in ["A", "B"] {
+            let mut entry = Entry {
+                id: id.to_owned(),
+                key: ApiKey::new(request(id), 1).unwrap(),
+                usage: Usage::default(),
+            };
+            block_on(put(&store, &entry)).unwrap();
+            reserve_upload(&mut entry, 1).unwrap();
+            block_on(put_usage(&store, &entry)).unwrap();
+        }
+
+        block_on(revoke(&store, "A")).unwrap();
+        assert!(block_on(revoke(&store, "A")).is_err());
+        assert_eq!(block_on(get_usage(&store, "A")).unwrap(), Usage::default());
+
+        let entries = block_on(list(&store, None, 10)).unwrap();
+        assert_eq!(entries.keys.len(), 1);
+        assert_eq!(entries.keys[0].id, "B");
+        assert_eq!(entries.keys[0].key.name, "B");
+        assert_eq!(entries.keys[0].usage.total, 1);
+    }
+}
@@ -1,11 +1,99 @@
-use crate::{consts, utils::ResponseExt, Error, Result};
+use crate::{
+    consts, crypto,
+    encoding::Encoding,
+    future::LocalBoxFuture,
+    http::{Request, Response},
+    utils::{self, ResponseExt},
+    Error, Result,
+};
 use std::borrow::Cow;
 use wasm_bindgen::prelude::*;
 use worker::{
     kv::{self, KvStore},
-    Env, Request, Response,
+    Env,
 };
 
+/// A static asset, e.g. the app bundle.
+pub struct Asset {
+    pub data: Vec<u8>,
+    /// Path the asset was resolved to, the extension determines the content type.
+    pub path: String,
+    /// Hash of the content, differs between the encoded variants of an asset.
+    pub etag: String,
+}
+
+/// Source of static assets, abstracted over the platform.
+pub trait Assets {
+    fn get<'a>(&'a self, path: &'a str) -> LocalBoxFuture<'a, Result<Option<Asset>>>;
+
+    /// Precompressed variant of the asset, e.g. `index.js.br` for Brotli.
+    ///
+    /// The returned asset has the path of the uncompressed asset.
+    fn get_encoded<'a>(
+        &'a self,
+        _path: &'a str,
+        _encoding: Encoding,
+    ) -> LocalBoxFuture<'a, Result<Option<Asset>>> {
+        Box::pin(async { Ok(None) })
+    }
+}
+
+/// Assets uploaded to Workers KV, file names are fingerprinted by wrangler.
+pub struct KvAssets(KvStore);
+
+impl KvAssets {
+    pub fn from_env(env: &Env) -> Result<Self> {
+        Ok(Self(env.get_assets()?))
+    }
+}
+
+impl Assets for KvAssets {
+    fn get<'a>(&'a self, path: &'a str) -> LocalBoxFuture<'a, Result<Option<Asset>>> {
+        Box::pin(async move {
+            let key = resolve(path);
+            let data = match self.0.get(&key).bytes().await? {
+                Some(data) => data,
+                None => return Ok(None),
+            };
+
+            // Keys in the manifest already contain a hash of the content
+            let etag = match content_hash(&key).filter(|_| key != path) {
+                Some(hash) => hash.to_owned(),
+                None => {
+                    let mut buf = data.clone();
+                    utils::hex(&crypto::sha1(&mut buf).await?)
+                }
+            };
+
+            Ok(Some(Asset {
+                data,
+                path: key.into_owned(),
+                etag,
+            }))
+        })
+    }
+
+    // Sent with `encodeBody: "manual"`, see `platform::cloudflare::response`
+    fn get_encoded<'a>(
+        &'a self,
+        path: &'a str,
+        encoding: Encoding,
+    ) -> LocalBoxFuture<'a, Result<Option<Asset>>> {
+        Box::pin(async move {
+            let extension = match encoding.extension() {
+                Some(extension) => extension,
+                None => return Ok(None),
+            };
+
+            let asset = self.get(&format!("{}.{}", path, extension)).await?;
+            Ok(asset.map(|asset| Asset {
+                path: path.to_owned(),
+                ..asset
+            }))
+        })
+    }
+}
+
 pub trait KvAssetExt {
     fn get_asset(&self, name: &str) -> kv::GetOptionsBuilder;
 }
@@ -31,35 +119,174 @@ impl EnvAssetExt for Env {
     }
 }
 
-pub async fn try_handle(req: &mut Request, env: &Env) -> Result<Option<Response>> {
+pub fn is_asset_path(path: &str) -> bool {
     // does the last segment contain a '.'
-    let is_asset_path = req
-        .path()
-        .rsplit_once("/")
+    path.rsplit_once('/')
         .map(|x| x.1)
-        .unwrap_or(&req.path())
-        .contains('.');
+        .unwrap_or(path)
+        .contains('.')
+}
 
-    if is_asset_path {
-        Ok(Some(serve_asset(req, env).await?))
+pub async fn try_handle(assets: &dyn Assets, req: &Request) -> Result<Option<Response>> {
+    if is_asset_path(req.path()) {
+        Ok(Some(serve_asset(assets, req).await?))
     } else {
         Ok(None)
     }
 }
 
-async fn serve_asset(req: &Request, env: &Env) -> Result<Response> {
-    let path = req.path();
-    let path = path.trim_start_matches('/');
-    let path = resolve(path);
-    let value = match env.get_asset(&path)?.bytes().await? {
-        Some(value) => value,
-        None => return Err(Error::NotFound("asset", path.to_string())),
+async fn serve_asset(assets: &dyn Assets, req: &Request) -> Result<Response> {
+    let path = req.path().trim_start_matches('/');
+    let range = req.header("Range");
+
+    // Ranges refer to the unencoded content, partial responses are never compressed
+    let mut encoded = None;
+    if range.is_none() {
+        for encoding in Encoding::accepted(req.header("Accept-Encoding")) {
+            if let Some(asset) = assets.get_encoded(path, encoding).await? {
+                encoded = Some((asset, encoding));
+                break;
+            }
+        }
+    }
+
+    let (asset, encoding) = match encoded {
+        Some(encoded) => encoded,
+        None => match assets.get(path).await? {
+            Some(asset) => (asset, Encoding::Identity),
+            None => return Err(Error::NotFound("asset", path.to_owned())),
+        },
+    };
+
+    let content_type = get_mime(&asset.path).unwrap_or("text/plain");
+    let headers = |response: Response| {
+        let response = response
+            .with_content_type(content_type)?
+            .with_etag(&asset.etag)?;
+        match is_fingerprinted(path) {
+            true => response.cache_immutable(consts::CACHE_IMMUTABLE),
+            false => response.cache_for(consts::CACHE_ASSETS),
+        }
+    };
+
+    if utils::etag_matches(req.header("If-None-Match"), &asset.etag) {
+        return headers(Response::empty().with_status(304))?.with_header("Vary", "Accept-Encoding");
+    }
+
+    if encoding != Encoding::Identity {
+        return headers(Response::from_bytes(asset.data))?.with_encoded_body(encoding);
+    }
+
+    let response = match partial(range, req.header("If-Range"), &asset.etag, &asset.data)? {
+        Some(response) if response.status() == 416 => return Ok(response),
+        Some(response) => response,
+        // Cached by url only, the response must not depend on the encoding of the request
+        None => Response::from_bytes(asset.data),
+    };
+
+    headers(response)?.with_header("Accept-Ranges", "bytes")
+}
+
+/// Partial response to a `Range` request for `data`, `None` if the full content is sent.
+///
+/// Also used for assets served from the edge cache, which only contains full responses.
+pub fn partial(
+    range: Option<&str>,
+    if_range: Option<&str>,
+    etag: &str,
+    data: &[u8],
+) -> Result<Option<Response>> {
+    // A range is only valid for the version of the asset the client already has
+    let len = data.len();
+    let range = match range {
+        Some(range) if if_range_matches(if_range, etag) => ByteRange::parse(range, len),
+        _ => ByteRange::Full,
     };
 
-    Response::from_bytes(value)?
-        .with_content_type(get_mime(&path).unwrap_or("text/plain"))?
-        .cache_for(consts::CACHE_ASSETS)?
-        .with_etag(&path)
+    let response = match range {
+        ByteRange::Full => return Ok(None),
+        ByteRange::Partial(start, end) => Response::from_bytes(data[start..=end].to_vec())
+            .with_status(206)
+            .with_header("Content-Range", &format!("bytes {}-{}/{}", start, end, len))?,
+        ByteRange::Unsatisfiable => Response::empty()
+            .with_status(416)
+            .with_header("Content-Range", &format!("bytes */{}", len))?,
+    };
+
+    Ok(Some(response))
+}
+
+/// Whether the `If-Range` header, if any, is the current entity tag.
+///
+/// If-Range requires the strong comparison, weak entity tags never match.
+fn if_range_matches(if_range: Option<&str>, etag: &str) -> bool {
+    match if_range {
+        Some(if_range) => {
+            let if_range = if_range.trim();
+            !if_range.starts_with("W/") && if_range.trim_matches('"') == etag
+        }
+        None => true,
+    }
+}
+
+/// A single range of a `Range` header, other range requests are answered with the full content.
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    /// First and last byte, inclusive.
+    Partial(usize, usize),
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    fn parse(range: &str, len: usize) -> Self {
+        let range = match range.trim().strip_prefix("bytes=") {
+            Some(range) if !range.contains(',') => range.trim(),
+            _ => return Self::Full,
+        };
+        let (start, end) = match range.split_once('-') {
+            Some(range) => range,
+            None => return Self::Full,
+        };
+
+        let (start, end) = match (start.parse::<usize>(), end.parse::<usize>()) {
+            // `bytes=-500`, the last 500 bytes
+            (Err(_), Ok(suffix)) if start.is_empty() => match suffix {
+                0 => return Self::Unsatisfiable,
+                suffix => (len.saturating_sub(suffix), len.saturating_sub(1)),
+            },
+            // `bytes=500-`, everything from byte 500
+            (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            _ => return Self::Full,
+        };
+
+        match start < len {
+            true => Self::Partial(start, end),
+            false => Self::Unsatisfiable,
+        }
+    }
+}
+
+/// Whether the file name contains a content hash, e.g. `index-4f1e2d3c4b5a6978_bg.wasm`.
+///
+/// Trunk fingerprints the files of the app bundle, every build references new file names.
+fn is_fingerprinted(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let stem = name.split('.').next().unwrap_or(name);
+    let stem = stem.strip_suffix("_bg").unwrap_or(stem);
+
+    matches!(stem.rsplit_once('-'), Some((_, hash)) if is_hash(hash))
+}
+
+/// Content hash wrangler adds to the keys, e.g. `favicon.0a1b2c3d4e.ico`.
+fn content_hash(key: &str) -> Option<&str> {
+    let hash = key.rsplit('.').nth(1)?;
+    is_hash(hash).then_some(hash)
+}
+
+fn is_hash(s: &str) -> bool {
+    s.len() >= 8 && s.bytes().all(|b| b.is_ascii_hexdigit())
 }
 
 #[wasm_bindgen(raw_module = "./assets.mjs")]
@@ -91,9 +318,154 @@ fn get_mime(path: &str) -> Option<&'static str> {
         "jpeg" => "image/jpeg",
         "ico" => "image/x-icon",
         "svg" => "image/svg+xml",
+        "webp" => "image/webp",
         "wasm" => "application/wasm",
+        "map" => "application/json",
+        "txt" => "text/plain",
+        "ttf" => "font/ttf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
         _ => return None,
     };
 
     Some(ct)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{future::block_on, http::Method};
+    use worker::Url;
+
+    struct Stub;
+
+    impl Assets for Stub {
+        fn get<'a>(&'a self, path: &'a str) -> LocalBoxFuture<'a, Result<Option<Asset>>> {
+            Box::pin(async move {
+                Ok((path == "index-0123456789abcdef.js").then(|| Asset {
+                    data: b"0123456789".to_vec(),
+                    path: path.to_owned(),
+                    etag: "plain".to_owned(),
+                }))
+            })
+        }
+
+        fn get_encoded<'a>(
+            &'a self,
+            path: &'a str,
+            encoding: Encoding,
+        ) -> LocalBoxFuture<'a, Result<Option<Asset>>> {
+            Box::pin(async move {
+                let asset = self.get(path).await?;
+                Ok(asset
+                    .filter(|_| encoding == Encoding::Gzip)
+                    .map(|asset| Asset {
+                        data: b"gzipped".to_vec(),
+                        etag: "gzipped".to_owned(),
+                        ..asset
+                    }))
+            })
+        }
+    }
+
+    fn serve(headers: &[(&str, &str)]) -> Response {
+        let request = Request::new(
+            Method::Get,
+            Url::parse("https://pobb.in/index-0123456789abcdef.js").unwrap(),
+            headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            Vec::new(),
+        );
+        block_on(serve_asset(&Stub, &request)).unwrap()
+    }
+
+    #[test]
+    fn test_serve_asset() {
+        let response = serve(&[("Accept-Encoding", "br, gzip")]);
+        assert_eq!(response.body(), b"gzipped");
+        assert!(response.is_encoded());
+        assert_eq!(response.header("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.header("Content-Type"), Some("text/javascript"));
+        assert_eq!(
+            response.header("Cache-Control"),
+            Some("public, max-age=31536000, immutable")
+        );
+
+        // Without a precompressed variant the asset is sent as is
+        let response = serve(&[("Accept-Encoding", "br")]);
+        assert_eq!(response.body(), b"0123456789");
+        assert!(!response.is_encoded());
+        assert_eq!(response.header("Content-Encoding"), None);
+
+        let response = serve(&[("If-None-Match", "\"plain\"")]);
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.body(), b"");
+
+        let response = serve(&[("Accept-Encoding", "gzip"), ("Range", "bytes=2-4")]);
+        assert_eq!(response.status(), 206);
+        assert_eq!(response.body(), b"234");
+        assert_eq!(response.header("Content-Range"), Some("bytes 2-4/10"));
+
+        let response = serve(&[("Range", "bytes=2-4"), ("If-Range", "\"old\"")]);
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), b"0123456789");
+
+        // If-Range only matches strong entity tags
+        let response = serve(&[("Range", "bytes=2-4"), ("If-Range", "W/\"plain\"")]);
+        assert_eq!(response.status(), 200);
+        let response = serve(&[("Range", "bytes=2-4"), ("If-Range", "\"plain\"")]);
+        assert_eq!(response.status(), 206);
+
+        let response = serve(&[("Range", "bytes=10-")]);
+        assert_eq!(response.status(), 416);
+        assert_eq!(response.header("Content-Range"), Some("bytes */10"));
+    }
+
+    #[test]
+    fn test_partial() {
+        assert!(partial(None, None, "etag", b"0123").unwrap().is_none());
+        assert!(partial(Some("bytes=1-2"), Some("\"old\""), "etag", b"0123")
+            .unwrap()
+            .is_none());
+
+        let response = partial(Some("bytes=1-2"), None, "etag", b"0123")
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), 206);
+        assert_eq!(response.body(), b"12");
+        assert_eq!(response.header("Content-Range"), Some("bytes 1-2/4"));
+
+        let response = partial(Some("bytes=4-"), Some("\"etag\""), "etag", b"0123")
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), 416);
+    }
+
+    #[test]
+    fn test_byte_range() {
+        use ByteRange::*;
+
+        assert_eq!(ByteRange::parse("bytes=0-0", 10), Partial(0, 0));
+        assert_eq!(ByteRange::parse("bytes=5-", 10), Partial(5, 9));
+        assert_eq!(ByteRange::parse("bytes=-3", 10), Partial(7, 9));
+        assert_eq!(ByteRange::parse("bytes=-30", 10), Partial(0, 9));
+        assert_eq!(ByteRange::parse("bytes=8-100", 10), Partial(8, 9));
+        assert_eq!(ByteRange::parse("bytes=10-20", 10), Unsatisfiable);
+        assert_eq!(ByteRange::parse("bytes=-0", 10), Unsatisfiable);
+        assert_eq!(ByteRange::parse("bytes=0-1,4-5", 10), Full);
+        assert_eq!(ByteRange::parse("bytes=5-2", 10), Full);
+        assert_eq!(ByteRange::parse("items=0-1", 10), Full);
+    }
+
+    #[test]
+    fn test_is_fingerprinted() {
+        assert!(is_fingerprinted("index-4f1e2d3c4b5a6978.js"));
+        assert!(is_fingerprinted("/index-4f1e2d3c4b5a6978_bg.wasm"));
+        assert!(!is_fingerprinted("favicon.ico"));
+        assert!(!is_fingerprinted("assets/path-of-building.png"));
+        assert_eq!(content_hash("favicon.0a1b2c3d4e.ico"), Some("0a1b2c3d4e"));
+        assert_eq!(content_hash("favicon.ico"), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! Serves the app without Cloudflare, see `pobbin-server --help`.
+
+use std::path::PathBuf;
+use worker_app::Config;
+
+const USAGE: &str = "Usage: pobbin-server [--listen <addr>] [--data <dir>] [--assets <dir>]
+
+Options:
+    --listen <addr>  Address to listen on [default: 127.0.0.1:8787]
+    --data <dir>     Directory for pastes and indexes [default: data]
+    --assets <dir>   Directory of the built app [default: app/dist]
+
+Environment:
+    WEBHOOKS         JSON list of webhooks notified about new public pastes
+    ROBOTS_DISALLOW  Comma separated paths excluded in the robots.txt";
+
+fn main() {
+    let _ = fern::Dispatch::new()
+        .format(|out, message, record| {
+            let request_id = worker_app::current_request_id().unwrap_or_default();
+            out.finish(format_args!(
+                "[{}] [{}] {}",
+                record.level(),
+                request_id,
+                message
+            ))
+        })
+        .level(log::LevelFilter::Info)
+        .chain(std::io::stderr())
+        .apply();
+
+    let mut config = Config {
+        listen: "127.0.0.1:8787".to_owned(),
+        data: PathBuf::from("data"),
+        assets: PathBuf::from("app/dist"),
+        security: Default::default(),
+        webhooks: std::env::var("WEBHOOKS")
+            .map(|webhooks| worker_app::parse_webhooks(&webhooks))
+            .unwrap_or_default(),
+        robots: std::env::var("ROBOTS_DISALLOW")
+            .map(|disallow| worker_app::Robots::parse(&disallow))
+            .unwrap_or_default(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || match args.next() {
+            Some(value) => value,
+            None => exit(&format!("missing value for {}", arg)),
+        };
+        match arg.as_str() {
+            "--listen" => config.listen = value(),
+            "--data" => config.data = value().into(),
+            "--assets" => config.assets = value().into(),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => exit(&format!("unknown argument {}", arg)),
+        }
+    }
+
+    if let Err(err) = worker_app::serve(config) {
+        exit(&err.to_string());
+    }
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    std::process::exit(1);
+}
@@ -0,0 +1,215 @@
+//! Keys of the edge cache.
+//!
+//! Responses are cached per namespace and deployed version, a deploy never serves markup
+//! rendered by the previous version. Purging a namespace increments its generation,
+//! entries of previous generations are no longer looked up and expire on their own.
+//!
+//! Generations are stored in their own KV namespace, keyed by the name of the namespace.
+
+use crate::{
+    api, assets, consts, http::Method, memo::Memo, preview, recent, sitemap, store::Store, Result,
+};
+use git_version::git_version;
+use worker::Url;
+
+thread_local! {
+    /// Generations looked up by this isolate, see [`current_generation`].
+    static GENERATIONS: Memo<u64> = Memo::new(consts::CACHE_GENERATION_TTL, Namespace::ALL.len());
+}
+
+/// Version of the deployed worker, part of every cache key.
+pub const VERSION: &str = git_version!();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    /// Server side rendered pages.
+    Html,
+    /// JSON responses of the API, oEmbed and feeds.
+    Api,
+    /// Raw paste content.
+    Raw,
+    /// Preview images of pastes.
+    Preview,
+    /// Assets of the app.
+    Static,
+}
+
+impl Namespace {
+    pub const ALL: [Self; 5] = [
+        Self::Html,
+        Self::Api,
+        Self::Raw,
+        Self::Preview,
+        Self::Static,
+    ];
+    /// Namespaces with responses of individual pastes, assets never contain a paste.
+    pub const PASTES: [Self; 4] = [Self::Html, Self::Api, Self::Raw, Self::Preview];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Api => "api",
+            Self::Raw => "raw",
+            Self::Preview => "preview",
+            Self::Static => "static",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|namespace| namespace.name() == name)
+    }
+
+    /// Namespace of a `GET` request.
+    pub fn of(path: &str) -> Self {
+        match api::route(Method::Get, path) {
+            Some("/pob/<id>" | "/<id>/raw") => return Self::Raw,
+            Some(_) => return Self::Api,
+            None => (),
+        }
+
+        if path == "/oembed.json" || recent::is_feed_url(path) || sitemap::is_sitemap_url(path) {
+            Self::Api
+        } else if preview::is_preview_url(path).is_some() {
+            Self::Preview
+        } else if assets::is_asset_path(path) {
+            Self::Static
+        } else {
+            Self::Html
+        }
+    }
+}
+
+/// Current generation of the namespace.
+pub async fn generation(store: &dyn Store, namespace: Namespace) -> Result<u64> {
+    let generation = store.get(namespace.name()).await?;
+    Ok(generation.and_then(|g| g.parse().ok()).unwrap_or(0))
+}
+
+/// Current generation of the namespace, remembered by the isolate for a short time.
+///
+/// Every cache lookup needs the generation, a failing store must not fail the request,
+/// the generation falls back to 0 and is not remembered.
+pub async fn current_generation(store: &dyn Store, namespace: Namespace, now: u64) -> u64 {
+    if let Some(generation) = GENERATIONS.with(|g| g.get(namespace.name(), now)) {
+        return generation;
+    }
+
+    match generation(store, namespace).await {
+        Ok(generation) => {
+            GENERATIONS.with(|g| g.insert(namespace.name(), generation, now));
+            generation
+        }
+        Err(err) => {
+            log::warn!("failed to load cache generation: {:?}", err);
+            0
+        }
+    }
+}
+
+/// Invalidates all cached responses of the namespace, returns the new generation.
+///
+/// Other isolates keep using the previous generation for up to
+/// [`consts::CACHE_GENERATION_TTL`].
+pub async fn purge(store: &dyn Store, namespace: Namespace) -> Result<u64> {
+    let generation = generation(store, namespace).await? + 1;
+    store
+        .put(namespace.name(), generation.to_string(), None)
+        .await?;
+    GENERATIONS.with(|g| g.remove(namespace.name()));
+    Ok(generation)
+}
+
+/// Key of the url in the edge cache, the cache only accepts urls as keys.
+pub fn key(url: &Url, namespace: Namespace, generation: u64) -> String {
+    let mut key = format!(
+        "{}/_cache/{}/{}/{}{}",
+        url.origin().ascii_serialization(),
+        namespace.name(),
+        VERSION,
+        generation,
+        url.path()
+    );
+    if let Some(query) = url.query() {
+        key.push('?');
+        key.push_str(query);
+    }
+    key
+}
+
+/// Key of the url in the edge cache, with the current generation of its namespace.
+pub async fn current_key(store: &dyn Store, url: &Url, now: u64) -> String {
+    let namespace = Namespace::of(url.path());
+    let generation = current_generation(store, namespace, now).await;
+    key(url, namespace, generation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{future::block_on, store::Memory};
+
+    #[test]
+    fn test_namespace() {
+        assert_eq!(Namespace::of("/"), Namespace::Html);
+        assert_eq!(Namespace::of("/abc"), Namespace::Html);
+        assert_eq!(Namespace::of("/search"), Namespace::Html);
+        assert_eq!(Namespace::of("/api/v1/search"), Namespace::Api);
+        assert_eq!(Namespace::of("/oembed.json"), Namespace::Api);
+        assert_eq!(Namespace::of("/recent.atom"), Namespace::Api);
+        assert_eq!(Namespace::of("/sitemap.xml"), Namespace::Api);
+        assert_eq!(Namespace::of("/abc/raw"), Namespace::Raw);
+        assert_eq!(Namespace::of("/pob/abc"), Namespace::Raw);
+        assert_eq!(Namespace::of("/abc/preview.png"), Namespace::Preview);
+        assert_eq!(Namespace::of("/index-123.js"), Namespace::Static);
+
+        assert_eq!(Namespace::parse("raw"), Some(Namespace::Raw));
+        assert_eq!(Namespace::parse("nope"), None);
+    }
+
+    #[test]
+    fn test_key() {
+        let store = Memory::default();
+        let url = Url::parse("https://pobb.in/abc?key=xyz").unwrap();
+
+        let key = block_on(current_key(&store, &url, 0));
+        assert_eq!(
+            key,
+            format!("https://pobb.in/_cache/html/{}/0/abc?key=xyz", VERSION)
+        );
+
+        assert_eq!(block_on(purge(&store, Namespace::Html)).unwrap(), 1);
+        assert_eq!(block_on(purge(&store, Namespace::Html)).unwrap(), 2);
+        assert_eq!(block_on(generation(&store, Namespace::Api)).unwrap(), 0);
+
+        let key = block_on(current_key(&store, &url, 0));
+        assert_eq!(
+            key,
+            format!("https://pobb.in/_cache/html/{}/2/abc?key=xyz", VERSION)
+        );
+    }
+
+    #[test]
+    fn test_current_generation() {
+        let store = Memory::default();
+        assert_eq!(block_on(current_generation(&store, Namespace::Api, 0)), 0);
+
+        // Purges by other isolates are picked up once the remembered generation expires
+        block_on(store.put("api", "3".to_owned(), None)).unwrap();
+        assert_eq!(block_on(current_generation(&store, Namespace::Api, 10)), 0);
+        let expired = consts::CACHE_GENERATION_TTL;
+        assert_eq!(
+            block_on(current_generation(&store, Namespace::Api, expired)),
+            3
+        );
+        assert_eq!(block_on(generation(&store, Namespace::Api)).unwrap(), 3);
+
+        // Purges by this isolate are visible immediately
+        assert_eq!(block_on(purge(&store, Namespace::Api)).unwrap(), 4);
+        assert_eq!(
+            block_on(current_generation(&store, Namespace::Api, expired)),
+            4
+        );
+    }
+}
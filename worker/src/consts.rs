@@ -3,18 +3,111 @@
 const ONE_KB: usize = 1024;
 
 pub const MAX_UPLOAD_SIZE: usize = 50 * ONE_KB;
+/// Upload size limit of an API key, unless it is issued with a different limit.
+pub const API_KEY_UPLOAD_SIZE: usize = 500 * ONE_KB;
+/// Highest upload size limit an API key can be issued with.
+pub const API_KEY_MAX_UPLOAD_SIZE: usize = 2 * 1024 * ONE_KB;
+/// Uploads per day of an API key, unless it is issued with a different quota.
+pub const API_KEY_DAILY_QUOTA: u32 = 1_000;
+pub const API_KEY_MAX_NAME_LENGTH: usize = 100;
+/// Random bytes of an API key, 24 bytes are 32 base64 characters.
+pub const API_KEY_BYTES: usize = 24;
+/// Usage of API keys is remembered per isolate, see [`crate::api_key::reserve_upload`].
+pub const API_KEY_USAGE_TTL: u64 = HOUR as u64 * 1000;
+pub const API_KEY_USAGE_CAPACITY: usize = 1_000;
+
+/// Limits for user provided paste metadata in characters.
+///
+/// B2 additionally limits the URI encoded metadata in bytes, see `storage::b2`.
+pub const MAX_TITLE_LENGTH: usize = 100;
+pub const MAX_DESCRIPTION_LENGTH: usize = 500;
+pub const MAX_TAGS: usize = 10;
+pub const MAX_TAG_LENGTH: usize = 30;
+pub const MAX_REPORT_LENGTH: usize = 1000;
+/// Reports per client within the window in milliseconds, counted per isolate.
+pub const REPORTS_PER_WINDOW: u32 = 10;
+pub const REPORTS_WINDOW: u64 = HOUR as u64 * 1000;
+pub const REPORTS_CLIENTS_CAPACITY: usize = 1_000;
+/// Anonymous uploads per client within the window in milliseconds, counted per isolate.
+pub const UPLOADS_PER_WINDOW: u32 = 30;
+pub const UPLOADS_WINDOW: u64 = 10 * 60 * 1000;
+pub const UPLOADS_CLIENTS_CAPACITY: usize = 1_000;
+
+/// Amount of SHA-1 bytes used for a paste id, 9 bytes are 12 base64 characters.
+pub const PASTE_ID_MIN_BYTES: usize = 9;
+/// On a collision the id is extended by 3 bytes (4 characters) up to this limit.
+pub const PASTE_ID_MAX_BYTES: usize = 18;
+/// Resolved ids are remembered per isolate, identical uploads skip the collision lookups.
+pub const RESOLVED_IDS_TTL: u64 = HOUR as u64 * 1000;
+pub const RESOLVED_IDS_CAPACITY: usize = 1_000;
+/// Random bytes of a private paste id, 12 bytes are 16 base64 characters.
+pub const PASTE_ID_PRIVATE_BYTES: usize = 12;
+/// Random bytes of a view key for private pastes.
+pub const VIEW_KEY_BYTES: usize = 18;
+
+/// Default and maximum amount of search results per page.
+pub const SEARCH_PAGE_SIZE: usize = 20;
+pub const SEARCH_MAX_PAGE_SIZE: usize = 50;
+/// Maximum amount of index listings for a single search page.
+pub const SEARCH_MAX_SCANS: usize = 5;
+
+/// Amount of pastes in the list of recent uploads and feeds.
+pub const RECENT_MAX_PASTES: usize = 50;
+
+/// Pastes listed at once for a sitemap page, Workers KV lists at most 1000 keys at once.
+pub const SITEMAP_LIST_LIMIT: u64 = 1_000;
+/// Sitemaps and sitemap indexes must not contain more than 50,000 urls.
+pub const SITEMAP_MAX_URLS: usize = 50_000;
+/// Creation time of the oldest paste, the sitemap index starts with its period.
+pub const SITEMAP_EPOCH: u64 = 1_640_995_200_000;
 
 pub const KV_STATIC_CONTENT: &str = "__STATIC_CONTENT";
 pub const KV_B2_CREDENTIALS: &str = "B2_CREDENTIALS";
 pub const KV_PASTE_STORAGE: &str = "PASTE_STORAGE";
+pub const KV_PASTE_INDEX: &str = "PASTE_INDEX";
+pub const KV_MODERATION: &str = "MODERATION";
+pub const KV_PREVIEWS: &str = "PREVIEWS";
+pub const KV_CACHE_GENERATIONS: &str = "CACHE_GENERATIONS";
+
+pub const ANALYTICS_METRICS: &str = "METRICS";
 
 pub const ENV_B2_KEY_ID: &str = "B2_KEY_ID";
 pub const ENV_B2_APPLICATION_KEY: &str = "B2_APPLICATION_KEY";
+/// Optional, without a public url pastes are downloaded with the B2 credentials.
 pub const ENV_B2_PUBLIC_FILE_URL: &str = "B2_PUBLIC_FILE_URL";
+pub const ENV_ADMIN_TOKEN: &str = "ADMIN_TOKEN";
 pub const ENV_SENTRY_PROJECT: &str = "SENTRY_PROJECT";
 pub const ENV_SENTRY_TOKEN: &str = "SENTRY_TOKEN";
+pub const ENV_SENTRY_ENVIRONMENT: &str = "SENTRY_ENVIRONMENT";
+pub const ENV_CSP_FRAME_ANCESTORS: &str = "CSP_FRAME_ANCESTORS";
+pub const ENV_CSP_REPORT_URI: &str = "CSP_REPORT_URI";
+/// JSON list of webhooks notified about new public pastes, see [`crate::webhook`].
+pub const ENV_WEBHOOKS: &str = "WEBHOOKS";
+/// Comma separated paths excluded in the `robots.txt`, see [`crate::sitemap::Robots`].
+pub const ENV_ROBOTS_DISALLOW: &str = "ROBOTS_DISALLOW";
 
 const HOUR: u32 = 3_600;
 const DAY: u32 = 24 * HOUR;
 
 pub const CACHE_ASSETS: u32 = 2 * DAY;
+/// Fingerprinted assets never change, a new build references new file names.
+pub const CACHE_IMMUTABLE: u32 = 365 * DAY;
+pub const CACHE_PREVIEW: u32 = 7 * DAY;
+/// Rendered previews are kept for a while, previews of popular pastes are rendered only once.
+pub const STORED_PREVIEW_TTL: u64 = 30 * DAY as u64;
+pub const CACHE_SEARCH: u32 = 60;
+pub const CACHE_RECENT: u32 = 5 * 60;
+pub const CACHE_SITEMAP: u32 = 6 * HOUR;
+pub const CACHE_ROBOTS: u32 = DAY;
+/// Cache generations are remembered per isolate for this many milliseconds, purges take up to
+/// this long to reach all isolates.
+pub const CACHE_GENERATION_TTL: u64 = 10 * 1000;
+/// The status is checked at most once per isolate in this many milliseconds, monitors polling
+/// it do not cause requests to the storage every time.
+pub const STATUS_TTL: u64 = 30 * 1000;
+
+/// Amount of pastes looked at per scheduled job run.
+pub const JOB_REPARSE_BATCH: u64 = 20;
+pub const JOB_COMPACT_BATCH: u64 = 100;
+/// Credentials are refreshed once they are older than this, in milliseconds.
+pub const JOB_CREDENTIALS_MAX_AGE: u64 = 10 * HOUR as u64 * 1000;
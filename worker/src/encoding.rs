@@ -0,0 +1,124 @@
+/// Content encodings the worker can respond with.
+///
+/// The worker itself never compresses response bodies, only precompressed assets are sent
+/// encoded. Those are never stored in the edge cache, its keys do not contain the encoding.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Identity,
+}
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gzip",
+            Self::Identity => "identity",
+        }
+    }
+
+    /// File extension of precompressed files, e.g. `index.js.br`.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Self::Brotli => Some("br"),
+            Self::Gzip => Some("gz"),
+            Self::Identity => None,
+        }
+    }
+
+    /// Picks the preferred encoding from an `Accept-Encoding` header.
+    pub fn negotiate(accept_encoding: Option<&str>) -> Self {
+        let accept_encoding = match accept_encoding {
+            Some(accept_encoding) => accept_encoding,
+            None => return Self::Identity,
+        };
+
+        let mut result = (Self::Identity, 0.0);
+        for item in accept_encoding.split(',') {
+            let (name, quality) = parse_item(item);
+
+            let encoding = match name {
+                "br" => Self::Brotli,
+                "gzip" | "x-gzip" => Self::Gzip,
+                _ => continue,
+            };
+
+            if quality > result.1 || (quality == result.1 && encoding == Self::Brotli) {
+                result = (encoding, quality);
+            }
+        }
+
+        result.0
+    }
+
+    /// All encodings accepted by an `Accept-Encoding` header, the preferred encoding first.
+    ///
+    /// The identity encoding is not included, it is always acceptable.
+    pub fn accepted(accept_encoding: Option<&str>) -> Vec<Self> {
+        let mut accepted = accept_encoding
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|item| {
+                let (name, quality) = parse_item(item);
+                let encoding = match name {
+                    "br" => Self::Brotli,
+                    "gzip" | "x-gzip" => Self::Gzip,
+                    _ => return None,
+                };
+                (quality > 0.0).then_some((encoding, quality))
+            })
+            .collect::<Vec<_>>();
+
+        // Stable sort, on equal quality Brotli is preferred like in `negotiate`
+        accepted.sort_by_key(|(encoding, _)| *encoding != Self::Brotli);
+        accepted.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        accepted.dedup_by_key(|(encoding, _)| *encoding);
+
+        accepted.into_iter().map(|(encoding, _)| encoding).collect()
+    }
+}
+
+fn parse_item(item: &str) -> (&str, f32) {
+    let mut parts = item.split(';').map(str::trim);
+    let name = parts.next().unwrap_or("");
+
+    let quality = parts
+        .find_map(|param| param.strip_prefix("q="))
+        .and_then(|q| q.parse().ok())
+        .unwrap_or(1.0);
+
+    (name, quality)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(Encoding::Identity, Encoding::negotiate(None));
+        assert_eq!(Encoding::Identity, Encoding::negotiate(Some("")));
+        assert_eq!(Encoding::Identity, Encoding::negotiate(Some("deflate")));
+        assert_eq!(Encoding::Gzip, Encoding::negotiate(Some("gzip")));
+        assert_eq!(
+            Encoding::Brotli,
+            Encoding::negotiate(Some("gzip, deflate, br"))
+        );
+        assert_eq!(Encoding::Gzip, Encoding::negotiate(Some("br;q=0.5, gzip")));
+        assert_eq!(Encoding::Identity, Encoding::negotiate(Some("gzip;q=0")));
+    }
+
+    #[test]
+    fn test_accepted() {
+        use Encoding::*;
+
+        assert_eq!(Encoding::accepted(None), []);
+        assert_eq!(
+            Encoding::accepted(Some("gzip, deflate, br")),
+            [Brotli, Gzip]
+        );
+        assert_eq!(Encoding::accepted(Some("br;q=0.5, gzip")), [Gzip, Brotli]);
+        assert_eq!(Encoding::accepted(Some("br;q=0, gzip, gzip")), [Gzip]);
+    }
+}
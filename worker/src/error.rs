@@ -8,6 +8,19 @@ pub enum Error {
     #[error("The requested {0} with id {1} does not exist")]
     NotFound(&'static str, String),
 
+    #[error("The requested {0} with id {1} is no longer available")]
+    Gone(&'static str, String),
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    /// The daily upload quota of an API key is used up.
+    #[error("The daily quota of {0} uploads is exceeded")]
+    QuotaExceeded(u32),
+
+    #[error("Too many requests, try again later")]
+    RateLimited,
+
     #[error("Request failed {0}: {1}")]
     RemoteFailed(u16, String),
 
@@ -23,9 +36,17 @@ pub enum Error {
     #[error("{0}")]
     BadRequest(String),
 
+    /// A valid request for something the worker does not support, e.g. an oEmbed format.
+    #[error("{0}")]
+    NotImplemented(String),
+
+    /// The export could not be decoded.
+    #[error(transparent)]
+    Pob(pob::Error),
+
     #[error("{0}")]
     // error, XML
-    InvalidPoB(String, String),
+    InvalidPoB(pob::Error, String),
 
     #[error("{0}")]
     Error(String),
@@ -35,24 +56,55 @@ impl Error {
     pub fn name(&self) -> &'static str {
         match self {
             Self::NotFound(..) => "NotFound",
+            Self::Gone(..) => "Gone",
+            Self::Unauthorized => "Unauthorized",
+            Self::QuotaExceeded(..) => "QuotaExceeded",
+            Self::RateLimited => "RateLimited",
             Self::RemoteFailed(..) => "Remote Failed",
             Self::Serde(..) => "Serde",
             Self::Kv(..) => "Kv",
             Self::Worker(..) => "Worker",
             Self::BadRequest(..) => "BadRequest",
+            Self::NotImplemented(..) => "NotImplemented",
+            Self::Pob(..) => "Pob",
             Self::InvalidPoB(..) => "InvalidPoB",
             Self::Error(..) => "Error",
         }
     }
 
+    /// Stable identifier of the error for API clients, unlike the message it never changes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound(..) => "not_found",
+            Self::Gone(..) => "gone",
+            Self::Unauthorized => "unauthorized",
+            Self::QuotaExceeded(..) => "quota_exceeded",
+            Self::RateLimited => "rate_limited",
+            Self::RemoteFailed(..) => "remote_failed",
+            Self::Serde(..) => "json",
+            Self::Kv(..) => "kv",
+            Self::Worker(err) => worker_code(err),
+            Self::BadRequest(..) => "bad_request",
+            Self::NotImplemented(..) => "not_implemented",
+            Self::Pob(err) | Self::InvalidPoB(err, _) => err.code(),
+            Self::Error(..) => "internal",
+        }
+    }
+
     pub fn level(&self) -> &'static str {
         match self {
             Self::NotFound(..) => "info",
+            Self::Gone(..) => "info",
+            Self::Unauthorized => "warning",
+            Self::QuotaExceeded(..) => "info",
+            Self::RateLimited => "info",
             Self::RemoteFailed(..) => "warning",
             Self::Serde(..) => "error",
             Self::Kv(..) => "error",
             Self::Worker(..) => "error",
             Self::BadRequest(..) => "info",
+            Self::NotImplemented(..) => "info",
+            Self::Pob(..) => "info",
             Self::InvalidPoB(..) => "error",
             Self::Error(..) => "error",
         }
@@ -64,6 +116,31 @@ impl Error {
             _ => None,
         }
     }
+
+    /// Where parsing failed, for errors of the PoB parser.
+    pub fn details(&self) -> Option<ErrorDetails> {
+        let err = match self {
+            Self::Pob(err) | Self::InvalidPoB(err, _) => err,
+            _ => return None,
+        };
+        let location = err.xml_location().cloned().unwrap_or_default();
+
+        Some(ErrorDetails {
+            offset: err.offset(),
+            path: location.path,
+            field: location.field,
+        })
+    }
+}
+
+fn worker_code(err: &worker::Error) -> &'static str {
+    match err {
+        worker::Error::BindingError(..) => "worker_binding",
+        worker::Error::BodyUsed => "worker_body_used",
+        worker::Error::Json(..) | worker::Error::SerdeJsonError(..) => "worker_json",
+        worker::Error::JsError(..) | worker::Error::Internal(..) => "worker_js",
+        _ => "worker",
+    }
 }
 
 impl From<String> for Error {
@@ -87,24 +164,81 @@ impl From<wasm_bindgen::JsValue> for Error {
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
     pub code: u16,
+    /// Stable identifier of the error, see [`Error::code`].
+    pub error: &'static str,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<ErrorDetails>,
+    /// Id of the failed request, see [`request_id`](crate::request_id).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Location of a parser error, fields are omitted if they are unknown.
+#[derive(Debug, Default, Serialize)]
+pub struct ErrorDetails {
+    /// Byte offset into the export or the decompressed XML.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    /// Path of the XML element, e.g. `PathOfBuilding/Skills`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Name of a missing XML element or attribute.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
 }
 
 impl From<Error> for ErrorResponse {
     fn from(err: Error) -> Self {
-        match err {
-            err @ Error::NotFound(..) => ErrorResponse {
-                code: 404,
-                message: err.to_string(),
-            },
-            err @ Error::BadRequest(..) | err @ Error::InvalidPoB(..) => ErrorResponse {
-                code: 400,
-                message: err.to_string(),
-            },
-            err => ErrorResponse {
-                code: 500,
-                message: err.to_string(),
-            },
+        let code = match err {
+            Error::NotFound(..) => 404,
+            Error::Gone(..) => 410,
+            Error::Unauthorized => 401,
+            Error::QuotaExceeded(..) | Error::RateLimited => 429,
+            Error::BadRequest(..) | Error::Pob(..) | Error::InvalidPoB(..) => 400,
+            Error::NotImplemented(..) => 501,
+            _ => 500,
+        };
+
+        ErrorResponse {
+            code,
+            error: err.code(),
+            message: err.to_string(),
+            details: err.details(),
+            request_id: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pob::SerdePathOfBuilding;
+    use serde_json::json;
+
+    #[test]
+    fn test_error_response() {
+        let err = Error::NotFound("paste", "abc".to_owned());
+        assert_eq!(
+            serde_json::to_value(ErrorResponse::from(err)).unwrap(),
+            json!({
+                "code": 404,
+                "error": "not_found",
+                "message": "The requested paste with id abc does not exist",
+            })
+        );
+
+        let xml = "<PathOfBuilding><Build></Skills>";
+        let err = SerdePathOfBuilding::from_xml(xml).unwrap_err();
+        let response = ErrorResponse::from(Error::InvalidPoB(err, xml.to_owned()));
+        assert_eq!(response.code, 400);
+        assert_eq!(response.error, "invalid_xml");
+        assert_eq!(
+            serde_json::to_value(response.details).unwrap(),
+            json!({"offset": 25, "path": "PathOfBuilding/Build"})
+        );
+
+        let response = ErrorResponse::from(Error::QuotaExceeded(10));
+        assert_eq!((response.code, response.error), (429, "quota_exceeded"));
+    }
+}
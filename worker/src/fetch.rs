@@ -0,0 +1,152 @@
+use crate::{future::LocalBoxFuture, Result};
+use worker::{js_sys, Fetch, Headers, Method, Request, RequestInit, Url};
+
+pub struct FetchResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl FetchResponse {
+    /// Value of the first header with the name, header names are case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Outgoing HTTP requests, abstracted so tests can stub remote sites.
+pub trait Fetcher {
+    fn get<'a>(&'a self, url: &'a str) -> LocalBoxFuture<'a, Result<FetchResponse>>;
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        body: Vec<u8>,
+    ) -> LocalBoxFuture<'a, Result<FetchResponse>>;
+}
+
+/// Fetches through the Workers runtime.
+pub struct WorkerFetcher;
+
+impl Fetcher for WorkerFetcher {
+    fn get<'a>(&'a self, url: &'a str) -> LocalBoxFuture<'a, Result<FetchResponse>> {
+        Box::pin(async move {
+            let url = Url::parse(url).map_err(|e| e.to_string())?;
+            into_response(Fetch::Url(url).send().await?).await
+        })
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        body: Vec<u8>,
+    ) -> LocalBoxFuture<'a, Result<FetchResponse>> {
+        Box::pin(async move {
+            let mut request_headers = Headers::new();
+            for (name, value) in headers {
+                request_headers.set(name, value)?;
+            }
+
+            let request = Request::new_with_init(
+                url,
+                &RequestInit {
+                    method: Method::Post,
+                    headers: request_headers,
+                    body: Some(js_sys::Uint8Array::from(body.as_slice()).into()),
+                    ..Default::default()
+                },
+            )?;
+
+            into_response(Fetch::Request(request).send().await?).await
+        })
+    }
+}
+
+async fn into_response(mut response: worker::Response) -> Result<FetchResponse> {
+    Ok(FetchResponse {
+        status: response.status_code(),
+        headers: response.headers().into_iter().collect(),
+        body: response.bytes().await?,
+    })
+}
+
+/// Url, headers and body of a post request to the [`Stub`].
+#[cfg(test)]
+pub type StubPost = (String, Vec<(String, String)>, Vec<u8>);
+
+/// Responds with canned responses and records all requested urls and posted bodies.
+#[cfg(test)]
+#[derive(Default)]
+pub struct Stub {
+    responses: std::cell::RefCell<std::collections::HashMap<String, Vec<(u16, String)>>>,
+    requests: std::cell::RefCell<Vec<String>>,
+    posts: std::cell::RefCell<Vec<StubPost>>,
+}
+
+#[cfg(test)]
+impl Stub {
+    /// Adds a response for the url, multiple responses for a url are returned in order
+    /// and the last one is repeated.
+    pub fn with(self, url: &str, status: u16, body: &str) -> Self {
+        self.responses
+            .borrow_mut()
+            .entry(url.to_owned())
+            .or_default()
+            .push((status, body.to_owned()));
+        self
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.borrow().clone()
+    }
+
+    pub fn posts(&self) -> Vec<StubPost> {
+        self.posts.borrow().clone()
+    }
+
+    fn respond(&self, url: &str) -> LocalBoxFuture<'static, Result<FetchResponse>> {
+        self.requests.borrow_mut().push(url.to_owned());
+
+        let mut responses = self.responses.borrow_mut();
+        let (status, body) = match responses.get_mut(url) {
+            Some(responses) if responses.len() > 1 => responses.remove(0),
+            Some(responses) => responses[0].clone(),
+            None => (404, String::new()),
+        };
+
+        Box::pin(async move {
+            Ok(FetchResponse {
+                status,
+                headers: Vec::new(),
+                body: body.into_bytes(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+impl Fetcher for Stub {
+    fn get<'a>(&'a self, url: &'a str) -> LocalBoxFuture<'a, Result<FetchResponse>> {
+        self.respond(url)
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        body: Vec<u8>,
+    ) -> LocalBoxFuture<'a, Result<FetchResponse>> {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        self.posts
+            .borrow_mut()
+            .push((url.to_owned(), headers, body));
+        self.respond(url)
+    }
+}
@@ -0,0 +1,183 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread::LocalKey;
+
+pub type LocalBoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// Future which sets a thread local to its value only while the inner future is polled.
+///
+/// Requests are handled concurrently on a single thread, a plain thread local would be
+/// shared by all requests in flight. Code running on behalf of a request only runs while
+/// the future of that request is polled, which makes the value local to the request.
+pub struct Scoped<'a, V: 'static, T> {
+    key: &'static LocalKey<RefCell<Option<V>>>,
+    value: Option<V>,
+    future: LocalBoxFuture<'a, T>,
+}
+
+/// Runs `future` with `key` set to `value`, see [`Scoped`].
+pub fn scoped<'a, V, T>(
+    key: &'static LocalKey<RefCell<Option<V>>>,
+    value: V,
+    future: impl Future<Output = T> + 'a,
+) -> Scoped<'a, V, T> {
+    Scoped {
+        key,
+        value: Some(value),
+        future: Box::pin(future),
+    }
+}
+
+impl<V: Unpin, T> Future for Scoped<'_, V, T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let this = self.get_mut();
+        let previous = this.key.with(|slot| slot.replace(this.value.take()));
+        let result = this.future.as_mut().poll(cx);
+        this.value = this.key.with(|slot| slot.replace(previous));
+        result
+    }
+}
+
+/// Future which polls all futures concurrently and completes once all of them completed.
+pub struct JoinAll<'a, T> {
+    futures: Vec<(LocalBoxFuture<'a, T>, Option<T>)>,
+}
+
+/// Runs all `futures` concurrently, see [`JoinAll`].
+pub fn join_all<'a, T>(futures: Vec<LocalBoxFuture<'a, T>>) -> JoinAll<'a, T> {
+    JoinAll {
+        futures: futures.into_iter().map(|future| (future, None)).collect(),
+    }
+}
+
+impl<T: Unpin> Future for JoinAll<'_, T> {
+    type Output = Vec<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<T>> {
+        let this = self.get_mut();
+        let mut pending = false;
+        for (future, result) in this.futures.iter_mut() {
+            if result.is_none() {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(value) => *result = Some(value),
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+
+        if pending {
+            return Poll::Pending;
+        }
+        let results = std::mem::take(&mut this.futures)
+            .into_iter()
+            .filter_map(|(_, result)| result)
+            .collect();
+        Poll::Ready(results)
+    }
+}
+
+/// Drives a future to completion on the current thread, for tests and the native server.
+///
+/// Futures must not depend on the JS event loop, e.g. only use in-memory stubs or
+/// blocking I/O, a pending future is polled in a busy loop.
+#[cfg(any(test, feature = "server"))]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    struct Noop;
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let waker = Waker::from(Arc::new(Noop));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+            return result;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    thread_local!(static VALUE: RefCell<Option<u32>> = RefCell::new(None));
+
+    fn current() -> Option<u32> {
+        VALUE.with(|value| *value.borrow())
+    }
+
+    /// Pending once, lets another future run in between.
+    struct Yield(bool);
+
+    impl Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            match std::mem::replace(&mut self.0, true) {
+                true => Poll::Ready(()),
+                false => Poll::Pending,
+            }
+        }
+    }
+
+    #[test]
+    fn test_scoped() {
+        let task = |value| {
+            scoped(&VALUE, value, async move {
+                assert_eq!(current(), Some(value));
+                Yield(false).await;
+                assert_eq!(current(), Some(value));
+                value
+            })
+        };
+
+        // Both futures are in flight at the same time, each sees its own value
+        let mut a = task(1);
+        let mut b = task(2);
+        let waker = std::task::Waker::from(std::sync::Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut a).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut b).poll(&mut cx).is_pending());
+        assert_eq!(current(), None);
+        assert_eq!(Pin::new(&mut a).poll(&mut cx), Poll::Ready(1));
+        assert_eq!(Pin::new(&mut b).poll(&mut cx), Poll::Ready(2));
+        assert_eq!(current(), None);
+    }
+
+    #[test]
+    fn test_join_all() {
+        let log = RefCell::new(Vec::new());
+        let task = |name, yields| {
+            let log = &log;
+            Box::pin(async move {
+                log.borrow_mut().push(name);
+                if yields {
+                    Yield(false).await;
+                }
+                log.borrow_mut().push(name);
+                name
+            }) as LocalBoxFuture<_>
+        };
+
+        // The second future starts before the first one completes
+        let results = block_on(join_all(vec![task("a", true), task("b", false)]));
+        assert_eq!(results, vec!["a", "b"]);
+        assert_eq!(*log.borrow(), vec!["a", "b", "b", "a"]);
+
+        assert!(block_on(join_all::<()>(Vec::new())).is_empty());
+    }
+
+    struct Noop;
+
+    impl std::task::Wake for Noop {
+        fn wake(self: std::sync::Arc<Self>) {}
+    }
+}
@@ -0,0 +1,319 @@
+//! Platform independent HTTP request and response types.
+//!
+//! Requests are fully buffered, which is fine for the small bodies the service accepts.
+
+use crate::{encoding::Encoding, utils::ResponseExt, Error, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use worker::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Options,
+    Other,
+}
+
+impl Method {
+    pub fn parse(method: &str) -> Self {
+        match method.to_ascii_uppercase().as_str() {
+            "GET" => Self::Get,
+            "HEAD" => Self::Head,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "OPTIONS" => Self::Options,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: Method,
+    url: Url,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    request_id: Option<String>,
+}
+
+impl Request {
+    pub fn new(method: Method, url: Url, headers: Vec<(String, String)>, body: Vec<u8>) -> Self {
+        Self {
+            method,
+            url,
+            headers,
+            body,
+            request_id: None,
+        }
+    }
+
+    /// Sets the id of the request, see [`request_id`](crate::request_id).
+    pub fn with_request_id(mut self, request_id: String) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn path(&self) -> &str {
+        self.url.path()
+    }
+
+    /// Value of the first header with the name, header names are case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        crate::utils::query_param(&self.url, name)
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    /// Essence of the content type, e.g. `multipart/form-data`, lowercased.
+    pub fn content_type(&self) -> String {
+        self.header("Content-Type")
+            .unwrap_or_default()
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+    }
+
+    /// Parses a `multipart/form-data` body.
+    pub fn form(&self) -> Result<Form> {
+        let boundary = self
+            .header("Content-Type")
+            .unwrap_or_default()
+            .split(';')
+            .find_map(|param| param.trim().strip_prefix("boundary="))
+            .map(|boundary| boundary.trim_matches('"'))
+            .filter(|boundary| !boundary.is_empty())
+            .ok_or_else(|| Error::BadRequest("Missing multipart boundary".to_owned()))?;
+
+        Form::parse(&self.body, boundary)
+    }
+}
+
+/// Fields of a `multipart/form-data` body, files are treated like any other field.
+#[derive(Debug, Default)]
+pub struct Form {
+    fields: Vec<(String, Vec<u8>)>,
+}
+
+impl Form {
+    fn parse(body: &[u8], boundary: &str) -> Result<Self> {
+        let invalid = || Error::BadRequest("Invalid multipart body".to_owned());
+        let delimiter = format!("--{}", boundary);
+
+        let mut fields = Vec::new();
+        // The first part is the (usually empty) preamble
+        for part in split(body, delimiter.as_bytes()).skip(1) {
+            // The closing delimiter is followed by `--`
+            if part.starts_with(b"--") {
+                return Ok(Self { fields });
+            }
+
+            let part = part.strip_prefix(b"\r\n").unwrap_or(part);
+            let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+            let (head, value) = split_once(part, b"\r\n\r\n").ok_or_else(invalid)?;
+
+            let head = String::from_utf8_lossy(head);
+            let name = head
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .filter(|(name, _)| name.trim().eq_ignore_ascii_case("content-disposition"))
+                .flat_map(|(_, value)| value.split(';'))
+                .find_map(|param| param.trim().strip_prefix("name="))
+                .map(|name| name.trim_matches('"').to_owned())
+                .ok_or_else(invalid)?;
+
+            fields.push((name, value.to_vec()));
+        }
+
+        Err(invalid())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Field as text, invalid UTF-8 is replaced.
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.get(name)
+            .map(|value| String::from_utf8_lossy(value).into_owned())
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn split_once<'a>(data: &'a [u8], needle: &[u8]) -> Option<(&'a [u8], &'a [u8])> {
+    let index = find(data, needle)?;
+    Some((&data[..index], &data[index + needle.len()..]))
+}
+
+fn split<'a>(mut data: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = &'a [u8]> + 'a {
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        match split_once(data, needle) {
+            Some((part, rest)) => {
+                data = rest;
+                Some(part)
+            }
+            None => {
+                done = true;
+                Some(data)
+            }
+        }
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    encoded: bool,
+}
+
+impl Response {
+    pub fn from_bytes(body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body,
+            encoded: false,
+        }
+    }
+
+    pub fn ok(body: impl Into<String>) -> Self {
+        Self::from_bytes(body.into().into_bytes())
+    }
+
+    pub fn empty() -> Self {
+        Self::from_bytes(Vec::new())
+    }
+
+    pub fn from_html(html: impl Into<String>) -> Result<Self> {
+        Self::ok(html).with_content_type("text/html; charset=utf-8")
+    }
+
+    pub fn from_json<T: Serialize>(value: &T) -> Result<Self> {
+        Self::from_bytes(serde_json::to_vec(value)?).with_content_type("application/json")
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Marks the body as already compressed with the encoding, e.g. a precompressed asset.
+    ///
+    /// Unlike [`ResponseExt::with_content_encoding`] the body is sent as is.
+    pub fn with_encoded_body(mut self, encoding: Encoding) -> Result<Self> {
+        self.encoded = encoding != Encoding::Identity;
+        self.with_content_encoding(encoding)
+    }
+
+    pub fn is_encoded(&self) -> bool {
+        self.encoded
+    }
+
+    pub fn into_parts(self) -> (u16, Vec<(String, String)>, Vec<u8>) {
+        (self.status, self.headers, self.body)
+    }
+}
+
+impl ResponseExt for Response {
+    fn dup_headers(self) -> Self {
+        self
+    }
+
+    fn has_header(&self, name: &str) -> bool {
+        self.header(name).is_some()
+    }
+
+    fn with_header(mut self, name: &str, value: &str) -> Result<Self> {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_owned(), value.to_owned()));
+        Ok(self)
+    }
+
+    fn cloned(self) -> Result<(Self, Self)> {
+        Ok((self.clone(), self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_form() {
+        let body = b"preamble\r\n--xyz\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nMy Build\r\n--xyz\r\nContent-Disposition: form-data; name=\"content\"; filename=\"build.txt\"\r\nContent-Type: text/plain\r\n\r\neNrt\r\nxyz\r\n--xyz--\r\n";
+        let request = Request::new(
+            Method::Post,
+            Url::parse("https://pobb.in/api/v1/paste/").unwrap(),
+            vec![(
+                "content-type".to_owned(),
+                "multipart/form-data; boundary=\"xyz\"".to_owned(),
+            )],
+            body.to_vec(),
+        );
+
+        assert_eq!(request.content_type(), "multipart/form-data");
+        let form = request.form().unwrap();
+        assert_eq!(form.text("title").as_deref(), Some("My Build"));
+        assert_eq!(form.text("content").as_deref(), Some("eNrt\r\nxyz"));
+        assert_eq!(form.get("missing"), None);
+    }
+}
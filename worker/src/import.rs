@@ -0,0 +1,134 @@
+use crate::{consts, fetch::Fetcher, Error, Result};
+use worker::Url;
+
+/// Whether an uploaded paste is a link to a third party site instead of a build.
+pub fn is_import(data: &[u8]) -> bool {
+    let data = trim_start(data);
+    data.starts_with(b"https://") || data.starts_with(b"http://")
+}
+
+/// Fetches the raw build export from a supported third party paste url.
+pub async fn fetch(fetcher: &dyn Fetcher, url: &str) -> Result<String> {
+    let url = Url::parse(url.trim()).map_err(|_| Error::BadRequest("Invalid url".to_owned()))?;
+    let raw =
+        raw_url(&url).ok_or_else(|| Error::BadRequest(format!("Unsupported url '{}'", url)))?;
+
+    log::debug!("importing paste from '{}'", raw);
+    let response = fetcher.get(&raw).await?;
+
+    match response.status {
+        200 => (),
+        404 => return Err(Error::NotFound("paste", url.to_string())),
+        status => {
+            return Err(Error::RemoteFailed(
+                status,
+                format!(
+                    "failed to import paste from {}",
+                    url.host_str().unwrap_or("")
+                ),
+            ))
+        }
+    }
+
+    if response.body.len() > consts::MAX_UPLOAD_SIZE {
+        return Err(Error::BadRequest("Paste too large".to_owned()));
+    }
+
+    let content = String::from_utf8(response.body)
+        .map_err(|_| Error::BadRequest("Imported paste has invalid content".to_owned()))?;
+
+    Ok(content.trim().to_owned())
+}
+
+/// Maps the url of a supported site to the url of the raw paste.
+fn raw_url(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let segments = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    let raw = match (host, segments.as_slice()) {
+        ("pastebin.com", [id]) | ("pastebin.com", ["raw", id]) => {
+            format!("https://pastebin.com/raw/{}", id)
+        }
+        ("poe.ninja", ["pob", id]) | ("poe.ninja", ["pob", "raw", id]) => {
+            format!("https://poe.ninja/pob/raw/{}", id)
+        }
+        ("rentry.co", [id]) | ("rentry.co", [id, "raw"]) => {
+            format!("https://rentry.co/{}/raw", id)
+        }
+        _ => return None,
+    };
+
+    Some(raw)
+}
+
+fn trim_start(data: &[u8]) -> &[u8] {
+    let start = data
+        .iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    &data[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fetch::Stub, future::block_on};
+
+    fn raw(url: &str) -> Option<String> {
+        raw_url(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_is_import() {
+        assert!(is_import(b"https://pastebin.com/abc"));
+        assert!(is_import(b"  http://pastebin.com/abc\n"));
+        assert!(!is_import(
+            b"eNrtPWtz2ziS3_MrUP5wNVc3ssW3OHHuyrETx7vxY2w5mb2aKxdEQhZjitTyYVu1u"
+        ));
+    }
+
+    #[test]
+    fn test_raw_url() {
+        let pastebin = Some("https://pastebin.com/raw/abc".to_owned());
+        assert_eq!(raw("https://pastebin.com/abc"), pastebin);
+        assert_eq!(raw("https://www.pastebin.com/abc/"), pastebin);
+        assert_eq!(raw("https://pastebin.com/raw/abc"), pastebin);
+
+        let ninja = Some("https://poe.ninja/pob/raw/abc".to_owned());
+        assert_eq!(raw("https://poe.ninja/pob/abc"), ninja);
+        assert_eq!(raw("https://poe.ninja/pob/raw/abc"), ninja);
+
+        let rentry = Some("https://rentry.co/abc/raw".to_owned());
+        assert_eq!(raw("https://rentry.co/abc"), rentry);
+
+        assert_eq!(raw("https://pastebin.com/"), None);
+        assert_eq!(raw("https://example.com/abc"), None);
+    }
+
+    #[test]
+    fn test_fetch() {
+        let fetcher = Stub::default().with("https://pastebin.com/raw/abc", 200, "  eNrt\n");
+
+        let content = block_on(fetch(&fetcher, "https://pastebin.com/abc\n")).unwrap();
+        assert_eq!(content, "eNrt");
+        assert_eq!(fetcher.requests(), vec!["https://pastebin.com/raw/abc"]);
+    }
+
+    #[test]
+    fn test_fetch_errors() {
+        let fetcher = Stub::default().with("https://poe.ninja/pob/raw/abc", 500, "");
+
+        let err = block_on(fetch(&fetcher, "https://example.com/abc")).unwrap_err();
+        assert!(matches!(err, Error::BadRequest(_)));
+
+        let err = block_on(fetch(&fetcher, "https://pastebin.com/abc")).unwrap_err();
+        assert!(matches!(err, Error::NotFound("paste", _)));
+
+        let err = block_on(fetch(&fetcher, "https://poe.ninja/pob/abc")).unwrap_err();
+        assert!(matches!(err, Error::RemoteFailed(500, _)));
+    }
+}
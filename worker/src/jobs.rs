@@ -0,0 +1,295 @@
+use crate::{
+    consts, future::LocalBoxFuture, moderation, recent, search, storage::Pastes, store::Store,
+    Error, Result,
+};
+use pob::SerdePathOfBuilding;
+
+/// Maintenance jobs, in the order they are run by the scheduled event.
+pub const JOBS: &[&dyn Job] = &[&RefreshCredentials, &ReparseSample, &CompactIndexes];
+
+/// A maintenance job, run periodically by the scheduled event.
+///
+/// Jobs only process a bounded amount of work per run and continue on the next run.
+pub trait Job {
+    fn name(&self) -> &'static str;
+    fn run<'a>(&'a self, ctx: &'a JobContext<'a>) -> LocalBoxFuture<'a, Result<Outcome>>;
+}
+
+/// Cached storage credentials.
+pub trait Credentials {
+    /// Time in milliseconds since the epoch the credentials were requested.
+    fn created(&self) -> LocalBoxFuture<'_, Result<Option<u64>>>;
+    fn refresh(&self) -> LocalBoxFuture<'_, Result<()>>;
+    /// Makes an authorized request, to verify the storage accepts the credentials.
+    fn verify(&self) -> LocalBoxFuture<'_, Result<()>>;
+}
+
+#[cfg(not(feature = "storage-kv"))]
+impl Credentials for crate::storage::Credentials {
+    fn created(&self) -> LocalBoxFuture<'_, Result<Option<u64>>> {
+        Box::pin(self.created())
+    }
+
+    fn refresh(&self) -> LocalBoxFuture<'_, Result<()>> {
+        Box::pin(self.refresh())
+    }
+
+    fn verify(&self) -> LocalBoxFuture<'_, Result<()>> {
+        Box::pin(self.verify())
+    }
+}
+
+pub struct JobContext<'a> {
+    /// Time of the run in milliseconds since the epoch.
+    pub now: u64,
+    pub index: &'a dyn Store,
+    pub moderation: &'a dyn Store,
+    pub pastes: &'a dyn Pastes,
+    /// `None` if the storage backend does not use credentials.
+    pub credentials: Option<&'a dyn Credentials>,
+}
+
+/// Result of a single job run.
+#[derive(Debug, Default)]
+pub struct Outcome {
+    /// Amount of items the job acted on.
+    pub processed: usize,
+    /// Failures for individual pastes, which did not stop the job.
+    pub problems: Vec<(String, Error)>,
+}
+
+/// Requests new storage credentials before the cached ones expire.
+pub struct RefreshCredentials;
+
+impl Job for RefreshCredentials {
+    fn name(&self) -> &'static str {
+        "refresh-credentials"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a JobContext<'a>) -> LocalBoxFuture<'a, Result<Outcome>> {
+        Box::pin(async move {
+            let credentials = match ctx.credentials {
+                Some(credentials) => credentials,
+                None => return Ok(Outcome::default()),
+            };
+
+            let age = credentials
+                .created()
+                .await?
+                .map(|created| ctx.now.saturating_sub(created));
+            if matches!(age, Some(age) if age < consts::JOB_CREDENTIALS_MAX_AGE) {
+                return Ok(Outcome::default());
+            }
+
+            credentials.refresh().await?;
+            Ok(Outcome {
+                processed: 1,
+                ..Default::default()
+            })
+        })
+    }
+}
+
+/// Parses a rotating sample of stored pastes, to detect regressions of the parser.
+pub struct ReparseSample;
+
+impl Job for ReparseSample {
+    fn name(&self) -> &'static str {
+        "reparse-sample"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a JobContext<'a>) -> LocalBoxFuture<'a, Result<Outcome>> {
+        Box::pin(async move {
+            let mut outcome = Outcome::default();
+
+            let ids = next_ids(ctx.index, "jobs:reparse:cursor", consts::JOB_REPARSE_BATCH).await?;
+            for id in ids {
+                let paste = match ctx.pastes.get(&id).await {
+                    Ok(Some(paste)) => paste,
+                    // Missing pastes are cleaned up by compacting the indexes
+                    Ok(None) => continue,
+                    Err(err) => {
+                        outcome.problems.push((id, err));
+                        continue;
+                    }
+                };
+
+                let content = String::from_utf8_lossy(&paste.data);
+                if let Err(err) = SerdePathOfBuilding::from_export(&content) {
+                    let err = Error::InvalidPoB(err, content.into_owned());
+                    outcome.problems.push((id, err));
+                }
+                outcome.processed += 1;
+            }
+
+            Ok(outcome)
+        })
+    }
+}
+
+/// Removes index entries of pastes which were deleted or blocked.
+pub struct CompactIndexes;
+
+impl Job for CompactIndexes {
+    fn name(&self) -> &'static str {
+        "compact-indexes"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a JobContext<'a>) -> LocalBoxFuture<'a, Result<Outcome>> {
+        Box::pin(async move {
+            let mut outcome = Outcome::default();
+
+            let ids = next_ids(ctx.index, "jobs:compact:cursor", consts::JOB_COMPACT_BATCH).await?;
+            for id in ids {
+                let stale = moderation::blocked(ctx.moderation, &id).await?.is_some()
+                    || !ctx.pastes.exists(&id).await?;
+
+                if stale {
+                    search::remove(ctx.index, &id).await?;
+                    recent::remove(ctx.index, &id).await?;
+                    outcome.processed += 1;
+                }
+            }
+
+            Ok(outcome)
+        })
+    }
+}
+
+/// Next batch of indexed paste ids, continuing where the previous run stopped.
+///
+/// The position is persisted in `cursor_key`, after the last batch it starts over.
+async fn next_ids(store: &dyn Store, cursor_key: &str, limit: u64) -> Result<Vec<String>> {
+    let cursor = store.get(cursor_key).await?;
+    let (ids, cursor) = search::ids(store, cursor, limit).await?;
+
+    match cursor {
+        Some(cursor) => store.put(cursor_key, cursor, None).await?,
+        None => store.delete(cursor_key).await?,
+    }
+
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        future::block_on,
+        moderation::Action,
+        storage::{self, Metadata},
+        store,
+    };
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    struct StubCredentials {
+        created: Option<u64>,
+        refreshed: Cell<bool>,
+    }
+
+    impl Credentials for StubCredentials {
+        fn created(&self) -> LocalBoxFuture<'_, Result<Option<u64>>> {
+            Box::pin(async move { Ok(self.created) })
+        }
+
+        fn refresh(&self) -> LocalBoxFuture<'_, Result<()>> {
+            self.refreshed.set(true);
+            Box::pin(async move { Ok(()) })
+        }
+
+        fn verify(&self) -> LocalBoxFuture<'_, Result<()>> {
+            Box::pin(async move { Ok(()) })
+        }
+    }
+
+    fn entry(id: &str) -> search::Entry {
+        search::Entry {
+            id: id.to_owned(),
+            title: id.to_owned(),
+            ascendancy_or_class: "Slayer".to_owned(),
+            main_skill: None,
+            keystones: Vec::new(),
+            level: 90,
+            created: 1,
+        }
+    }
+
+    fn run(job: &dyn Job, ctx: &JobContext) -> Outcome {
+        block_on(job.run(ctx)).unwrap()
+    }
+
+    #[test]
+    fn test_refresh_credentials() {
+        let index = store::Memory::default();
+        let pastes = storage::Memory::default();
+        let now = 100 * consts::JOB_CREDENTIALS_MAX_AGE;
+
+        for (created, refreshed) in [
+            (None, true),
+            (Some(now - consts::JOB_CREDENTIALS_MAX_AGE), true),
+            (Some(now - 1), false),
+        ] {
+            let credentials = StubCredentials {
+                created,
+                ..Default::default()
+            };
+            let ctx = JobContext {
+                now,
+                index: &index,
+                moderation: &index,
+                pastes: &pastes,
+                credentials: Some(&credentials),
+            };
+            run(&RefreshCredentials, &ctx);
+            assert_eq!(credentials.refreshed.get(), refreshed);
+        }
+    }
+
+    #[test]
+    fn test_reparse_sample() {
+        let index = store::Memory::default();
+        let pastes = storage::Memory::default().with("broken", "garbage", Metadata::default());
+        block_on(search::add(&index, &entry("broken"))).unwrap();
+        block_on(search::add(&index, &entry("missing"))).unwrap();
+
+        let ctx = JobContext {
+            now: 0,
+            index: &index,
+            moderation: &index,
+            pastes: &pastes,
+            credentials: None,
+        };
+        let outcome = run(&ReparseSample, &ctx);
+        assert_eq!(outcome.processed, 1);
+        assert!(matches!(
+            outcome.problems.as_slice(),
+            [(id, Error::InvalidPoB(..))] if id == "broken"
+        ));
+    }
+
+    #[test]
+    fn test_compact_indexes() {
+        let index = store::Memory::default();
+        let moderation = store::Memory::default();
+        let pastes = storage::Memory::default()
+            .with("ok", "", Metadata::default())
+            .with("hidden", "", Metadata::default());
+        for id in ["ok", "hidden", "missing"] {
+            block_on(search::add(&index, &entry(id))).unwrap();
+        }
+        block_on(moderation::block(&moderation, "hidden", Action::Hidden)).unwrap();
+
+        let ctx = JobContext {
+            now: 0,
+            index: &index,
+            moderation: &moderation,
+            pastes: &pastes,
+            credentials: None,
+        };
+        assert_eq!(run(&CompactIndexes, &ctx).processed, 2);
+
+        let (ids, _) = block_on(search::ids(&index, None, 10)).unwrap();
+        assert_eq!(ids, ["ok"]);
+    }
+}
@@ -1,190 +1,378 @@
-use serde::Serialize;
 use std::future::Future;
-use worker::{event, Cache, Context, Env, Headers, Method, Request, Response};
+use std::rc::Rc;
+use worker::{
+    event, Cache, Context, Env, Headers, Method, Request, Response, ScheduleContext, ScheduledEvent,
+};
 
+mod admin;
 mod api;
+mod api_key;
 mod assets;
+mod cache;
 mod consts;
 mod crypto;
+mod encoding;
 mod error;
+mod fetch;
+mod future;
+mod http;
+mod import;
+mod jobs;
+mod memo;
+mod metrics;
+mod moderation;
+mod oembed;
+mod platform;
+mod preview;
+mod ratelimit;
+mod recent;
+mod request_id;
 mod retry;
+mod search;
+mod security;
 mod sentry;
+mod service;
+mod sitemap;
+mod status;
 mod storage;
+mod store;
 mod utils;
+mod webhook;
 
 pub use self::error::{Error, ErrorResponse, Result};
-use assets::EnvAssetExt;
+pub use self::request_id::current as current_request_id;
+#[cfg(feature = "server")]
+pub use self::{
+    platform::native::{serve, Config},
+    sitemap::Robots,
+    webhook::{parse as parse_webhooks, Webhook},
+};
 use sentry::Sentry;
 use utils::ResponseExt;
 
-async fn build_context(req: &Request, env: &Env, route: app::Route) -> Result<app::Context> {
-    // TODO: refactor this context garbage, maybe make it into a trait?
-    let host = req.url()?.host_str().unwrap().to_owned();
-    use app::{Context, Route::*};
-    let ctx = match route {
-        Index => Context::index(host),
-        NotFound => Context::not_found(host),
-        Paste(name) => match utils::to_path(&name) {
-            Err(_) => Context::not_found(host),
-            Ok(path) => {
-                if let Some(mut response) = storage::get(env, &path).await? {
-                    let content = response.text().await?;
-                    Context::paste(host, name, content)
-                } else {
-                    Context::not_found(host)
-                }
-            }
-        },
-    };
-
-    Ok(ctx)
-}
-
-#[derive(Serialize)]
-struct Oembed<'a> {
-    provider_name: &'a str,
-    provider_url: &'a str,
-}
-
 #[cfg(feature = "debug")]
 thread_local!(static LAST_LOG_MSG: std::cell::Cell<u64> = std::cell::Cell::new(0));
-#[cfg(feature = "debug")]
 static LOG_INIT: std::sync::Once = std::sync::Once::new();
 
 #[event(fetch)]
-pub async fn main(mut req: Request, env: Env, ctx: Context) -> worker::Result<Response> {
+pub async fn main(req: Request, env: Env, ctx: Context) -> worker::Result<Response> {
     #[cfg(feature = "debug")]
-    {
-        LAST_LOG_MSG.with(|last| last.set(worker::Date::now().as_millis()));
-        LOG_INIT.call_once(setup_logging);
-    }
+    LAST_LOG_MSG.with(|last| last.set(worker::Date::now().as_millis()));
+    LOG_INIT.call_once(setup_logging);
 
-    let err: ErrorResponse = match cached(&mut req, &env, &ctx, try_main).await {
-        Ok(response) => {
-            worker::console_log!(
-                "{:?} {} {}",
-                req.method(),
-                req.path(),
-                response.status_code()
-            );
-            return Ok(response);
-        }
+    let ray = req.headers().get("cf-ray")?;
+    let request_id = request_id::new(ray.as_deref(), |buf| Ok(crypto::fill_random(buf)?))
+        .map_err(|err| worker::Error::RustError(err.to_string()))?;
+
+    let scope = Rc::new(sentry::Scope::for_request(
+        Sentry::from_env(&env),
+        &req,
+        &request_id,
+    ));
+    let future = scope.run(handle(req, env, ctx, &scope, &request_id));
+    request_id::run(request_id.clone(), future).await
+}
+
+async fn handle(
+    mut req: Request,
+    env: Env,
+    ctx: Context,
+    scope: &sentry::Scope,
+    request_id: &str,
+) -> worker::Result<Response> {
+    metrics::init(&env);
+
+    let start = worker::Date::now().as_millis();
+    let (response, err) = match cached(&mut req, &env, &ctx, request_id, try_main).await {
+        Ok(response) => (response, None),
         Err(err) => {
             if let Some(sentry) = Sentry::from_env(&env) {
-                sentry.capture_err(&err, &req, &ctx);
+                sentry.capture_err(scope, &err, &req, request_id, &ctx);
             }
 
-            err.into()
+            let name = err.name();
+            let mut err: ErrorResponse = err.into();
+            err.request_id = Some(request_id.to_owned());
+            log::warn!("request failed: {}", err.message);
+
+            // Don't use ResponseExt here, it returns crate::Result
+            let mut headers = Headers::new();
+            headers.set("Content-Type", "application/json")?;
+            let response = Response::ok(serde_json::to_string(&err)?)?
+                .with_status(err.code)
+                .with_headers(headers);
+
+            (response, Some(name))
         }
     };
+    let response = security::apply(response, &security::Policy::from_env(&env))
+        .and_then(|response| response.with_header(request_id::HEADER, request_id))
+        .map_err(|err| worker::Error::RustError(err.to_string()))?;
 
-    // Don't use ResponseExt here, it returns crate::Result
-    let mut headers = Headers::new();
-    headers.set("Content-Type", "application/json")?;
-    let response = Response::ok(serde_json::to_string(&err)?)?
-        .with_status(err.code)
-        .with_headers(headers);
-
-    worker::console_warn!(
-        "{:?} {} {} {}",
-        req.method(),
-        req.path(),
-        response.status_code(),
-        err.message
-    );
+    metrics::emit(metrics::Event::Request {
+        method: &format!("{:?}", req.method()),
+        path: &req.path(),
+        transaction: transaction(&req),
+        status: response.status_code(),
+        duration_ms: worker::Date::now().as_millis() - start,
+        error: err,
+    });
 
     Ok(response)
 }
 
-async fn try_main(req: &mut Request, env: &Env, _ctx: &Context) -> Result<Response> {
-    if let Some(response) = api::try_handle(req, env).await? {
-        return Ok(response);
+#[event(scheduled)]
+pub async fn scheduled(_event: ScheduledEvent, env: Env, _ctx: ScheduleContext) {
+    LOG_INIT.call_once(setup_logging);
+    metrics::init(&env);
+
+    let scope = Rc::new(sentry::Scope::default());
+    if let Err(err) = scope.run(run_jobs(&env, &scope)).await {
+        log::error!("failed to run jobs: {}", err);
     }
+}
 
-    if req.path() == "/oembed.json" && req.method() == Method::Get {
-        let oembed = Oembed {
-            provider_name: "Paste of Exile - POB B.in",
-            provider_url: &format!("https://{}", req.url()?.host_str().unwrap()),
+async fn run_jobs(env: &Env, scope: &sentry::Scope) -> Result<()> {
+    let index = store::WorkerKv::new(env, consts::KV_PASTE_INDEX)?;
+    let moderation = store::WorkerKv::new(env, consts::KV_MODERATION)?;
+    #[cfg(not(feature = "storage-kv"))]
+    let credentials = storage::Credentials::from_env(env)?;
+    #[cfg(not(feature = "storage-kv"))]
+    let credentials = Some(&credentials as &dyn jobs::Credentials);
+    #[cfg(feature = "storage-kv")]
+    let credentials = None;
+
+    let ctx = jobs::JobContext {
+        now: worker::Date::now().as_millis(),
+        index: &index,
+        moderation: &moderation,
+        pastes: &storage::EnvPastes(env),
+        credentials,
+    };
+    let sentry = Sentry::from_env(env);
+
+    for job in jobs::JOBS {
+        let errors = match job.run(&ctx).await {
+            Ok(outcome) => {
+                log::info!(
+                    "job '{}' processed {} items with {} problems",
+                    job.name(),
+                    outcome.processed,
+                    outcome.problems.len()
+                );
+                outcome
+                    .problems
+                    .into_iter()
+                    .map(|(id, err)| (Some(id), err))
+                    .collect()
+            }
+            Err(err) => vec![(None, err)],
         };
-        return Response::from_json(&oembed)?.cache_for(12 * 3_600);
+
+        for (paste, err) in errors {
+            match &paste {
+                Some(paste) => log::warn!("job '{}' failed for '{}': {}", job.name(), paste, err),
+                None => log::warn!("job '{}' failed: {}", job.name(), err),
+            }
+            if let Some(sentry) = &sentry {
+                sentry
+                    .capture_job_err(scope, job.name(), paste.as_deref(), &err)
+                    .await;
+            }
+        }
     }
 
-    if let Some(response) = assets::try_handle(req, env).await? {
+    Ok(())
+}
+
+async fn try_main(
+    req: &mut Request,
+    env: &Env,
+    ctx: &Context,
+    request_id: &str,
+) -> Result<Response> {
+    if let Some(response) = admin::try_handle(req, env).await? {
         return Ok(response);
     }
 
-    let route = app::Route::resolve(&req.path());
-    let ctx = build_context(req, env, route).await?;
+    if let Some(response) = oembed::try_handle(req, env).await? {
+        return Ok(response);
+    }
 
-    let (app, rctx) = app::render_to_string(ctx);
-    let head = app::render_head(rctx.meta.unwrap_or_default());
+    if let Some(response) = preview::try_handle(req, env).await? {
+        return Ok(response);
+    }
 
-    let index = env.get_asset("index.html")?.text().await?.unwrap();
-    let index = index.replace("<!-- %head% -->", &head);
-    let index = index.replace("<!-- %app% -->", &app);
+    if let Some(response) = recent::try_handle(req, env).await? {
+        return Ok(response);
+    }
 
-    Response::from_html(index)?
-        .with_status(rctx.status_code)
-        .cache_for(3_600)
+    let platform = platform::WorkerPlatform::new(env)?.with_context(ctx);
+    let request = platform::request(req)
+        .await?
+        .with_request_id(request_id.to_owned());
+    platform::response(service::handle(&platform, &request).await?)
 }
 
 async fn cached<'a, F, Fut>(
     req: &'a mut Request,
     env: &'a Env,
     ctx: &'a worker::Context,
+    request_id: &'a str,
     f: F,
 ) -> Result<Response>
 where
-    F: Fn(&'a mut Request, &'a Env, &'a worker::Context) -> Fut,
+    F: Fn(&'a mut Request, &'a Env, &'a worker::Context, &'a str) -> Fut,
     Fut: Future<Output = Result<Response>> + 'a,
 {
     let cache = Cache::default();
-    let use_cache = req.method() == Method::Get;
+    let key = match req.method() == Method::Get {
+        true => {
+            let generations = store::WorkerKv::new(env, consts::KV_CACHE_GENERATIONS)?;
+            let now = worker::Date::now().as_millis();
+            Some(cache::current_key(&generations, &req.url()?, now).await)
+        }
+        false => None,
+    };
 
-    if use_cache {
-        if let Some(response) = cache.get(&*req, true).await? {
+    if let Some(key) = &key {
+        let response = cache.get(key.as_str(), true).await?;
+        metrics::emit(metrics::Event::Cache {
+            transaction: transaction(req),
+            hit: response.is_some(),
+        });
+
+        if let Some(response) = response {
             log::debug!("cache hit");
-            // TODO: 304 handling?
+
+            let etag = response.headers().get("Etag")?.unwrap_or_default();
+            let if_none_match = req.headers().get("If-None-Match")?;
+            if !etag.is_empty() && utils::etag_matches(if_none_match.as_deref(), &etag) {
+                let mut not_modified = Response::empty()?.with_status(304);
+                for name in ["Etag", "Cache-Control", "Vary"] {
+                    if let Some(value) = response.headers().get(name)? {
+                        not_modified.headers_mut().set(name, &value)?;
+                    }
+                }
+                return not_modified.with_header("Cf-Cache-Status", "HIT");
+            }
+
+            // Only full responses are cached, ranges are applied to them
+            if let Some(range) = req.headers().get("Range")? {
+                return cached_range(req, response, &range)
+                    .await?
+                    .with_header("Cf-Cache-Status", "HIT");
+            }
+
             return response
                 .dup_headers() // cached response has immutable headers
                 .with_header("Cf-Cache-Status", "HIT");
         }
     }
 
-    // I think I cannot get around this clone in current rust,
-    // if I use HRTBs for `F`, I cannot give `Fut` the correct lifetime.
-    // except by using a `BoxFuture`, which isn't really better.
-    // --> clone always (need to clone in most cases anyways)
-    let request_for_cache = req.clone()?;
-    let response = f(req, env, ctx).await?;
-    let req = request_for_cache;
-
-    if use_cache {
-        let (response, response_for_cache) = response.cloned()?;
-
-        ctx.wait_until(async move {
-            log::debug!("--> caching response");
-            let _ = cache.put(&req, response_for_cache).await;
-            log::debug!("<-- response cached");
-        });
+    let response = f(req, env, ctx, request_id).await?;
+
+    // Private responses, e.g. private pastes, must never end up in the shared cache
+    match key {
+        Some(key) if utils::is_cacheable(&response) => {
+            let (response, response_for_cache) = response.cloned()?;
+
+            ctx.wait_until(async move {
+                log::debug!("--> caching response");
+                let _ = cache.put(key.as_str(), response_for_cache).await;
+                log::debug!("<-- response cached");
+            });
+
+            response.with_header("Cf-Cache-Status", "MISS")
+        }
+        _ => Ok(response),
+    }
+}
+
+/// Applies the `Range` of the request to a full response from the cache, see [`assets::partial`].
+async fn cached_range(req: &Request, mut response: Response, range: &str) -> Result<Response> {
+    let headers = response.headers().clone();
+    let accepts_ranges = headers.get("Accept-Ranges")?.as_deref() == Some("bytes");
+    if response.status_code() != 200 || !accepts_ranges {
+        return Ok(response.dup_headers());
+    }
+
+    // Weak entity tags keep their `W/` prefix and never match `If-Range`
+    let etag = headers.get("Etag")?.unwrap_or_default();
+    let if_range = req.headers().get("If-Range")?;
+    let data = response.bytes().await?;
+    let partial = assets::partial(
+        Some(range),
+        if_range.as_deref(),
+        etag.trim_matches('"'),
+        &data,
+    )?;
+
+    let mut partial = match partial {
+        Some(partial) => platform::response(partial)?,
+        None => return Ok(Response::from_bytes(data)?.with_headers(headers)),
+    };
+    if partial.status_code() == 206 {
+        for name in ["Content-Type", "Etag", "Cache-Control", "Accept-Ranges"] {
+            if let Some(value) = headers.get(name)? {
+                partial.headers_mut().set(name, &value)?;
+            }
+        }
+    }
+
+    Ok(partial)
+}
 
-        response.with_header("Cf-Cache-Status", "MISS")
+/// Name of the route handling the request, used to group requests e.g. in Sentry.
+fn transaction(req: &Request) -> &'static str {
+    if let Some(route) = api::route(platform::method(req.method()), &req.path()) {
+        return route;
+    }
+    if let Some(route) = admin::route(req) {
+        return route;
+    }
+
+    let path = req.path();
+    if path == "/oembed.json" {
+        "/oembed.json"
+    } else if preview::is_preview_url(&path).is_some() {
+        "/<id>/preview.png"
+    } else if recent::is_feed_url(&path) {
+        "/recent.<feed>"
+    } else if sitemap::is_sitemap_url(&path) {
+        "/sitemap.xml"
+    } else if path == "/robots.txt" {
+        "/robots.txt"
+    } else if assets::is_asset_path(&path) {
+        "<asset>"
     } else {
-        Ok(response)
+        app::Route::resolve(&path).pattern()
     }
 }
 
+#[cfg(not(feature = "debug"))]
+fn setup_logging() {
+    std::panic::set_hook(Box::new(sentry::panic_hook));
+    let _ = log::set_logger(&sentry::BreadcrumbLogger);
+    log::set_max_level(log_level());
+}
+
 #[cfg(feature = "debug")]
 fn setup_logging() {
-    console_error_panic_hook::set_once();
-    let _ = fern::Dispatch::new()
+    std::panic::set_hook(Box::new(|info| {
+        console_error_panic_hook::hook(info);
+        sentry::panic_hook(info);
+    }));
+
+    let console = fern::Dispatch::new()
         .format(|out, message, record| {
             let now = worker::Date::now().as_millis();
             let last = LAST_LOG_MSG.with(|last| last.replace(now));
 
             out.finish(format_args!(
-                "[+ {:>5}] <{:<25}> {:>5}: {}",
+                "[+ {:>5}] [{}] <{:<25}> {:>5}: {}",
                 now - last,
+                request_id::current().unwrap_or_default(),
                 format!(
                     "{}:{}",
                     record.file().unwrap_or_else(|| record.target()),
@@ -194,7 +382,19 @@ fn setup_logging() {
                 message,
             ))
         })
-        .level(log::LevelFilter::Debug)
-        .chain(fern::Output::call(console_log::log))
+        .chain(fern::Output::call(console_log::log));
+
+    let _ = fern::Dispatch::new()
+        .level(log_level())
+        .chain(console)
+        .chain(Box::new(sentry::BreadcrumbLogger) as Box<dyn log::Log>)
         .apply();
 }
+
+/// Debug messages are only recorded in debug builds, otherwise breadcrumbs start at info.
+fn log_level() -> log::LevelFilter {
+    match cfg!(any(debug_assertions, feature = "debug")) {
+        true => log::LevelFilter::Debug,
+        false => log::LevelFilter::Info,
+    }
+}
@@ -0,0 +1,76 @@
+//! Small caches in isolate memory.
+//!
+//! An isolate handles many requests before it is evicted, values which are expensive to look
+//! up and rarely change can be kept in memory between requests. Every isolate has its own
+//! copy, values must be fine to be slightly out of date.
+
+use std::{cell::RefCell, collections::BTreeMap};
+
+pub struct Memo<V> {
+    /// Value and the time it was inserted.
+    entries: RefCell<BTreeMap<String, (V, u64)>>,
+    ttl_ms: u64,
+    capacity: usize,
+}
+
+impl<V: Clone> Memo<V> {
+    pub const fn new(ttl_ms: u64, capacity: usize) -> Self {
+        Self {
+            entries: RefCell::new(BTreeMap::new()),
+            ttl_ms,
+            capacity,
+        }
+    }
+
+    /// The value of the key, if it was inserted less than the TTL ago.
+    pub fn get(&self, key: &str, now: u64) -> Option<V> {
+        let entries = self.entries.borrow();
+        let (value, inserted) = entries.get(key)?;
+        (now.saturating_sub(*inserted) < self.ttl_ms).then(|| value.clone())
+    }
+
+    pub fn insert(&self, key: &str, value: V, now: u64) {
+        let mut entries = self.entries.borrow_mut();
+        entries.retain(|_, (_, inserted)| now.saturating_sub(*inserted) < self.ttl_ms);
+
+        if entries.len() >= self.capacity && !entries.contains_key(key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (_, inserted))| *inserted)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(key.to_owned(), (value, now));
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.entries.borrow_mut().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memo() {
+        let memo = Memo::new(100, 2);
+        memo.insert("a", 1, 0);
+        assert_eq!(memo.get("a", 99), Some(1));
+        assert_eq!(memo.get("a", 100), None);
+        assert_eq!(memo.get("b", 0), None);
+
+        // The oldest entry makes room for new ones
+        memo.insert("b", 2, 10);
+        memo.insert("c", 3, 20);
+        assert_eq!(memo.get("a", 20), None);
+        assert_eq!(memo.get("b", 20), Some(2));
+
+        memo.remove("b");
+        assert_eq!(memo.get("b", 20), None);
+        assert_eq!(memo.get("c", 20), Some(3));
+    }
+}
@@ -0,0 +1,266 @@
+use serde::Serialize;
+use std::cell::RefCell;
+use wasm_bindgen::JsValue;
+use worker::Env;
+
+use crate::consts;
+
+thread_local! {
+    static EXPORTERS: RefCell<Vec<Box<dyn Exporter>>> = RefCell::new(Vec::new());
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// Emitted once for every handled request.
+    Request {
+        method: &'a str,
+        path: &'a str,
+        transaction: &'a str,
+        status: u16,
+        duration_ms: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+    },
+    /// Edge cache lookup.
+    Cache { transaction: &'a str, hit: bool },
+    /// A failed attempt of a retried operation.
+    Retry {
+        operation: &'a str,
+        attempt: usize,
+        exhausted: bool,
+    },
+    /// A request to the storage backend.
+    Storage {
+        operation: &'a str,
+        status: u16,
+        duration_ms: u64,
+    },
+    /// A successful paste upload.
+    Upload {
+        class: &'a str,
+        bytes: usize,
+        /// `false` if the paste already existed.
+        stored: bool,
+    },
+    /// An uploaded paste could not be parsed.
+    ParseFailure {
+        /// Stable error code, messages contain details of the paste.
+        code: &'static str,
+    },
+}
+
+impl<'a> Event<'a> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Request { .. } => "request",
+            Self::Cache { .. } => "cache",
+            Self::Retry { .. } => "retry",
+            Self::Storage { .. } => "storage",
+            Self::Upload { .. } => "upload",
+            Self::ParseFailure { .. } => "parse_failure",
+        }
+    }
+
+    /// Flattens the event into string and numeric values, e.g. for Analytics Engine.
+    fn data_point(&self) -> (Vec<&str>, Vec<f64>) {
+        match *self {
+            Self::Request {
+                method,
+                transaction,
+                status,
+                duration_ms,
+                error,
+                ..
+            } => (
+                vec![method, transaction, error.unwrap_or("")],
+                vec![status as f64, duration_ms as f64],
+            ),
+            Self::Cache { transaction, hit } => (vec![transaction], vec![hit as u8 as f64]),
+            Self::Retry {
+                operation,
+                attempt,
+                exhausted,
+            } => (
+                vec![operation],
+                vec![attempt as f64, exhausted as u8 as f64],
+            ),
+            Self::Storage {
+                operation,
+                status,
+                duration_ms,
+            } => (vec![operation], vec![status as f64, duration_ms as f64]),
+            Self::Upload {
+                class,
+                bytes,
+                stored,
+            } => (vec![class], vec![bytes as f64, stored as u8 as f64]),
+            Self::ParseFailure { code } => (vec![code], vec![]),
+        }
+    }
+}
+
+/// Destination for emitted events.
+pub trait Exporter {
+    fn export(&self, event: &Event);
+}
+
+/// Writes every event as a single JSON line to the console.
+pub struct JsonLog;
+
+impl Exporter for JsonLog {
+    fn export(&self, event: &Event) {
+        match serde_json::to_string(event) {
+            Ok(line) => worker::console_log!("{}", line),
+            Err(err) => log::warn!("failed to serialize event: {:?}", err),
+        }
+    }
+}
+
+/// Writes events as data points into a Workers Analytics Engine dataset.
+pub struct AnalyticsEngine {
+    dataset: JsValue,
+}
+
+impl AnalyticsEngine {
+    pub fn from_env(env: &Env) -> Option<Self> {
+        js_sys::Reflect::get(env, &consts::ANALYTICS_METRICS.into())
+            .ok()
+            .filter(|dataset| dataset.is_object())
+            .map(|dataset| Self { dataset })
+    }
+
+    fn write_data_point(&self, event: &Event) -> Result<(), JsValue> {
+        let (blobs, doubles) = event.data_point();
+
+        let data_point = js_sys::Object::new();
+        let indexes = js_sys::Array::of1(&event.name().into());
+        let blobs = blobs
+            .into_iter()
+            .map(JsValue::from)
+            .collect::<js_sys::Array>();
+        let doubles = doubles
+            .into_iter()
+            .map(JsValue::from)
+            .collect::<js_sys::Array>();
+        js_sys::Reflect::set(&data_point, &"indexes".into(), &indexes)?;
+        js_sys::Reflect::set(&data_point, &"blobs".into(), &blobs)?;
+        js_sys::Reflect::set(&data_point, &"doubles".into(), &doubles)?;
+
+        let write: js_sys::Function =
+            js_sys::Reflect::get(&self.dataset, &"writeDataPoint".into())?.into();
+        write.call1(&self.dataset, &data_point)?;
+
+        Ok(())
+    }
+}
+
+impl Exporter for AnalyticsEngine {
+    fn export(&self, event: &Event) {
+        if let Err(err) = self.write_data_point(event) {
+            log::warn!("failed to write data point: {:?}", err);
+        }
+    }
+}
+
+/// Keeps all emitted events in memory, useful in tests.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct Memory {
+    events: std::rc::Rc<RefCell<Vec<serde_json::Value>>>,
+}
+
+#[cfg(test)]
+impl Memory {
+    pub fn events(&self) -> Vec<serde_json::Value> {
+        self.events.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+impl Exporter for Memory {
+    fn export(&self, event: &Event) {
+        if let Ok(event) = serde_json::to_value(event) {
+            self.events.borrow_mut().push(event);
+        }
+    }
+}
+
+/// Configures the exporters for the current request.
+pub fn init(env: &Env) {
+    let mut exporters: Vec<Box<dyn Exporter>> = vec![Box::new(JsonLog)];
+    if let Some(analytics) = AnalyticsEngine::from_env(env) {
+        exporters.push(Box::new(analytics));
+    }
+    set_exporters(exporters);
+}
+
+pub fn set_exporters(exporters: Vec<Box<dyn Exporter>>) {
+    EXPORTERS.with(|e| *e.borrow_mut() = exporters);
+}
+
+pub fn emit(event: Event) {
+    EXPORTERS.with(|exporters| {
+        for exporter in exporters.borrow().iter() {
+            exporter.export(&event);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_emit() {
+        let memory = Memory::default();
+        set_exporters(vec![Box::new(memory.clone())]);
+
+        emit(Event::Cache {
+            transaction: "/<id>",
+            hit: true,
+        });
+        emit(Event::Retry {
+            operation: "upload",
+            attempt: 1,
+            exhausted: false,
+        });
+
+        assert_eq!(
+            memory.events(),
+            vec![
+                json!({"event": "cache", "transaction": "/<id>", "hit": true}),
+                json!({"event": "retry", "operation": "upload", "attempt": 1, "exhausted": false}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_request_without_error() {
+        let event = Event::Request {
+            method: "GET",
+            path: "/foo",
+            transaction: "/<id>",
+            status: 200,
+            duration_ms: 12,
+            error: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "event": "request",
+                "method": "GET",
+                "path": "/foo",
+                "transaction": "/<id>",
+                "status": 200,
+                "duration_ms": 12,
+            })
+        );
+        assert_eq!(
+            event.data_point(),
+            (vec!["GET", "/<id>", ""], vec![200.0, 12.0])
+        );
+    }
+}
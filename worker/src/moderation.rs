@@ -0,0 +1,265 @@
+use crate::{recent, search, store::Store, Error, Result};
+use app::RecentPaste;
+use serde::{Deserialize, Serialize};
+
+/// A user report of an abusive paste.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub id: String,
+    pub reason: String,
+    /// Time of the report in milliseconds since the epoch.
+    pub created: u64,
+}
+
+/// Why a paste is no longer available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Hidden,
+    Deleted,
+}
+
+/// The public listings of a hidden paste, restored when the paste is unhidden.
+#[derive(Debug, Serialize, Deserialize)]
+struct Listing {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    search: Option<search::Entry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recent: Option<RecentPaste>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Reports {
+    pub reports: Vec<Report>,
+    pub cursor: Option<String>,
+}
+
+fn report_prefix(id: &str) -> String {
+    format!("report:{}:", id)
+}
+
+fn blocked_key(id: &str) -> String {
+    format!("blocked:{}", id)
+}
+
+fn listing_key(id: &str) -> String {
+    format!("listing:{}", id)
+}
+
+pub async fn report(store: &dyn Store, report: &Report) -> Result<()> {
+    let key = format!("{}{:020}", report_prefix(&report.id), report.created);
+    let metadata = serde_json::to_value(report)?;
+    store
+        .put(&key, serde_json::to_string(report)?, Some(metadata))
+        .await
+}
+
+/// Lists open reports, grouped by paste.
+pub async fn reports(store: &dyn Store, cursor: Option<String>, limit: u64) -> Result<Reports> {
+    let page = store.list("report:", cursor, limit).await?;
+
+    Ok(Reports {
+        reports: page
+            .keys
+            .into_iter()
+            .filter_map(|key| serde_json::from_value(key.metadata?).ok())
+            .collect(),
+        cursor: page.cursor,
+    })
+}
+
+/// Removes all reports of a paste.
+pub async fn dismiss(store: &dyn Store, id: &str) -> Result<()> {
+    let prefix = report_prefix(id);
+
+    let mut cursor = None;
+    loop {
+        let page = store.list(&prefix, cursor, 100).await?;
+        for key in page.keys {
+            store.delete(&key.name).await?;
+        }
+
+        cursor = page.cursor;
+        if cursor.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+pub async fn block(store: &dyn Store, id: &str, action: Action) -> Result<()> {
+    store
+        .put(&blocked_key(id), serde_json::to_string(&action)?, None)
+        .await?;
+    dismiss(store, id).await
+}
+
+pub async fn unblock(store: &dyn Store, id: &str) -> Result<()> {
+    store.delete(&blocked_key(id)).await
+}
+
+pub async fn blocked(store: &dyn Store, id: &str) -> Result<Option<Action>> {
+    match store.get(&blocked_key(id)).await? {
+        Some(action) => Ok(Some(serde_json::from_str(&action)?)),
+        None => Ok(None),
+    }
+}
+
+/// Removes the paste from all public listings of the index.
+///
+/// Listings of hidden pastes are kept in the moderation store, [`relist`] restores them.
+pub async fn unlist(store: &dyn Store, index: &dyn Store, id: &str, action: Action) -> Result<()> {
+    match action {
+        Action::Hidden => {
+            let listing = Listing {
+                search: search::get(index, id).await?,
+                recent: recent::get(index, id).await?,
+            };
+            if listing.search.is_some() || listing.recent.is_some() {
+                store
+                    .put(&listing_key(id), serde_json::to_string(&listing)?, None)
+                    .await?;
+            }
+        }
+        // Deleted pastes are never listed again
+        Action::Deleted => store.delete(&listing_key(id)).await?,
+    }
+
+    search::remove(index, id).await?;
+    recent::remove(index, id).await
+}
+
+/// Adds a paste removed by [`unlist`] back to the listings it was in, with its original
+/// upload time. Does nothing if the paste was not listed when it was hidden.
+pub async fn relist(store: &dyn Store, index: &dyn Store, id: &str) -> Result<()> {
+    let listing: Listing = match store.get(&listing_key(id)).await? {
+        Some(listing) => serde_json::from_str(&listing)?,
+        None => return Ok(()),
+    };
+
+    if let Some(entry) = &listing.search {
+        search::add(index, entry).await?;
+    }
+    // Pruned again if newer pastes filled the list in the meantime
+    if let Some(paste) = listing.recent {
+        recent::add(index, paste).await?;
+    }
+
+    store.delete(&listing_key(id)).await
+}
+
+/// Fails with [`Error::Gone`] if the paste was hidden or deleted by a moderator.
+pub async fn ensure_available(store: &dyn Store, id: &str) -> Result<()> {
+    match blocked(store, id).await? {
+        Some(_) => Err(Error::Gone("paste", id.to_owned())),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{future::block_on, store::Memory};
+
+    fn report(id: &str, created: u64) -> Report {
+        Report {
+            id: id.to_owned(),
+            reason: "spam".to_owned(),
+            created,
+        }
+    }
+
+    #[test]
+    fn test_reports() {
+        let store = Memory::default();
+        for r in [report("b", 2), report("a", 3), report("b", 1)] {
+            block_on(super::report(&store, &r)).unwrap();
+        }
+
+        let page = block_on(reports(&store, None, 2)).unwrap();
+        assert_eq!(page.reports, vec![report("a", 3), report("b", 1)]);
+        let page = block_on(reports(&store, page.cursor, 2)).unwrap();
+        assert_eq!(page.reports, vec![report("b", 2)]);
+        assert_eq!(page.cursor, None);
+
+        block_on(dismiss(&store, "b")).unwrap();
+        let page = block_on(reports(&store, None, 10)).unwrap();
+        assert_eq!(page.reports, vec![report("a", 3)]);
+    }
+
+    #[test]
+    fn test_block() {
+        let store = Memory::default();
+        block_on(super::report(&store, &report("a", 1))).unwrap();
+
+        block_on(block(&store, "a", Action::Hidden)).unwrap();
+        assert_eq!(
+            block_on(blocked(&store, "a")).unwrap(),
+            Some(Action::Hidden)
+        );
+        assert!(matches!(
+            block_on(ensure_available(&store, "a")),
+            Err(Error::Gone("paste", _))
+        ));
+        assert!(block_on(ensure_available(&store, "b")).is_ok());
+        // Blocking resolves all reports
+        assert!(block_on(reports(&store, None, 10))
+            .unwrap()
+            .reports
+            .is_empty());
+
+        block_on(unblock(&store, "a")).unwrap();
+        assert!(block_on(ensure_available(&store, "a")).is_ok());
+    }
+
+    fn listed(index: &Memory, id: &str, created: u64) {
+        let entry = search::Entry {
+            id: id.to_owned(),
+            title: "Cyclone Slayer".to_owned(),
+            ascendancy_or_class: "Slayer".to_owned(),
+            main_skill: Some("Cyclone".to_owned()),
+            keystones: Vec::new(),
+            level: 90,
+            created,
+        };
+        let paste = RecentPaste {
+            id: id.to_owned(),
+            title: entry.title.clone(),
+            ascendancy_or_class: entry.ascendancy_or_class.clone(),
+            main_skill: entry.main_skill.clone(),
+            summary: Vec::new(),
+            created,
+        };
+        block_on(search::add(index, &entry)).unwrap();
+        block_on(recent::add(index, paste)).unwrap();
+    }
+
+    #[test]
+    fn test_relist() {
+        let store = Memory::default();
+        let index = Memory::default();
+        listed(&index, "a", 1);
+        listed(&index, "b", 2);
+        let keys = index.keys();
+
+        block_on(unlist(&store, &index, "a", Action::Hidden)).unwrap();
+        assert_eq!(block_on(search::get(&index, "a")).unwrap(), None);
+        assert_eq!(block_on(recent::get(&index, "a")).unwrap(), None);
+
+        // Relisted with the original upload time, behind newer pastes
+        block_on(relist(&store, &index, "a")).unwrap();
+        assert_eq!(index.keys(), keys);
+        let recent = block_on(recent::list(&index)).unwrap();
+        assert_eq!(recent[1].id, "a");
+        assert!(store.keys().is_empty());
+
+        // Deleted pastes and pastes which were never listed stay unlisted
+        block_on(unlist(&store, &index, "a", Action::Hidden)).unwrap();
+        block_on(unlist(&store, &index, "a", Action::Deleted)).unwrap();
+        block_on(unlist(&store, &index, "c", Action::Hidden)).unwrap();
+        block_on(relist(&store, &index, "a")).unwrap();
+        block_on(relist(&store, &index, "c")).unwrap();
+        assert_eq!(block_on(search::get(&index, "a")).unwrap(), None);
+        assert_eq!(block_on(recent::get(&index, "a")).unwrap(), None);
+        assert!(store.keys().is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    moderation,
+    platform::{Platform, WorkerPlatform},
+    storage::{Pastes, StoredPaste},
+    store::Store,
+    utils::{self, ResponseExt},
+    Error, Result,
+};
+use pob::SerdePathOfBuilding;
+use serde::Serialize;
+use std::borrow::Cow;
+use worker::{Env, Method, Request, Response, Url};
+
+const PROVIDER_NAME: &str = "Paste of Exile - POB B.in";
+const CACHE_AGE: u32 = 12 * 3_600;
+const MAX_WIDTH: u32 = 600;
+const MAX_HEIGHT: u32 = 200;
+
+/// oEmbed response, see <https://oembed.com/#section2.3>.
+#[derive(Serialize)]
+struct Oembed {
+    version: &'static str,
+    r#type: &'static str,
+    provider_name: &'static str,
+    provider_url: String,
+    cache_age: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    /// The embedded paste is private and must not be cached publicly.
+    #[serde(skip)]
+    private: bool,
+}
+
+impl Oembed {
+    fn link(provider_url: String) -> Self {
+        Self {
+            version: "1.0",
+            r#type: "link",
+            provider_name: PROVIDER_NAME,
+            provider_url,
+            cache_age: CACHE_AGE,
+            title: None,
+            author_name: None,
+            author_url: None,
+            thumbnail_url: None,
+            thumbnail_width: None,
+            thumbnail_height: None,
+            html: None,
+            width: None,
+            height: None,
+            private: false,
+        }
+    }
+}
+
+pub async fn try_handle(req: &Request, env: &Env) -> Result<Option<Response>> {
+    if req.path() != "/oembed.json" || req.method() != Method::Get {
+        return Ok(None);
+    }
+
+    let oembed = handle(req, env).await?;
+    let response = Response::from_json(&oembed)?;
+    let response = match oembed.private {
+        true => response.cache_private()?,
+        false => response.cache_for(CACHE_AGE)?,
+    };
+    Ok(Some(response))
+}
+
+/// Parameters of an oEmbed request.
+struct Query {
+    provider_url: String,
+    /// Page to embed, `None` if only provider information is requested.
+    page: Option<Url>,
+    width: u32,
+    height: u32,
+}
+
+impl Query {
+    fn parse(url: &Url) -> Result<Self> {
+        let host = url.host_str().unwrap();
+        let provider_url = format!("https://{}", host);
+
+        // The spec requires a 501 for formats the provider does not support
+        if let Some(format) = utils::query_param(url, "format") {
+            if format != "json" {
+                return Err(Error::NotImplemented(format!(
+                    "unsupported format '{}'",
+                    format
+                )));
+            }
+        }
+
+        let page = match utils::query_param(url, "url") {
+            Some(page) => {
+                let page =
+                    Url::parse(&page).map_err(|_| Error::BadRequest("invalid url".to_owned()))?;
+                if page.host_str() != Some(host) {
+                    return Err(Error::NotFound("url", page.to_string()));
+                }
+                Some(page)
+            }
+            None => None,
+        };
+
+        let max_dimension = |name: &str, max: u32| {
+            utils::query_param(url, name)
+                .and_then(|value| value.parse::<u32>().ok())
+                .map(|value| value.min(max))
+                .unwrap_or(max)
+        };
+
+        Ok(Self {
+            provider_url,
+            page,
+            width: max_dimension("maxwidth", MAX_WIDTH),
+            height: max_dimension("maxheight", MAX_HEIGHT),
+        })
+    }
+}
+
+async fn handle(req: &Request, env: &Env) -> Result<Oembed> {
+    let query = Query::parse(&req.url()?)?;
+
+    let page = match query.page {
+        Some(ref page) => page,
+        // No url, just provider information
+        None => return Ok(Oembed::link(query.provider_url)),
+    };
+
+    let id = match app::Route::resolve(page.path()) {
+        app::Route::Paste(id) => id,
+        _ => return Ok(Oembed::link(query.provider_url)),
+    };
+
+    let platform = WorkerPlatform::new(env)?;
+    let paste = load(platform.pastes(), platform.moderation(), &id).await?;
+    let key = utils::query_param(page, "key");
+    paste.verify_access(&platform, &id, key.as_deref()).await?;
+
+    let content = String::from_utf8(paste.data).map_err(|e| e.to_string())?;
+    let pob = SerdePathOfBuilding::from_export(&content).map_err(|e| e.to_string())?;
+    let mut info = app::PasteInfo::new(&pob);
+    if let Some(title) = paste.metadata.title {
+        info.title = title;
+    }
+
+    Ok(rich(&query, page, info, paste.metadata.private))
+}
+
+async fn load(pastes: &dyn Pastes, moderation: &dyn Store, id: &str) -> Result<StoredPaste> {
+    moderation::ensure_available(moderation, id).await?;
+    pastes
+        .get(id)
+        .await?
+        .ok_or_else(|| Error::NotFound("paste", id.to_owned()))
+}
+
+fn rich(query: &Query, page: &Url, info: app::PasteInfo, private: bool) -> Oembed {
+    let mut oembed = Oembed::link(query.provider_url.clone());
+    oembed.r#type = "rich";
+    oembed.private = private;
+    oembed.html = Some(render_html(page.as_str(), &info, query.width, query.height));
+    oembed.width = Some(query.width);
+    oembed.height = Some(query.height);
+    oembed.title = Some(info.title);
+    oembed.author_name = Some(info.ascendancy_or_class);
+    oembed.author_url = Some(page.to_string());
+    if let Some((image, width, height)) = info.image {
+        oembed.thumbnail_url = Some(format!("{}{}", query.provider_url, image));
+        oembed.thumbnail_width = Some(width);
+        oembed.thumbnail_height = Some(height);
+    }
+
+    oembed
+}
+
+fn render_html(url: &str, info: &app::PasteInfo, width: u32, height: u32) -> String {
+    let summary = info
+        .summary
+        .iter()
+        .map(|line| utils::html_escape(line))
+        .collect::<Vec<Cow<str>>>()
+        .join("<br>");
+
+    format!(
+        r#"<blockquote style="max-width: {}px; max-height: {}px; border-left: 4px solid {}; padding-left: 8px"><a href="{}">{}</a><p>{}</p></blockquote>"#,
+        width,
+        height,
+        info.color,
+        utils::html_escape(url),
+        utils::html_escape(&info.title),
+        summary
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{future::block_on, storage, store, ErrorResponse};
+    use serde_json::json;
+
+    fn query(url: &str) -> Result<Query> {
+        Query::parse(&Url::parse(url).unwrap())
+    }
+
+    fn info() -> app::PasteInfo {
+        app::PasteInfo {
+            title: "Level 90 Slayer".to_owned(),
+            ascendancy_or_class: "Slayer".to_owned(),
+            main_skill: None,
+            summary: vec!["Life: 5000".to_owned(), "<b>".to_owned()],
+            stats: Vec::new(),
+            image: Some(("/assets/asc/Slayer.png", 93, 93)),
+            color: "#f4d7a4",
+        }
+    }
+
+    #[test]
+    fn test_query() {
+        let q = query("https://pobb.in/oembed.json").unwrap();
+        assert_eq!(q.provider_url, "https://pobb.in");
+        assert!(q.page.is_none());
+        assert_eq!((q.width, q.height), (MAX_WIDTH, MAX_HEIGHT));
+
+        let q = query("https://pobb.in/oembed.json?url=https%3A%2F%2Fpobb.in%2Fabc&maxwidth=300&maxheight=9999").unwrap();
+        assert_eq!(q.page.unwrap().path(), "/abc");
+        assert_eq!((q.width, q.height), (300, MAX_HEIGHT));
+
+        let q = query("https://pobb.in/oembed.json?maxwidth=wide").unwrap();
+        assert_eq!(q.width, MAX_WIDTH);
+
+        let err = query("https://pobb.in/oembed.json?format=xml")
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::NotImplemented(_)));
+        assert_eq!(ErrorResponse::from(err).code, 501);
+        let err = query("https://pobb.in/oembed.json?url=https%3A%2F%2Fexample.com%2Fabc")
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::NotFound("url", _)));
+    }
+
+    #[test]
+    fn test_rich() {
+        let q = query("https://pobb.in/oembed.json?url=https%3A%2F%2Fpobb.in%2Fabc&maxwidth=300")
+            .unwrap();
+        let page = q.page.clone().unwrap();
+        let oembed = rich(&q, &page, info(), false);
+
+        let json = serde_json::to_value(&oembed).unwrap();
+        let html = json["html"].as_str().unwrap().to_owned();
+        assert_eq!(
+            json,
+            json!({
+                "version": "1.0",
+                "type": "rich",
+                "provider_name": PROVIDER_NAME,
+                "provider_url": "https://pobb.in",
+                "cache_age": CACHE_AGE,
+                "title": "Level 90 Slayer",
+                "author_name": "Slayer",
+                "author_url": "https://pobb.in/abc",
+                "thumbnail_url": "https://pobb.in/assets/asc/Slayer.png",
+                "thumbnail_width": 93,
+                "thumbnail_height": 93,
+                "html": html,
+                "width": 300,
+                "height": MAX_HEIGHT,
+            })
+        );
+        assert!(html.contains("max-width: 300px"));
+        assert!(html.contains("Life: 5000<br>&lt;b&gt;"));
+    }
+
+    #[test]
+    fn test_load_not_found() {
+        let pastes = storage::Memory::default();
+        let moderation = store::Memory::default();
+
+        let err = block_on(load(&pastes, &moderation, "abc")).err().unwrap();
+        assert!(matches!(err, Error::NotFound("paste", id) if id == "abc"));
+    }
+}
@@ -0,0 +1,181 @@
+use super::Platform;
+use crate::{
+    assets::{Assets, KvAssets},
+    consts, crypto,
+    fetch::{Fetcher, WorkerFetcher},
+    future::LocalBoxFuture,
+    http,
+    jobs::Credentials,
+    request_id,
+    retry::{Timer, WorkerTimer},
+    security,
+    sitemap::Robots,
+    storage::{EnvPastes, Pastes},
+    store::{Store, WorkerKv},
+    webhook::{self, Webhook},
+    Result,
+};
+use std::rc::Rc;
+use worker::{wasm_bindgen_futures, Context, Env, Method};
+
+/// The Cloudflare Workers runtime.
+pub struct WorkerPlatform<'e> {
+    pastes: EnvPastes<'e>,
+    #[cfg(not(feature = "storage-kv"))]
+    env: &'e Env,
+    index: Rc<WorkerKv>,
+    moderation: WorkerKv,
+    assets: KvAssets,
+    security: security::Policy,
+    webhooks: Vec<Webhook>,
+    robots: Robots,
+    ctx: Option<&'e Context>,
+}
+
+impl<'e> WorkerPlatform<'e> {
+    pub fn new(env: &'e Env) -> Result<Self> {
+        Ok(Self {
+            pastes: EnvPastes(env),
+            #[cfg(not(feature = "storage-kv"))]
+            env,
+            index: Rc::new(WorkerKv::new(env, consts::KV_PASTE_INDEX)?),
+            moderation: WorkerKv::new(env, consts::KV_MODERATION)?,
+            assets: KvAssets::from_env(env)?,
+            security: security::Policy::from_env(env),
+            webhooks: webhook::from_env(env),
+            robots: Robots::from_env(env),
+            ctx: None,
+        })
+    }
+
+    /// Background tasks are kept alive by the context until they complete.
+    pub fn with_context(mut self, ctx: &'e Context) -> Self {
+        self.ctx = Some(ctx);
+        self
+    }
+}
+
+impl Platform for WorkerPlatform<'_> {
+    fn now(&self) -> u64 {
+        worker::Date::now().as_millis()
+    }
+
+    fn random(&self, buf: &mut [u8]) -> Result<()> {
+        crypto::fill_random(buf)?;
+        Ok(())
+    }
+
+    fn sha1<'a>(&'a self, data: &'a [u8]) -> LocalBoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move { Ok(crypto::sha1(&mut data.to_vec()).await?) })
+    }
+
+    fn pastes(&self) -> &dyn Pastes {
+        &self.pastes
+    }
+
+    #[cfg(not(feature = "storage-kv"))]
+    fn credentials(&self) -> Result<Option<Box<dyn Credentials + '_>>> {
+        let credentials = crate::storage::Credentials::from_env(self.env)?;
+        Ok(Some(Box::new(credentials)))
+    }
+
+    #[cfg(feature = "storage-kv")]
+    fn credentials(&self) -> Result<Option<Box<dyn Credentials + '_>>> {
+        Ok(None)
+    }
+
+    fn index(&self) -> &dyn Store {
+        &*self.index
+    }
+
+    fn shared_index(&self) -> Rc<dyn Store> {
+        self.index.clone()
+    }
+
+    fn moderation(&self) -> &dyn Store {
+        &self.moderation
+    }
+
+    fn assets(&self) -> &dyn Assets {
+        &self.assets
+    }
+
+    fn fetcher(&self) -> &'static dyn Fetcher {
+        &WorkerFetcher
+    }
+
+    fn timer(&self) -> &'static dyn Timer {
+        &WorkerTimer
+    }
+
+    fn security(&self) -> &security::Policy {
+        &self.security
+    }
+
+    fn webhooks(&self) -> &[Webhook] {
+        &self.webhooks
+    }
+
+    fn robots(&self) -> &Robots {
+        &self.robots
+    }
+
+    fn wait_until(&self, task: LocalBoxFuture<'static, ()>) {
+        let task = request_id::inherit(task);
+        match self.ctx {
+            Some(ctx) => ctx.wait_until(task),
+            // Without a context the task may be cancelled once the response is sent
+            None => wasm_bindgen_futures::spawn_local(task),
+        }
+    }
+}
+
+pub fn method(method: Method) -> http::Method {
+    match method {
+        Method::Get => http::Method::Get,
+        Method::Head => http::Method::Head,
+        Method::Post => http::Method::Post,
+        Method::Put => http::Method::Put,
+        Method::Delete => http::Method::Delete,
+        Method::Options => http::Method::Options,
+        _ => http::Method::Other,
+    }
+}
+
+/// Buffers a worker request, the body is only read for methods which may have one.
+pub async fn request(req: &mut worker::Request) -> Result<http::Request> {
+    let method = method(req.method());
+    let body = match method {
+        http::Method::Get | http::Method::Head => Vec::new(),
+        _ => req.bytes().await?,
+    };
+
+    Ok(http::Request::new(
+        method,
+        req.url()?,
+        req.headers().into_iter().collect(),
+        body,
+    ))
+}
+
+/// Marks a response with an already encoded body, e.g. a precompressed asset.
+///
+/// The runtime compresses every body with a `Content-Encoding` unless the response is created
+/// with `encodeBody: "manual"`, which workers-rs does not expose. The module entry point
+/// written by `build.sh` recreates marked responses with it and removes the header.
+pub const ENCODED_BODY_HEADER: &str = "X-Encoded-Body";
+
+pub fn response(response: http::Response) -> Result<worker::Response> {
+    let is_encoded = response.is_encoded();
+    let (status, headers, body) = response.into_parts();
+
+    let mut response = worker::Response::from_bytes(body)?.with_status(status);
+    for (name, value) in headers {
+        response.headers_mut().set(&name, &value)?;
+    }
+    if is_encoded {
+        response.headers_mut().set(ENCODED_BODY_HEADER, "1")?;
+    }
+
+    Ok(response)
+}
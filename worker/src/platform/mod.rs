@@ -0,0 +1,43 @@
+//! Everything the [`service`](crate::service) needs from the environment it runs in.
+
+use crate::{
+    assets::Assets, fetch::Fetcher, future::LocalBoxFuture, jobs::Credentials, retry::Timer,
+    security, sitemap::Robots, storage::Pastes, store::Store, webhook::Webhook, Result,
+};
+use std::rc::Rc;
+
+mod cloudflare;
+#[cfg(feature = "server")]
+pub mod native;
+
+pub use self::cloudflare::{method, request, response, WorkerPlatform, ENCODED_BODY_HEADER};
+
+pub trait Platform {
+    /// Current time in milliseconds since the epoch.
+    fn now(&self) -> u64;
+    /// Fills the buffer with cryptographically secure random bytes.
+    fn random(&self, buf: &mut [u8]) -> Result<()>;
+    fn sha1<'a>(&'a self, data: &'a [u8]) -> LocalBoxFuture<'a, Result<Vec<u8>>>;
+
+    fn pastes(&self) -> &dyn Pastes;
+    /// Cached credentials of the storage backend, `None` if it does not use credentials.
+    ///
+    /// Created on demand, a misconfigured backend only fails the requests which need it.
+    fn credentials(&self) -> Result<Option<Box<dyn Credentials + '_>>>;
+    /// Search index and list of recent pastes.
+    fn index(&self) -> &dyn Store;
+    /// The [`index`](Self::index), shared with background tasks.
+    fn shared_index(&self) -> Rc<dyn Store>;
+    fn moderation(&self) -> &dyn Store;
+    fn assets(&self) -> &dyn Assets;
+    /// Fetcher for outgoing requests, which can also be used by background tasks.
+    fn fetcher(&self) -> &'static dyn Fetcher;
+    /// Timer for retries, which can also be used by background tasks.
+    fn timer(&self) -> &'static dyn Timer;
+    fn security(&self) -> &security::Policy;
+    fn webhooks(&self) -> &[Webhook];
+    fn robots(&self) -> &Robots;
+
+    /// Runs a background task without delaying the response, e.g. to notify webhooks.
+    fn wait_until(&self, task: LocalBoxFuture<'static, ()>);
+}
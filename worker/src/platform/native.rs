@@ -0,0 +1,430 @@
+//! A native HTTP server with filesystem storage, for development and self-hosting.
+
+use super::Platform;
+use crate::{
+    assets::{Asset, Assets},
+    encoding::Encoding,
+    fetch::{FetchResponse, Fetcher},
+    future::{self, LocalBoxFuture},
+    http::{self, Method},
+    jobs::Credentials,
+    request_id,
+    retry::Timer,
+    security, service,
+    sitemap::Robots,
+    storage::{FsPastes, Pastes},
+    store::{FileStore, Store},
+    utils::{self, ResponseExt},
+    webhook::Webhook,
+    ErrorResponse, Result,
+};
+use std::{
+    cell::RefCell,
+    io::Read,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub struct Config {
+    /// Address to listen on, e.g. `127.0.0.1:8787`.
+    pub listen: String,
+    /// Directory for pastes and indexes, created if it does not exist.
+    pub data: PathBuf,
+    /// Directory of the built app, usually `app/dist`.
+    pub assets: PathBuf,
+    pub security: security::Policy,
+    pub webhooks: Vec<Webhook>,
+    pub robots: Robots,
+}
+
+pub struct NativePlatform {
+    pastes: FsPastes,
+    index: Rc<FileStore>,
+    moderation: FileStore,
+    assets: FsAssets,
+    security: security::Policy,
+    webhooks: Vec<Webhook>,
+    robots: Robots,
+    tasks: RefCell<Vec<LocalBoxFuture<'static, ()>>>,
+}
+
+impl NativePlatform {
+    pub fn new(config: &Config) -> Result<Self> {
+        std::fs::create_dir_all(&config.data).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            pastes: FsPastes::new(config.data.join("pastes")),
+            index: Rc::new(FileStore::open(config.data.join("index.json"))?),
+            moderation: FileStore::open(config.data.join("moderation.json"))?,
+            assets: FsAssets(config.assets.clone()),
+            security: config.security.clone(),
+            webhooks: config.webhooks.clone(),
+            robots: config.robots.clone(),
+            tasks: RefCell::default(),
+        })
+    }
+
+    /// Runs all background tasks, which were started since the last call.
+    fn run_tasks(&self) {
+        let tasks = self.tasks.take();
+        for task in tasks {
+            future::block_on(task);
+        }
+    }
+}
+
+impl Platform for NativePlatform {
+    fn now(&self) -> u64 {
+        now()
+    }
+
+    fn random(&self, buf: &mut [u8]) -> Result<()> {
+        getrandom::getrandom(buf).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn sha1<'a>(&'a self, data: &'a [u8]) -> LocalBoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move { Ok(sha1_smol::Sha1::from(data).digest().bytes().to_vec()) })
+    }
+
+    fn pastes(&self) -> &dyn Pastes {
+        &self.pastes
+    }
+
+    fn credentials(&self) -> Result<Option<Box<dyn Credentials + '_>>> {
+        Ok(None)
+    }
+
+    fn index(&self) -> &dyn Store {
+        &*self.index
+    }
+
+    fn shared_index(&self) -> Rc<dyn Store> {
+        self.index.clone()
+    }
+
+    fn moderation(&self) -> &dyn Store {
+        &self.moderation
+    }
+
+    fn assets(&self) -> &dyn Assets {
+        &self.assets
+    }
+
+    fn fetcher(&self) -> &'static dyn Fetcher {
+        &UreqFetcher
+    }
+
+    fn timer(&self) -> &'static dyn Timer {
+        &SleepTimer
+    }
+
+    fn security(&self) -> &security::Policy {
+        &self.security
+    }
+
+    fn webhooks(&self) -> &[Webhook] {
+        &self.webhooks
+    }
+
+    fn robots(&self) -> &Robots {
+        &self.robots
+    }
+
+    fn wait_until(&self, task: LocalBoxFuture<'static, ()>) {
+        self.tasks.borrow_mut().push(request_id::inherit(task));
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Assets served from a directory.
+struct FsAssets(PathBuf);
+
+impl Assets for FsAssets {
+    fn get<'a>(&'a self, path: &'a str) -> LocalBoxFuture<'a, Result<Option<Asset>>> {
+        Box::pin(async move {
+            // Never serve anything outside of the asset directory
+            let relative = Path::new(path);
+            if !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+            {
+                return Ok(None);
+            }
+
+            let data = match std::fs::read(self.0.join(relative)) {
+                Ok(data) => data,
+                Err(_) => return Ok(None),
+            };
+            let etag = utils::hex(&sha1_smol::Sha1::from(&data).digest().bytes());
+
+            Ok(Some(Asset {
+                data,
+                path: path.to_owned(),
+                etag,
+            }))
+        })
+    }
+
+    fn get_encoded<'a>(
+        &'a self,
+        path: &'a str,
+        encoding: Encoding,
+    ) -> LocalBoxFuture<'a, Result<Option<Asset>>> {
+        Box::pin(async move {
+            let extension = match encoding.extension() {
+                Some(extension) => extension,
+                None => return Ok(None),
+            };
+
+            let asset = self.get(&format!("{}.{}", path, extension)).await?;
+            Ok(asset.map(|asset| Asset {
+                path: path.to_owned(),
+                ..asset
+            }))
+        })
+    }
+}
+
+struct UreqFetcher;
+
+impl Fetcher for UreqFetcher {
+    fn get<'a>(&'a self, url: &'a str) -> LocalBoxFuture<'a, Result<FetchResponse>> {
+        Box::pin(async move { into_response(ureq::get(url).call()) })
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'a str, String)],
+        body: Vec<u8>,
+    ) -> LocalBoxFuture<'a, Result<FetchResponse>> {
+        Box::pin(async move {
+            let request = headers
+                .iter()
+                .fold(ureq::post(url), |request, (name, value)| {
+                    request.set(name, value)
+                });
+            into_response(request.send_bytes(&body))
+        })
+    }
+}
+
+fn into_response(
+    response: std::result::Result<ureq::Response, ureq::Error>,
+) -> Result<FetchResponse> {
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(err) => return Err(err.to_string().into()),
+    };
+
+    let status = response.status();
+    let headers = response
+        .headers_names()
+        .into_iter()
+        .filter_map(|name| {
+            let value = response.header(&name)?.to_owned();
+            Some((name, value))
+        })
+        .collect();
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+
+    Ok(FetchResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Blocks the thread, background tasks only run after the response is sent.
+struct SleepTimer;
+
+impl Timer for SleepTimer {
+    fn now(&self) -> u64 {
+        now()
+    }
+
+    fn sleep(&self, ms: u64) -> LocalBoxFuture<'_, ()> {
+        Box::pin(async move { std::thread::sleep(Duration::from_millis(ms)) })
+    }
+
+    fn random(&self) -> f64 {
+        let mut buf = [0; 4];
+        let _ = getrandom::getrandom(&mut buf);
+        u32::from_le_bytes(buf) as f64 / u32::MAX as f64
+    }
+}
+
+/// Serves the app until the process is terminated, requests are handled one at a time.
+pub fn serve(config: Config) -> Result<()> {
+    let server = tiny_http::Server::http(&config.listen).map_err(|e| e.to_string())?;
+    log::info!("listening on http://{}", config.listen);
+    run(server, &config)
+}
+
+fn run(server: tiny_http::Server, config: &Config) -> Result<()> {
+    let platform = NativePlatform::new(config)?;
+
+    for mut request in server.incoming_requests() {
+        let request_id = match request_id::new(None, |buf| platform.random(buf)) {
+            Ok(request_id) => request_id,
+            Err(err) => {
+                log::warn!("failed to create request id: {}", err);
+                continue;
+            }
+        };
+
+        let response = match to_request(&mut request, &config.listen) {
+            Ok(req) => {
+                let req = req.with_request_id(request_id.clone());
+                // All futures of the native platform complete without yielding
+                future::block_on(request_id::run(request_id.clone(), async {
+                    log::info!("{:?} {}", req.method(), req.url().path());
+                    service::handle(&platform, &req).await
+                }))
+            }
+            Err(err) => Err(err),
+        };
+
+        let response = response.unwrap_or_else(|err| {
+            log::warn!("request failed: {}", err);
+            let mut err: ErrorResponse = err.into();
+            err.request_id = Some(request_id.clone());
+            http::Response::from_json(&err)
+                .map(|response| response.with_status(err.code))
+                .unwrap_or_else(|_| http::Response::empty().with_status(500))
+        });
+        let response = security::apply(response, &config.security)
+            .and_then(|response| response.with_header(request_id::HEADER, &request_id))
+            .unwrap_or_else(|_| http::Response::empty().with_status(500));
+
+        if let Err(err) = request.respond(to_response(response)) {
+            log::warn!("failed to respond: {}", err);
+        }
+        platform.run_tasks();
+    }
+
+    Ok(())
+}
+
+fn to_request(request: &mut tiny_http::Request, listen: &str) -> Result<http::Request> {
+    let headers = request
+        .headers()
+        .iter()
+        .map(|h| (h.field.as_str().to_string(), h.value.as_str().to_owned()))
+        .collect::<Vec<_>>();
+
+    let host = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("host"))
+        .map(|(_, host)| host.as_str())
+        .unwrap_or(listen);
+    let url = format!("http://{}{}", host, request.url());
+    let url = worker::Url::parse(&url).map_err(|e| e.to_string())?;
+
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+
+    Ok(http::Request::new(
+        Method::parse(request.method().as_str()),
+        url,
+        headers,
+        body,
+    ))
+}
+
+fn to_response(response: http::Response) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+    let (status, headers, body) = response.into_parts();
+
+    let mut result = tiny_http::Response::from_data(body).with_status_code(status);
+    for (name, value) in headers {
+        if let Ok(header) = tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            result.add_header(header);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    const FIXTURE: &str = include_str!("../../../pob/test/316_empty.xml");
+
+    fn export() -> String {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(FIXTURE.as_bytes()).unwrap();
+        base64::encode_config(encoder.finish().unwrap(), base64::URL_SAFE)
+    }
+
+    /// Starts a server on a free port with an empty data directory, returns its address.
+    fn start(name: &str) -> (String, PathBuf) {
+        let data = std::env::temp_dir().join(format!("pobbin-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&data);
+
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap().to_string();
+        let config = Config {
+            listen: addr.clone(),
+            data: data.clone(),
+            assets: data.join("assets"),
+            security: Default::default(),
+            webhooks: Vec::new(),
+            robots: Default::default(),
+        };
+        std::thread::spawn(move || {
+            let _ = run(server, &config);
+        });
+
+        (format!("http://{}", addr), data)
+    }
+
+    #[test]
+    fn test_upload_and_fetch() {
+        let (url, data) = start("upload");
+        let export = export();
+
+        let id = ureq::post(&format!("{}/pob/", url))
+            .send_string(&export)
+            .unwrap()
+            .into_string()
+            .unwrap();
+
+        let response = ureq::get(&format!("{}/pob/{}", url, id)).call().unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(response.header(request_id::HEADER).is_some());
+        assert_eq!(response.into_string().unwrap(), export);
+
+        // Uploading the same build again resolves to the stored paste
+        let again = ureq::post(&format!("{}/pob/", url))
+            .send_string(&export)
+            .unwrap()
+            .into_string()
+            .unwrap();
+        assert_eq!(again, id);
+
+        let err = ureq::get(&format!("{}/pob/missing", url))
+            .call()
+            .unwrap_err();
+        assert!(matches!(err, ureq::Error::Status(404, _)));
+
+        let _ = std::fs::remove_dir_all(data);
+    }
+}
@@ -0,0 +1,358 @@
+use crate::{
+    assets::EnvAssetExt,
+    consts, moderation,
+    platform::{Platform, WorkerPlatform},
+    storage::StoredPaste,
+    utils::{self, ResponseExt},
+    Error, Result,
+};
+use fontdue::{Font, FontSettings};
+use pob::SerdePathOfBuilding;
+use std::borrow::Cow;
+use tiny_skia::{
+    Color, FilterQuality, Paint, Pixmap, PixmapPaint, PremultipliedColorU8, Rect, Transform,
+};
+use worker::{Env, Method, Request, Response};
+
+const FONT: &str = "assets/fonts/DejaVuSans-Bold.ttf";
+/// Part of the key and the entity tag of stored previews, bump it when the card changes to
+/// render them again.
+const CARD_VERSION: u32 = 1;
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const PADDING: f32 = 64.0;
+const ART_SIZE: f32 = 186.0;
+
+const BACKGROUND: [u8; 3] = [0x0f, 0x17, 0x2a];
+const TEXT: [u8; 3] = [0xf8, 0xfa, 0xfc];
+const TEXT_MUTED: [u8; 3] = [0x94, 0xa3, 0xb8];
+
+pub async fn try_handle(req: &Request, env: &Env) -> Result<Option<Response>> {
+    if req.method() != Method::Get {
+        return Ok(None);
+    }
+
+    match is_preview_url(&req.path()) {
+        Some(id) => handle_preview(req, env, id).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn is_preview_url(path: &str) -> Option<&str> {
+    path.trim_start_matches('/')
+        .split_once('/')
+        .filter(|(_, name)| *name == "preview.png")
+        .map(|(id, _)| id)
+}
+
+async fn handle_preview(req: &Request, env: &Env, id: &str) -> Result<Response> {
+    let platform = WorkerPlatform::new(env)?;
+    moderation::ensure_available(platform.moderation(), id).await?;
+    let paste = platform
+        .pastes()
+        .get(id)
+        .await?
+        .ok_or_else(|| Error::NotFound("paste", id.to_owned()))?;
+    let url = req.url()?;
+    let key = utils::query_param(&url, "key");
+    paste.verify_access(&platform, id, key.as_deref()).await?;
+
+    // Rendering is expensive, previews of public pastes are rendered once and stored.
+    // Private pastes are rare and their previews must not outlive the paste.
+    let private = paste.metadata.private;
+    let host = url.host_str().unwrap_or_default();
+    let previews = env.kv(consts::KV_PREVIEWS)?;
+    let stored_key = format!("v{}/{}/{}", CARD_VERSION, host, id);
+
+    let stored = match private {
+        true => None,
+        false => previews.get(&stored_key).bytes().await?,
+    };
+    let png = match stored {
+        Some(png) => png,
+        None => {
+            let png = render_paste(env, paste, host).await?;
+            if !private {
+                let stored = match previews.put_bytes(&stored_key, &png) {
+                    Ok(put) => {
+                        put.expiration_ttl(consts::STORED_PREVIEW_TTL)
+                            .execute()
+                            .await
+                    }
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = stored {
+                    log::warn!("failed to store preview of '{}': {}", id, err);
+                }
+            }
+            png
+        }
+    };
+
+    let response = Response::from_bytes(png)?
+        .with_content_type("image/png")?
+        .with_etag(&format!("v{}-{}", CARD_VERSION, id))?;
+
+    match private {
+        true => response.cache_private(),
+        false => response.cache_for(consts::CACHE_PREVIEW),
+    }
+}
+
+async fn render_paste(env: &Env, paste: StoredPaste, host: &str) -> Result<Vec<u8>> {
+    let content = String::from_utf8(paste.data).map_err(|e| e.to_string())?;
+    let pob = SerdePathOfBuilding::from_export(&content).map_err(|e| e.to_string())?;
+    let mut info = app::PasteInfo::new(&pob);
+    if let Some(title) = paste.metadata.title {
+        info.title = title;
+    }
+
+    let font = env
+        .get_asset(FONT)?
+        .bytes()
+        .await?
+        .ok_or_else(|| Error::NotFound("asset", FONT.to_owned()))?;
+    let art = match info.image {
+        Some((path, _, _)) => env.get_asset(path.trim_start_matches('/'))?.bytes().await?,
+        None => None,
+    };
+
+    let card = Card {
+        title: &info.title,
+        subtitle: &info.ascendancy_or_class,
+        color: info.color,
+        stats: &info.stats,
+        footer: host,
+    };
+    render(&card, &font, art.as_deref())
+}
+
+/// Everything displayed on a preview card.
+pub struct Card<'a> {
+    pub title: &'a str,
+    pub subtitle: &'a str,
+    /// Hex color of the ascendancy, e.g. `#96afc8`.
+    pub color: &'a str,
+    /// Stat lines as spans of text and their tailwind classes.
+    pub stats: &'a [Vec<(&'static str, String)>],
+    pub footer: &'a str,
+}
+
+/// Renders the card into a PNG, `art` is the PNG encoded ascendancy image.
+pub fn render(card: &Card, font: &[u8], art: Option<&[u8]>) -> Result<Vec<u8>> {
+    let font = Font::from_bytes(font, FontSettings::default())
+        .map_err(|e| format!("invalid font: {}", e))?;
+
+    let mut pixmap = Pixmap::new(WIDTH, HEIGHT).unwrap();
+    pixmap.fill(rgb(BACKGROUND));
+
+    let accent = parse_hex(card.color).unwrap_or_else(|| rgb(TEXT));
+    let mut paint = Paint::default();
+    paint.set_color(accent);
+    let bar = Rect::from_xywh(0.0, 0.0, WIDTH as f32, 12.0).unwrap();
+    pixmap.fill_rect(bar, &paint, Transform::identity(), None);
+
+    let mut left = PADDING;
+    if let Some(art) = art {
+        let art = Pixmap::decode_png(art).map_err(|e| format!("invalid image: {}", e))?;
+        let scale = ART_SIZE / art.height() as f32;
+        let paint = PixmapPaint {
+            quality: FilterQuality::Bicubic,
+            ..PixmapPaint::default()
+        };
+        let transform = Transform::from_row(scale, 0.0, 0.0, scale, PADDING, 72.0);
+        pixmap.draw_pixmap(0, 0, art.as_ref(), &paint, transform, None);
+        left += art.width() as f32 * scale + 48.0;
+    }
+
+    let max_width = WIDTH as f32 - PADDING - left;
+    let title = truncate(&font, card.title, 56.0, max_width);
+    draw_text(&mut pixmap, &font, &title, left, 140.0, 56.0, rgb(TEXT));
+    let subtitle = truncate(&font, card.subtitle, 40.0, max_width);
+    draw_text(&mut pixmap, &font, &subtitle, left, 204.0, 40.0, accent);
+
+    let mut baseline = 340.0;
+    for line in card.stats {
+        let mut x = PADDING;
+        for (formatting, text) in line {
+            x = draw_text(
+                &mut pixmap,
+                &font,
+                text,
+                x,
+                baseline,
+                40.0,
+                rgb(tailwind_color(formatting)),
+            );
+        }
+        baseline += 64.0;
+    }
+
+    let footer_width = text_width(&font, card.footer, 28.0);
+    draw_text(
+        &mut pixmap,
+        &font,
+        card.footer,
+        WIDTH as f32 - PADDING - footer_width,
+        HEIGHT as f32 - 40.0,
+        28.0,
+        rgb(TEXT_MUTED),
+    );
+
+    Ok(pixmap
+        .encode_png()
+        .map_err(|e| format!("failed to encode png: {}", e))?)
+}
+
+/// Draws a single line of text, returns the x position after the text.
+fn draw_text(
+    pixmap: &mut Pixmap,
+    font: &Font,
+    text: &str,
+    x: f32,
+    baseline: f32,
+    size: f32,
+    color: Color,
+) -> f32 {
+    let color = color.to_color_u8();
+    let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+    let pixels = pixmap.pixels_mut();
+
+    let mut x = x;
+    for c in text.chars() {
+        let (metrics, coverage) = font.rasterize(c, size);
+        let left = x.round() as i32 + metrics.xmin;
+        let top = baseline.round() as i32 - metrics.height as i32 - metrics.ymin;
+
+        for (i, &alpha) in coverage.iter().enumerate() {
+            let px = left + (i % metrics.width) as i32;
+            let py = top + (i / metrics.width) as i32;
+            if alpha == 0 || px < 0 || py < 0 || px >= width || py >= height {
+                continue;
+            }
+
+            let pixel = &mut pixels[(py * width + px) as usize];
+            *pixel = blend(*pixel, color, alpha);
+        }
+
+        x += metrics.advance_width;
+    }
+
+    x
+}
+
+fn blend(dst: PremultipliedColorU8, src: tiny_skia::ColorU8, coverage: u8) -> PremultipliedColorU8 {
+    let alpha = src.alpha() as u32 * coverage as u32 / 255;
+    let mix = |src: u8, dst: u8| ((src as u32 * alpha + dst as u32 * (255 - alpha)) / 255) as u8;
+    PremultipliedColorU8::from_rgba(
+        mix(src.red(), dst.red()),
+        mix(src.green(), dst.green()),
+        mix(src.blue(), dst.blue()),
+        (alpha + dst.alpha() as u32 * (255 - alpha) / 255) as u8,
+    )
+    .unwrap_or(dst)
+}
+
+fn text_width(font: &Font, text: &str, size: f32) -> f32 {
+    text.chars()
+        .map(|c| font.metrics(c, size).advance_width)
+        .sum()
+}
+
+/// Shortens the text with an ellipsis to fit into `max_width`.
+fn truncate<'a>(font: &Font, text: &'a str, size: f32, max_width: f32) -> Cow<'a, str> {
+    if text_width(font, text, size) <= max_width {
+        return Cow::Borrowed(text);
+    }
+
+    let max_width = max_width - text_width(font, "…", size);
+    let mut width = 0.0;
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        width += font.metrics(c, size).advance_width;
+        if width > max_width {
+            break;
+        }
+        result.push(c);
+    }
+    result.truncate(result.trim_end().len());
+    result.push('…');
+
+    Cow::Owned(result)
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> Color {
+    Color::from_rgba8(r, g, b, 255)
+}
+
+fn parse_hex(color: &str) -> Option<Color> {
+    let color = color.strip_prefix('#')?;
+    let value = u32::from_str_radix(color, 16).ok()?;
+    (color.len() == 6).then(|| rgb([(value >> 16) as u8, (value >> 8) as u8, value as u8]))
+}
+
+/// Maps the text colors used by the stat summaries to RGB, preferring dark mode variants.
+fn tailwind_color(formatting: &str) -> [u8; 3] {
+    let class = formatting
+        .split_whitespace()
+        .find_map(|class| class.strip_prefix("dark:"))
+        .or_else(|| formatting.split_whitespace().next())
+        .unwrap_or("");
+
+    match class {
+        "text-rose-500" => [0xf4, 0x3f, 0x5e],
+        "text-cyan-200" => [0xa5, 0xf3, 0xfc],
+        "text-blue-400" => [0x60, 0xa5, 0xfa],
+        "text-amber-50" => [0xff, 0xfb, 0xeb],
+        "text-orange-400" => [0xfb, 0x92, 0x3c],
+        "text-orange-500" => [0xf9, 0x73, 0x16],
+        "text-yellow-300" => [0xfd, 0xe0, 0x47],
+        "text-fuchsia-500" => [0xd9, 0x46, 0xef],
+        _ => TEXT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT: &[u8] = include_bytes!("../../app/assets/fonts/DejaVuSans-Bold.ttf");
+    const ART: &[u8] = include_bytes!("../../app/assets/asc/Slayer.png");
+
+    #[test]
+    fn test_render() {
+        let stats = vec![
+            vec![
+                ("", "Life: ".to_owned()),
+                ("text-rose-500", "5,123".to_owned()),
+                ("text-rose-500", " [150%]".to_owned()),
+            ],
+            vec![
+                ("", "Resistances: ".to_owned()),
+                ("text-orange-500 dark:text-orange-400", "75%".to_owned()),
+            ],
+        ];
+        let card = Card {
+            title: "Level 95 Slayer: Cyclone of a very long title that does not fit at all",
+            subtitle: "Slayer",
+            color: "#96afc8",
+            stats: &stats,
+            footer: "pobb.in",
+        };
+
+        let png = render(&card, FONT, Some(ART)).unwrap();
+        let pixmap = Pixmap::decode_png(&png).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (WIDTH, HEIGHT));
+    }
+
+    #[test]
+    fn test_tailwind_color() {
+        assert_eq!(
+            tailwind_color("text-orange-500 dark:text-orange-400"),
+            [0xfb, 0x92, 0x3c]
+        );
+        assert_eq!(tailwind_color("text-rose-500"), [0xf4, 0x3f, 0x5e]);
+        assert_eq!(tailwind_color(""), TEXT);
+    }
+}
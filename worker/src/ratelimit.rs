@@ -0,0 +1,58 @@
+//! Limits of requests per client in isolate memory.
+//!
+//! Every isolate counts on its own, limits are approximate and only stop a single client
+//! from flooding an endpoint.
+
+use crate::{http::Request, memo::Memo, Error, Result};
+
+pub struct Limit {
+    /// Requests per client and window.
+    counts: Memo<u32>,
+    max: u32,
+    window_ms: u64,
+}
+
+impl Limit {
+    pub const fn new(max: u32, window_ms: u64, capacity: usize) -> Self {
+        Self {
+            counts: Memo::new(window_ms, capacity),
+            max,
+            window_ms,
+        }
+    }
+
+    /// Counts a request of the client, fails with [`Error::RateLimited`] once the client
+    /// used up the requests of the current window.
+    pub fn check(&self, client: &str, now: u64) -> Result<()> {
+        let key = format!("{}:{}", client, now / self.window_ms);
+        let count = self.counts.get(&key, now).unwrap_or(0);
+        if count >= self.max {
+            return Err(Error::RateLimited);
+        }
+
+        self.counts.insert(&key, count + 1, now);
+        Ok(())
+    }
+}
+
+/// Address of the client, `None` if the platform does not provide it, e.g. the native server.
+pub fn client(req: &Request) -> Option<&str> {
+    req.header("CF-Connecting-IP")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit() {
+        let limit = Limit::new(2, 1_000, 10);
+        assert!(limit.check("a", 0).is_ok());
+        assert!(limit.check("a", 10).is_ok());
+        assert!(matches!(limit.check("a", 20), Err(Error::RateLimited)));
+        assert!(limit.check("b", 20).is_ok());
+
+        // The next window starts over
+        assert!(limit.check("a", 1_000).is_ok());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    consts,
+    store::{Store, WorkerKv},
+    utils::{self, html_escape, ResponseExt},
+    Result,
+};
+use app::RecentPaste;
+use std::fmt::Write;
+use worker::{Env, Method, Request, Response};
+
+const PREFIX: &str = "recent:";
+const TITLE: &str = "POB B.in - Recent Builds";
+/// Pastes past the limit are deleted in batches of this size, the list stays bounded
+/// even if a prune fails.
+const PRUNE_BATCH: usize = 10;
+
+pub async fn try_handle(req: &Request, env: &Env) -> Result<Option<Response>> {
+    if req.method() != Method::Get {
+        return Ok(None);
+    }
+
+    let (content_type, render): (_, fn(&str, &[RecentPaste]) -> String) = match req.path().as_str()
+    {
+        "/recent.atom" => ("application/atom+xml; charset=utf-8", atom),
+        "/recent.rss" => ("application/rss+xml; charset=utf-8", rss),
+        _ => return Ok(None),
+    };
+
+    let url = req.url()?;
+    let host = url.host_str().unwrap_or_default();
+    let recent = list(&WorkerKv::new(env, consts::KV_PASTE_INDEX)?).await?;
+
+    Response::ok(render(host, &recent))?
+        .with_content_type(content_type)?
+        .cache_for(consts::CACHE_RECENT)
+        .map(Some)
+}
+
+pub fn is_feed_url(path: &str) -> bool {
+    matches!(path, "/recent.atom" | "/recent.rss")
+}
+
+/// The most recent public pastes, newest first.
+pub async fn list(store: &dyn Store) -> Result<Vec<RecentPaste>> {
+    let page = store
+        .list(PREFIX, None, consts::RECENT_MAX_PASTES as u64)
+        .await?;
+
+    Ok(page
+        .keys
+        .into_iter()
+        .filter_map(|key| serde_json::from_value(key.metadata?).ok())
+        .collect())
+}
+
+/// Adds a paste to the list, must only be called for public pastes.
+///
+/// Every paste has its own key, ordered by the inverted upload time like the search index,
+/// so concurrent uploads do not overwrite each other. Pastes beyond
+/// [`consts::RECENT_MAX_PASTES`] are pruned.
+pub async fn add(store: &dyn Store, paste: RecentPaste) -> Result<()> {
+    let key = key(paste.created, &paste.id);
+    store
+        .put(&key, String::new(), Some(serde_json::to_value(&paste)?))
+        .await?;
+
+    let limit = (consts::RECENT_MAX_PASTES + PRUNE_BATCH) as u64;
+    let page = store.list(PREFIX, None, limit).await?;
+    for key in page.keys.iter().skip(consts::RECENT_MAX_PASTES) {
+        store.delete(&key.name).await?;
+    }
+
+    Ok(())
+}
+
+/// The paste if it is in the list.
+pub async fn get(store: &dyn Store, id: &str) -> Result<Option<RecentPaste>> {
+    Ok(list(store).await?.into_iter().find(|paste| paste.id == id))
+}
+
+pub async fn remove(store: &dyn Store, id: &str) -> Result<()> {
+    let limit = (consts::RECENT_MAX_PASTES + PRUNE_BATCH) as u64;
+    let page = store.list(PREFIX, None, limit).await?;
+
+    let suffix = format!(":{}", id);
+    for key in page.keys.iter().filter(|key| key.name.ends_with(&suffix)) {
+        store.delete(&key.name).await?;
+    }
+
+    Ok(())
+}
+
+fn key(created: u64, id: &str) -> String {
+    format!("{}{:020}:{}", PREFIX, u64::MAX - created, id)
+}
+
+fn atom(host: &str, recent: &[RecentPaste]) -> String {
+    let updated = recent.first().map(|p| p.created).unwrap_or_default();
+
+    let mut feed = String::new();
+    let _ = write!(
+        feed,
+        r#"<?xml version="1.0" encoding="utf-8"?><feed xmlns="http://www.w3.org/2005/Atom"><title>{}</title><id>https://{host}/recent</id><link rel="alternate" href="https://{host}/recent"/><link rel="self" href="https://{host}/recent.atom"/><updated>{}</updated>"#,
+        TITLE,
+        utils::rfc3339(updated),
+        host = html_escape(host),
+    );
+    for paste in recent {
+        let _ = write!(
+            feed,
+            r#"<entry><title>{}</title><id>https://{host}/{id}</id><link href="https://{host}/{id}"/><updated>{}</updated><author><name>{}</name></author><category term="{}"/><summary>{}</summary></entry>"#,
+            html_escape(&paste.title),
+            utils::rfc3339(paste.created),
+            html_escape(host),
+            html_escape(&paste.ascendancy_or_class),
+            html_escape(&summary(paste)),
+            host = html_escape(host),
+            id = paste.id,
+        );
+    }
+    feed.push_str("</feed>");
+
+    feed
+}
+
+fn rss(host: &str, recent: &[RecentPaste]) -> String {
+    let mut feed = String::new();
+    let _ = write!(
+        feed,
+        r#"<?xml version="1.0" encoding="utf-8"?><rss version="2.0"><channel><title>{}</title><link>https://{host}/recent</link><description>Recently shared Path of Building builds</description>"#,
+        TITLE,
+        host = html_escape(host),
+    );
+    for paste in recent {
+        let _ = write!(
+            feed,
+            r#"<item><title>{}</title><link>https://{host}/{id}</link><guid>https://{host}/{id}</guid><pubDate>{}</pubDate><category>{}</category><description>{}</description></item>"#,
+            html_escape(&paste.title),
+            utils::rfc2822(paste.created),
+            html_escape(&paste.ascendancy_or_class),
+            html_escape(&summary(paste)),
+            host = html_escape(host),
+            id = paste.id,
+        );
+    }
+    feed.push_str("</channel></rss>");
+
+    feed
+}
+
+fn summary(paste: &RecentPaste) -> String {
+    std::iter::once(paste.ascendancy_or_class.as_str())
+        .chain(paste.main_skill.as_deref())
+        .chain(paste.summary.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{future::block_on, store::Memory};
+
+    fn paste(id: &str) -> RecentPaste {
+        paste_at(id, 1_640_995_200_000)
+    }
+
+    fn paste_at(id: &str, created: u64) -> RecentPaste {
+        RecentPaste {
+            id: id.to_owned(),
+            title: format!("Build <{}>", id),
+            ascendancy_or_class: "Slayer".to_owned(),
+            main_skill: Some("Cyclone".to_owned()),
+            summary: vec!["Life: 5,000".to_owned()],
+            created,
+        }
+    }
+
+    #[test]
+    fn test_add() {
+        let store = Memory::default();
+        for (i, id) in ["a", "b"].into_iter().enumerate() {
+            block_on(add(&store, paste_at(id, i as u64))).unwrap();
+        }
+        for i in 0..consts::RECENT_MAX_PASTES {
+            block_on(add(&store, paste_at(&i.to_string(), 10 + i as u64))).unwrap();
+        }
+
+        let recent = block_on(list(&store)).unwrap();
+        assert_eq!(recent.len(), consts::RECENT_MAX_PASTES);
+        assert_eq!(recent[0].id, (consts::RECENT_MAX_PASTES - 1).to_string());
+        assert!(recent.iter().all(|p| p.id != "a" && p.id != "b"));
+        assert_eq!(store.keys().len(), consts::RECENT_MAX_PASTES);
+
+        let id = recent[1].id.clone();
+        assert_eq!(block_on(get(&store, &id)).unwrap(), Some(recent[1].clone()));
+        block_on(remove(&store, &id)).unwrap();
+        assert_eq!(block_on(get(&store, &id)).unwrap(), None);
+        let recent = block_on(list(&store)).unwrap();
+        assert!(recent.iter().all(|p| p.id != id));
+        assert_eq!(recent.len(), consts::RECENT_MAX_PASTES - 1);
+    }
+
+    #[test]
+    fn test_feeds() {
+        let recent = [paste("a")];
+
+        let atom = atom("pobb.in", &recent);
+        assert!(atom.contains("<title>Build &lt;a&gt;</title>"));
+        assert!(atom.contains("<id>https://pobb.in/a</id>"));
+        assert!(atom.contains("<updated>2022-01-01T00:00:00Z</updated>"));
+
+        let rss = rss("pobb.in", &recent);
+        assert!(rss.contains("<link>https://pobb.in/a</link>"));
+        assert!(rss.contains("<pubDate>Sat, 01 Jan 2022 00:00:00 GMT</pubDate>"));
+        assert!(rss.contains("<description>Slayer\nCyclone\nLife: 5,000</description>"));
+    }
+}
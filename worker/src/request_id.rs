@@ -0,0 +1,98 @@
+//! Identifier of a request, which users can quote to correlate reports with logs and errors.
+//!
+//! On Cloudflare the `cf-ray` of the request is used, which also shows up in the Cloudflare
+//! dashboard, otherwise a random id is generated.
+//!
+//! Requests are handled concurrently, the id is only set while the future of its request is
+//! polled, see [`run`].
+
+use crate::{
+    future::{self, LocalBoxFuture},
+    Result,
+};
+use std::cell::RefCell;
+use std::future::Future;
+
+pub const HEADER: &str = "X-Request-Id";
+
+thread_local! {
+    static CURRENT: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Creates the id of a request from its `cf-ray` header or `random` bytes.
+pub fn new(ray: Option<&str>, random: impl FnOnce(&mut [u8]) -> Result<()>) -> Result<String> {
+    let ray = ray.map(str::trim).filter(|ray| {
+        !ray.is_empty()
+            && ray.len() <= 64
+            && ray.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    });
+    if let Some(ray) = ray {
+        return Ok(ray.to_owned());
+    }
+
+    let mut buf = [0; 12];
+    random(&mut buf)?;
+    Ok(base64::encode_config(buf, base64::URL_SAFE_NO_PAD))
+}
+
+/// Runs `future` with `id` as the id of the request, which is attached to all log messages.
+pub fn run<'a, T: 'a>(
+    id: String,
+    future: impl Future<Output = T> + 'a,
+) -> impl Future<Output = T> + 'a {
+    future::scoped(&CURRENT, id, future)
+}
+
+/// Runs a background task with the id of the request currently handled, if there is one.
+pub fn inherit(task: LocalBoxFuture<'static, ()>) -> LocalBoxFuture<'static, ()> {
+    match current() {
+        Some(id) => Box::pin(run(id, task)),
+        None => task,
+    }
+}
+
+/// Id of the request currently handled, see [`run`].
+pub fn current() -> Option<String> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::future::block_on;
+
+    #[test]
+    fn test_new() {
+        let random = |buf: &mut [u8]| {
+            buf.fill(0xff);
+            Ok(())
+        };
+
+        assert_eq!(
+            new(Some("7d1e2f3a4b5c6d7e-FRA"), random).unwrap(),
+            "7d1e2f3a4b5c6d7e-FRA"
+        );
+        assert_eq!(new(None, random).unwrap(), "________________");
+        assert_eq!(new(Some(""), random).unwrap(), "________________");
+        assert_eq!(new(Some("<script>"), random).unwrap(), "________________");
+    }
+
+    #[test]
+    fn test_run() {
+        assert_eq!(current(), None);
+
+        let mut task = None;
+        block_on(run("abc".to_owned(), async {
+            assert_eq!(current().as_deref(), Some("abc"));
+            task = Some(inherit(Box::pin(async {
+                assert_eq!(current().as_deref(), Some("abc"));
+            })));
+        }));
+        assert_eq!(current(), None);
+
+        // Background tasks outlive the request and still log its id
+        block_on(task.unwrap());
+        assert_eq!(current(), None);
+        block_on(inherit(Box::pin(async { assert_eq!(current(), None) })));
+    }
+}
@@ -1,8 +1,14 @@
+use crate::{future::LocalBoxFuture, metrics};
 use std::future::Future;
 
 pub enum Retry<T, Err> {
     Ok(T),
+    /// Failed attempt, retried after the backoff delay of the policy.
     Err(Err),
+    /// Failed attempt, which can be retried immediately, e.g. with new credentials.
+    Now(Err),
+    /// Failed attempt, retried after the delay in milliseconds requested by the server.
+    After(Err, u64),
 }
 
 impl<T, Err> Retry<T, Err> {
@@ -13,30 +19,236 @@ impl<T, Err> Retry<T, Err> {
     pub fn err(err: Err) -> Result<Self, Err> {
         Ok(Self::Err(err))
     }
+
+    pub fn now(err: Err) -> Result<Self, Err> {
+        Ok(Self::Now(err))
+    }
+
+    /// Retries after the server provided delay or the backoff delay if there is none.
+    pub fn after(err: Err, delay_ms: Option<u64>) -> Result<Self, Err> {
+        match delay_ms {
+            Some(delay_ms) => Ok(Self::After(err, delay_ms)),
+            None => Ok(Self::Err(err)),
+        }
+    }
+}
+
+/// Exponentially increasing delays between attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    /// Delay in milliseconds after the first failed attempt, doubled after every attempt.
+    pub initial_ms: u64,
+    pub max_ms: u64,
+    /// Fraction of the delay which is randomized, between `0.0` and `1.0`.
+    ///
+    /// Spreads out retries of concurrent requests, which failed at the same time.
+    pub jitter: f64,
+}
+
+impl Backoff {
+    /// Delay after the failed `attempt`, `random` is between `0.0` and `1.0`.
+    fn delay(&self, attempt: usize, random: f64) -> u64 {
+        let factor = 1u64 << (attempt.max(1) - 1).min(20);
+        let delay = self.initial_ms.saturating_mul(factor).min(self.max_ms) as f64;
+        (delay * (1.0 - self.jitter.clamp(0.0, 1.0) * random)) as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Policy {
+    pub max_attempts: usize,
+    pub backoff: Backoff,
+    /// Total time budget in milliseconds for all attempts including the delays.
+    ///
+    /// No further attempt is made if its delay would exceed the budget.
+    pub deadline_ms: u64,
+}
+
+/// Time source of the retries, which can be faked in tests.
+pub trait Timer {
+    /// Current time in milliseconds.
+    fn now(&self) -> u64;
+    fn sleep(&self, ms: u64) -> LocalBoxFuture<'_, ()>;
+    /// A random number between `0.0` and `1.0`, used for the jitter.
+    fn random(&self) -> f64;
 }
 
-pub async fn retry<F, T, Err, Fut>(max_attempts: usize, mut f: F) -> Result<T, Err>
+/// Timer of the workers runtime, sleeps using `setTimeout`.
+pub struct WorkerTimer;
+
+impl Timer for WorkerTimer {
+    fn now(&self) -> u64 {
+        worker::Date::now().as_millis()
+    }
+
+    fn sleep(&self, ms: u64) -> LocalBoxFuture<'_, ()> {
+        use web_sys::WorkerGlobalScope;
+        use worker::{js_sys, wasm_bindgen::JsCast, wasm_bindgen_futures::JsFuture};
+
+        Box::pin(async move {
+            let promise = js_sys::Promise::new(&mut |resolve, _| {
+                let worker: WorkerGlobalScope = js_sys::global().unchecked_into();
+                let _ = worker
+                    .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms as i32);
+            });
+            let _ = JsFuture::from(promise).await;
+        })
+    }
+
+    fn random(&self) -> f64 {
+        worker::js_sys::Math::random()
+    }
+}
+
+/// Parses a `Retry-After` header in seconds into milliseconds, dates are not supported.
+pub fn parse_retry_after(value: &str) -> Option<u64> {
+    value
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|seconds| seconds.saturating_mul(1_000))
+}
+
+pub async fn retry<F, T, Err, Fut>(
+    operation: &'static str,
+    policy: &Policy,
+    timer: &dyn Timer,
+    mut f: F,
+) -> Result<T, Err>
 where
     F: FnMut(usize) -> Fut,
     Fut: Future<Output = Result<Retry<T, Err>, Err>>,
 {
     assert!(
-        max_attempts > 1,
+        policy.max_attempts > 1,
         "it does not make sense to retry with only 1 attempt"
     );
-    for i in 1..=max_attempts {
-        match f(i).await {
+    let start = timer.now();
+
+    for i in 1..=policy.max_attempts {
+        let (err, delay) = match f(i).await {
             Ok(Retry::Ok(result)) => return Ok(result),
-            Ok(Retry::Err(err)) => {
-                let is_last_attempt = i == max_attempts;
-                if is_last_attempt {
-                    log::warn!("no more attempts available");
-                    return Err(err);
-                }
-            }
+            Ok(Retry::Err(err)) => (err, policy.backoff.delay(i, timer.random())),
+            Ok(Retry::Now(err)) => (err, 0),
+            Ok(Retry::After(err, delay)) => (err, delay),
             Err(err) => return Err(err),
+        };
+
+        let elapsed = timer.now().saturating_sub(start);
+        let is_last_attempt =
+            i == policy.max_attempts || elapsed.saturating_add(delay) > policy.deadline_ms;
+        metrics::emit(metrics::Event::Retry {
+            operation,
+            attempt: i,
+            exhausted: is_last_attempt,
+        });
+        if is_last_attempt {
+            log::warn!("no more attempts available");
+            return Err(err);
+        }
+
+        if delay > 0 {
+            log::debug!("retrying '{}' in {}ms", operation, delay);
+            timer.sleep(delay).await;
         }
     }
 
     unreachable!();
 }
+
+/// Timer which does not sleep, but advances its time and records all delays.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeTimer {
+    now: std::cell::Cell<u64>,
+    pub sleeps: std::cell::RefCell<Vec<u64>>,
+}
+
+#[cfg(test)]
+impl Timer for FakeTimer {
+    fn now(&self) -> u64 {
+        self.now.get()
+    }
+
+    fn sleep(&self, ms: u64) -> LocalBoxFuture<'_, ()> {
+        self.now.set(self.now.get() + ms);
+        self.sleeps.borrow_mut().push(ms);
+        Box::pin(async {})
+    }
+
+    fn random(&self) -> f64 {
+        0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::future::block_on;
+    use std::cell::{Cell, RefCell};
+
+    const POLICY: Policy = Policy {
+        max_attempts: 5,
+        backoff: Backoff {
+            initial_ms: 100,
+            max_ms: 300,
+            jitter: 0.5,
+        },
+        deadline_ms: 10_000,
+    };
+
+    /// Runs `retry` with the results of the attempts in order.
+    fn run(
+        policy: &Policy,
+        timer: &FakeTimer,
+        results: Vec<Retry<usize, usize>>,
+    ) -> (Result<usize, usize>, usize) {
+        let results = RefCell::new(results.into_iter());
+        let attempts = Cell::new(0);
+        let result = block_on(retry("test", policy, timer, |attempt| {
+            attempts.set(attempt);
+            let result = results.borrow_mut().next().unwrap();
+            async move { Ok(result) }
+        }));
+        (result, attempts.get())
+    }
+
+    #[test]
+    fn test_backoff() {
+        let timer = FakeTimer::default();
+        let results = vec![Retry::Err(1), Retry::Err(2), Retry::Err(3), Retry::Ok(4)];
+        assert_eq!(run(&POLICY, &timer, results), (Ok(4), 4));
+        assert_eq!(*timer.sleeps.borrow(), [75, 150, 225]);
+
+        let timer = FakeTimer::default();
+        let results = (1..=5).map(Retry::Err).collect();
+        assert_eq!(run(&POLICY, &timer, results), (Err(5), 5));
+    }
+
+    #[test]
+    fn test_server_delay() {
+        let timer = FakeTimer::default();
+        let results = vec![Retry::After(1, 2_000), Retry::Now(2), Retry::Ok(3)];
+        assert_eq!(run(&POLICY, &timer, results), (Ok(3), 3));
+        assert_eq!(*timer.sleeps.borrow(), [2_000]);
+    }
+
+    #[test]
+    fn test_deadline() {
+        let timer = FakeTimer::default();
+        let policy = Policy {
+            deadline_ms: 3_000,
+            ..POLICY
+        };
+        let results = vec![Retry::After(1, 2_000), Retry::After(2, 2_000), Retry::Ok(3)];
+        assert_eq!(run(&policy, &timer, results), (Err(2), 2));
+        assert_eq!(*timer.sleeps.borrow(), [2_000]);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("3"), Some(3_000));
+        assert_eq!(parse_retry_after(" 0 "), Some(0));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
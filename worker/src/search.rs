@@ -0,0 +1,502 @@
+use crate::{consts, store::Store, Error, Result};
+use pob::{Keystone, PathOfBuilding, PathOfBuildingExt, SerdePathOfBuilding};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+const PREFIX: &str = "search";
+/// Leading digits of the inverted creation time shared by the keys of a period.
+///
+/// Periods span 10^8 milliseconds (about 28 hours), see [`period_of`].
+const PERIOD_DIGITS: usize = 12;
+
+/// A public paste in the search index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    pub title: String,
+    pub ascendancy_or_class: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub main_skill: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keystones: Vec<String>,
+    pub level: u8,
+    /// Upload time in milliseconds since the epoch.
+    pub created: u64,
+}
+
+impl Entry {
+    pub fn new(id: String, created: u64, pob: &SerdePathOfBuilding, title: String) -> Self {
+        Self {
+            id,
+            title,
+            ascendancy_or_class: pob.ascendancy_or_class_name().to_owned(),
+            main_skill: pob.main_skill_name().map(ToOwned::to_owned),
+            keystones: Keystone::ALL
+                .iter()
+                .filter(|keystone| pob.has_keystone(**keystone))
+                .map(|keystone| keystone.name().to_owned())
+                .collect(),
+            level: pob.level(),
+            created,
+        }
+    }
+
+    /// The index keys of the entry, one listing all pastes and one per attribute.
+    ///
+    /// Keys end with the inverted upload time, listings return the newest pastes first.
+    fn keys(&self) -> Vec<String> {
+        let suffix = format!("{:020}:{}", u64::MAX - self.created, self.id);
+
+        let mut keys = vec![format!("{}:all:{}", PREFIX, suffix)];
+        let mut attribute = |name, value: &str| {
+            keys.push(format!("{}{}", attribute_prefix(name, value), suffix));
+        };
+        attribute("ascendancy", &self.ascendancy_or_class);
+        if let Some(skill) = &self.main_skill {
+            attribute("skill", skill);
+        }
+        for keystone in &self.keystones {
+            attribute("keystone", keystone);
+        }
+        attribute("level", &level(self.level));
+
+        keys
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Query {
+    pub ascendancy: Option<String>,
+    pub skill: Option<String>,
+    pub keystones: Vec<&'static str>,
+    pub min_level: Option<u8>,
+    pub max_level: Option<u8>,
+}
+
+impl Query {
+    /// Parses the query from url parameters, `keystone` may be repeated.
+    pub fn from_pairs<'a, I>(pairs: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (Cow<'a, str>, Cow<'a, str>)>,
+    {
+        let mut query = Self::default();
+
+        for (name, value) in pairs {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+
+            let level = || {
+                value
+                    .parse()
+                    .map_err(|_| Error::BadRequest(format!("Invalid level '{}'", value)))
+            };
+
+            match name.as_ref() {
+                "ascendancy" | "class" => query.ascendancy = Some(value.to_owned()),
+                "skill" => query.skill = Some(value.to_owned()),
+                "keystone" => {
+                    let keystone = Keystone::ALL
+                        .iter()
+                        .map(Keystone::name)
+                        .find(|name| slug(name) == slug(value))
+                        .ok_or_else(|| {
+                            Error::BadRequest(format!("Unknown keystone '{}'", value))
+                        })?;
+                    query.keystones.push(keystone);
+                }
+                "min_level" => query.min_level = Some(level()?),
+                "max_level" => query.max_level = Some(level()?),
+                _ => (),
+            }
+        }
+
+        Ok(query)
+    }
+
+    /// Prefix of the most selective index of the query, the other filters are applied while
+    /// scanning it.
+    fn prefix(&self) -> String {
+        if let Some(ascendancy) = &self.ascendancy {
+            return attribute_prefix("ascendancy", ascendancy);
+        }
+        if let Some(skill) = &self.skill {
+            return attribute_prefix("skill", skill);
+        }
+        if let Some(keystone) = self.keystones.first() {
+            return attribute_prefix("keystone", keystone);
+        }
+        match (self.min_level, self.max_level) {
+            (Some(min), Some(max)) if min == max => attribute_prefix("level", &level(min)),
+            _ => format!("{}:all:", PREFIX),
+        }
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        let eq = |a: &str, b: &str| slug(a) == slug(b);
+
+        self.ascendancy.as_ref().map_or(true, |ascendancy| {
+            eq(ascendancy, &entry.ascendancy_or_class)
+        }) && self.skill.as_ref().map_or(true, |skill| {
+            entry
+                .main_skill
+                .as_ref()
+                .map_or(false, |main_skill| eq(skill, main_skill))
+        }) && self
+            .keystones
+            .iter()
+            .all(|keystone| entry.keystones.iter().any(|k| eq(keystone, k)))
+            && self.min_level.map_or(true, |level| entry.level >= level)
+            && self.max_level.map_or(true, |level| entry.level <= level)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Page {
+    pub results: Vec<Entry>,
+    /// Continues the search, `None` if there are no more results.
+    pub cursor: Option<String>,
+}
+
+/// Adds a paste to the index, must only be called for public pastes.
+///
+/// The entry is only stored as metadata of its keys, listings return it without extra reads.
+pub async fn add(store: &dyn Store, entry: &Entry) -> Result<()> {
+    let metadata = serde_json::to_value(entry)?;
+    let keys = entry.keys();
+    for key in &keys {
+        store
+            .put(key, String::new(), Some(metadata.clone()))
+            .await?;
+    }
+    // Lookup by id, to be able to remove the paste again
+    store.put(&id_key(&entry.id), keys.join("\n"), None).await?;
+
+    Ok(())
+}
+
+/// The indexed entry of a paste, `None` if the paste is not indexed.
+pub async fn get(store: &dyn Store, id: &str) -> Result<Option<Entry>> {
+    let key = match store.get(&id_key(id)).await? {
+        Some(keys) => keys.lines().next().unwrap_or_default().to_owned(),
+        None => return Ok(None),
+    };
+
+    let page = store.list(&key, None, 1).await?;
+    Ok(page
+        .keys
+        .into_iter()
+        .find(|k| k.name == key)
+        .and_then(|k| serde_json::from_value(k.metadata?).ok()))
+}
+
+/// Removes a paste from the index, does nothing if the paste is not indexed.
+pub async fn remove(store: &dyn Store, id: &str) -> Result<()> {
+    let keys = match store.get(&id_key(id)).await? {
+        Some(keys) => keys,
+        None => return Ok(()),
+    };
+
+    for key in keys.lines() {
+        store.delete(key).await?;
+    }
+    store.delete(&id_key(id)).await?;
+
+    Ok(())
+}
+
+/// Searches the index, newest pastes first.
+///
+/// Only the index of the most selective filter is scanned, the remaining filters are applied
+/// while scanning, a page may contain less than `limit` results even if there are more.
+pub async fn search(
+    store: &dyn Store,
+    query: &Query,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<Page> {
+    let prefix = query.prefix();
+
+    let mut results = Vec::with_capacity(limit);
+    let mut cursor = cursor;
+    for _ in 0..consts::SEARCH_MAX_SCANS {
+        // Never list more keys than there is space left, so the cursor does not skip results
+        let remaining = (limit - results.len()) as u64;
+        let page = store.list(&prefix, cursor.take(), remaining).await?;
+
+        results.extend(
+            page.keys
+                .into_iter()
+                .filter_map(|key| serde_json::from_value::<Entry>(key.metadata?).ok())
+                .filter(|entry| query.matches(entry)),
+        );
+
+        cursor = page.cursor;
+        if cursor.is_none() || results.len() >= limit {
+            break;
+        }
+    }
+
+    Ok(Page { results, cursor })
+}
+
+/// Lists all indexed pastes, newest first.
+pub async fn all(
+    store: &dyn Store,
+    cursor: Option<String>,
+    limit: u64,
+) -> Result<(Vec<Entry>, Option<String>)> {
+    list(store, &format!("{}:all:", PREFIX), cursor, limit).await
+}
+
+/// Period of pastes created at the time, newer pastes have smaller periods.
+///
+/// Keys of a period share a prefix, a paste never moves to another period.
+pub fn period_of(created: u64) -> u64 {
+    (u64::MAX - created) / 10u64.pow(20 - PERIOD_DIGITS as u32)
+}
+
+/// Lists the indexed pastes of a period, newest first, see [`period_of`].
+pub async fn all_in_period(
+    store: &dyn Store,
+    period: u64,
+    cursor: Option<String>,
+    limit: u64,
+) -> Result<(Vec<Entry>, Option<String>)> {
+    let prefix = format!("{}:all:{:0width$}", PREFIX, period, width = PERIOD_DIGITS);
+    list(store, &prefix, cursor, limit).await
+}
+
+async fn list(
+    store: &dyn Store,
+    prefix: &str,
+    cursor: Option<String>,
+    limit: u64,
+) -> Result<(Vec<Entry>, Option<String>)> {
+    let page = store.list(prefix, cursor, limit).await?;
+
+    let entries = page
+        .keys
+        .into_iter()
+        .filter_map(|key| serde_json::from_value(key.metadata?).ok())
+        .collect();
+
+    Ok((entries, page.cursor))
+}
+
+/// Lists the ids of all indexed pastes, ordered by id.
+pub async fn ids(
+    store: &dyn Store,
+    cursor: Option<String>,
+    limit: u64,
+) -> Result<(Vec<String>, Option<String>)> {
+    let prefix = id_key("");
+    let page = store.list(&prefix, cursor, limit).await?;
+
+    let ids = page
+        .keys
+        .into_iter()
+        .filter_map(|key| key.name.strip_prefix(&prefix).map(ToOwned::to_owned))
+        .collect();
+
+    Ok((ids, page.cursor))
+}
+
+fn id_key(id: &str) -> String {
+    format!("{}:id:{}", PREFIX, id)
+}
+
+fn attribute_prefix(name: &str, value: &str) -> String {
+    format!("{}:{}:{}:", PREFIX, name, slug(value))
+}
+
+/// Levels are zero padded, the prefix of a level never matches another level.
+fn level(level: u8) -> String {
+    format!("{:03}", level)
+}
+
+fn slug(s: &str) -> String {
+    s.trim()
+        .chars()
+        .map(|c| match c.is_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '-',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{future::block_on, store::Memory};
+
+    fn entry(id: &str, created: u64, class: &str, skill: &str, keystones: &[&str]) -> Entry {
+        Entry {
+            id: id.to_owned(),
+            title: format!("{} {}", class, skill),
+            ascendancy_or_class: class.to_owned(),
+            main_skill: Some(skill.to_owned()),
+            keystones: keystones.iter().map(|k| k.to_string()).collect(),
+            level: 90,
+            created,
+        }
+    }
+
+    fn query(pairs: &[(&'static str, &'static str)]) -> Query {
+        Query::from_pairs(
+            pairs
+                .iter()
+                .map(|(k, v)| (Cow::Borrowed(*k), Cow::Borrowed(*v))),
+        )
+        .unwrap()
+    }
+
+    fn ids(page: &Page) -> Vec<&str> {
+        page.results.iter().map(|e| e.id.as_str()).collect()
+    }
+
+    fn store() -> Memory {
+        let store = Memory::default();
+        let entries = [
+            entry("a", 1, "Slayer", "Cyclone", &[]),
+            entry("b", 2, "Occultist", "Vortex", &["Chaos Inoculation"]),
+            entry("c", 3, "Slayer", "Cyclone", &["Mind Over Matter"]),
+            entry("d", 4, "Hierophant", "Arc", &["Mind Over Matter"]),
+        ];
+        for entry in &entries {
+            block_on(add(&store, entry)).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn test_query_from_pairs() {
+        let q = query(&[
+            ("ascendancy", "Slayer"),
+            ("keystone", "mind over matter"),
+            ("min_level", "90"),
+            ("skill", " "),
+        ]);
+        assert_eq!(
+            q,
+            Query {
+                ascendancy: Some("Slayer".to_owned()),
+                keystones: vec!["Mind Over Matter"],
+                min_level: Some(90),
+                ..Default::default()
+            }
+        );
+
+        let pairs = [(Cow::Borrowed("keystone"), Cow::Borrowed("Iron Reflexes"))];
+        assert!(Query::from_pairs(pairs).is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let store = store();
+
+        let page = block_on(search(&store, &Query::default(), None, 10)).unwrap();
+        assert_eq!(ids(&page), vec!["d", "c", "b", "a"]);
+        assert_eq!(page.cursor, None);
+
+        let q = query(&[("class", "slayer")]);
+        let page = block_on(search(&store, &q, None, 10)).unwrap();
+        assert_eq!(ids(&page), vec!["c", "a"]);
+
+        let q = query(&[("skill", "Cyclone"), ("keystone", "Mind Over Matter")]);
+        let page = block_on(search(&store, &q, None, 10)).unwrap();
+        assert_eq!(ids(&page), vec!["c"]);
+
+        let q = query(&[("max_level", "89")]);
+        let page = block_on(search(&store, &q, None, 10)).unwrap();
+        assert_eq!(ids(&page), Vec::<&str>::new());
+
+        let q = query(&[("min_level", "90"), ("max_level", "90")]);
+        let page = block_on(search(&store, &q, None, 10)).unwrap();
+        assert_eq!(ids(&page), vec!["d", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_search_rare() {
+        let store = store();
+        let limit = 2;
+        for created in 10..10 + (limit * consts::SEARCH_MAX_SCANS) as u64 {
+            let entry = entry(&created.to_string(), created, "Slayer", "Cyclone", &[]);
+            block_on(add(&store, &entry)).unwrap();
+        }
+
+        // Only the index of the ascendancy is scanned, newer pastes do not hide the result
+        let q = query(&[("ascendancy", "Occultist")]);
+        let page = block_on(search(&store, &q, None, limit)).unwrap();
+        assert_eq!(ids(&page), vec!["b"]);
+        assert_eq!(page.cursor, None);
+
+        let q = query(&[("keystone", "Chaos Inoculation")]);
+        let page = block_on(search(&store, &q, None, limit)).unwrap();
+        assert_eq!(ids(&page), vec!["b"]);
+    }
+
+    #[test]
+    fn test_search_pagination() {
+        let store = store();
+        let q = query(&[("keystone", "Mind Over Matter"), ("class", "Slayer")]);
+
+        let page = block_on(search(&store, &Query::default(), None, 3)).unwrap();
+        assert_eq!(ids(&page), vec!["d", "c", "b"]);
+        let page = block_on(search(&store, &Query::default(), page.cursor, 3)).unwrap();
+        assert_eq!(ids(&page), vec!["a"]);
+        assert_eq!(page.cursor, None);
+
+        let page = block_on(search(&store, &q, None, 1)).unwrap();
+        assert_eq!(ids(&page), vec!["c"]);
+    }
+
+    #[test]
+    fn test_all_in_period() {
+        const PERIOD: u64 = 100_000_000;
+
+        let period = period_of(consts::SITEMAP_EPOCH);
+        let newest = u64::MAX - period * PERIOD;
+        let oldest = newest - PERIOD + 1;
+        assert_eq!(period_of(newest), period);
+        assert_eq!(period_of(oldest), period);
+        assert_eq!(period_of(newest + 1), period - 1);
+        assert_eq!(period_of(oldest - 1), period + 1);
+
+        let store = Memory::default();
+        let created = [oldest - 1, oldest, newest, newest + 1];
+        for (id, created) in ["a", "b", "c", "d"].iter().zip(created) {
+            block_on(add(&store, &entry(id, created, "Slayer", "Cyclone", &[]))).unwrap();
+        }
+
+        let (entries, cursor) = block_on(all_in_period(&store, period, None, 10)).unwrap();
+        let ids = entries.iter().map(|e| e.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["c", "b"]);
+        assert_eq!(cursor, None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let store = store();
+        // Per paste the id, all pastes, ascendancy, skill, level and every keystone
+        assert_eq!(store.keys().len(), 4 * 5 + 3);
+
+        block_on(remove(&store, "c")).unwrap();
+        block_on(remove(&store, "x")).unwrap();
+
+        assert!(store.keys().iter().all(|key| !key.ends_with(":c")));
+        assert!(store.keys().iter().any(|key| key.ends_with(":a")));
+        assert_eq!(store.keys().len(), 3 * 5 + 2);
+    }
+
+    #[test]
+    fn test_get() {
+        let store = store();
+
+        let entry = block_on(get(&store, "b")).unwrap().unwrap();
+        assert_eq!(entry.id, "b");
+        assert_eq!(entry.ascendancy_or_class, "Occultist");
+        assert_eq!(block_on(get(&store, "x")).unwrap(), None);
+    }
+}
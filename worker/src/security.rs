@@ -0,0 +1,216 @@
+//! Security headers for all responses.
+//!
+//! Rendered pages get a strict Content-Security-Policy, inline scripts are only allowed with
+//! the hash of the page template. Everything else is not supposed to be rendered by a
+//! browser and forbids loading anything at all.
+//!
+//! Unlike nonces, hashes are the same for every response, rendered pages can be cached.
+
+use crate::{consts, utils::ResponseExt, Result};
+use worker::Env;
+
+const CONTENT_SECURITY_POLICY: &str = "Content-Security-Policy";
+
+/// Features the app never uses.
+const PERMISSIONS_POLICY: &str =
+    "accelerometer=(), camera=(), geolocation=(), gyroscope=(), microphone=(), payment=(), usb=()";
+
+/// Configuration of the security headers.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    /// Sources allowed to frame pages.
+    pub frame_ancestors: String,
+    /// Where browsers report violations of the Content-Security-Policy.
+    pub report_uri: Option<String>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            frame_ancestors: "'none'".to_owned(),
+            report_uri: None,
+        }
+    }
+}
+
+impl Policy {
+    /// Reads the policy from the environment, unset variables keep their defaults.
+    pub fn from_env(env: &Env) -> Self {
+        let var = |name| env.var(name).ok().map(|var| var.to_string());
+
+        let default = Self::default();
+        Self {
+            frame_ancestors: var(consts::ENV_CSP_FRAME_ANCESTORS)
+                .unwrap_or(default.frame_ancestors),
+            report_uri: var(consts::ENV_CSP_REPORT_URI).filter(|uri| !uri.is_empty()),
+        }
+    }
+
+    /// Policy of a rendered page, `script_hashes` allow the inline scripts of the page.
+    ///
+    /// Inline styles are allowed, the app sets them e.g. for colored PoB text.
+    pub fn page(&self, script_hashes: &[String]) -> String {
+        let mut script_src = String::from("'self'");
+        for hash in script_hashes {
+            script_src.push(' ');
+            script_src.push_str(hash);
+        }
+
+        let mut policy = format!(
+            "default-src 'self'; script-src {} 'wasm-unsafe-eval'; \
+             style-src 'self' 'unsafe-inline'; img-src 'self' data:; object-src 'none'; \
+             base-uri 'self'; form-action 'self'; frame-ancestors {}",
+            script_src, self.frame_ancestors
+        );
+        if let Some(report_uri) = &self.report_uri {
+            policy.push_str("; report-uri ");
+            policy.push_str(report_uri);
+        }
+        policy
+    }
+
+    /// Policy of all other responses, e.g. API responses or raw pastes.
+    pub fn resource(&self) -> String {
+        format!(
+            "default-src 'none'; frame-ancestors {}",
+            self.frame_ancestors
+        )
+    }
+}
+
+/// Hashes of all inline scripts of the page template, in the format of a CSP source.
+///
+/// Must be computed before user content is inserted into the template.
+pub fn script_hashes(template: &str) -> Vec<String> {
+    let mut hashes = Vec::new();
+
+    let mut rest = template;
+    while let Some(start) = rest.find("<script") {
+        let (tag, content) = match rest[start..].split_once('>') {
+            Some(script) => script,
+            None => break,
+        };
+        let end = match content.find("</script>") {
+            Some(end) => end,
+            None => break,
+        };
+
+        // External scripts are covered by 'self'
+        if !tag.contains(" src=") {
+            let hash = hmac_sha256::Hash::hash(content[..end].as_bytes());
+            hashes.push(format!("'sha256-{}'", base64::encode(hash)));
+        }
+        rest = &content[end..];
+    }
+
+    hashes
+}
+
+/// Adds the security headers to a response, a policy set by the response itself is kept.
+pub fn apply<R: ResponseExt>(response: R, policy: &Policy) -> Result<R> {
+    let response = match response.has_header(CONTENT_SECURITY_POLICY) {
+        true => response,
+        false => response.with_header(CONTENT_SECURITY_POLICY, &policy.resource())?,
+    };
+
+    response
+        .with_header("X-Content-Type-Options", "nosniff")?
+        .with_header("Referrer-Policy", "strict-origin-when-cross-origin")?
+        .with_header("Permissions-Policy", PERMISSIONS_POLICY)
+}
+
+/// Sets the policy of a rendered page, see [`Policy::page`].
+pub fn with_page_policy<R: ResponseExt>(
+    response: R,
+    policy: &Policy,
+    script_hashes: &[String],
+) -> Result<R> {
+    response.with_header(CONTENT_SECURITY_POLICY, &policy.page(script_hashes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Response;
+
+    fn headers(response: Response) -> Response {
+        apply(response, &Policy::default()).unwrap()
+    }
+
+    fn assert_common(response: &Response) {
+        assert_eq!(response.header("X-Content-Type-Options"), Some("nosniff"));
+        assert_eq!(
+            response.header("Referrer-Policy"),
+            Some("strict-origin-when-cross-origin")
+        );
+        assert_eq!(
+            response.header("Permissions-Policy"),
+            Some(PERMISSIONS_POLICY)
+        );
+    }
+
+    #[test]
+    fn test_page() {
+        let policy = Policy::default();
+        let response = Response::from_html("<html></html>").unwrap();
+        let hashes = ["'sha256-abc='".to_owned()];
+        let response = with_page_policy(response, &policy, &hashes).unwrap();
+        let response = headers(response);
+
+        assert_common(&response);
+        let csp = response.header(CONTENT_SECURITY_POLICY).unwrap();
+        assert!(csp.contains("script-src 'self' 'sha256-abc=' 'wasm-unsafe-eval';"));
+        assert!(!csp.contains("unsafe-hashes"));
+        assert!(policy
+            .page(&[])
+            .contains("script-src 'self' 'wasm-unsafe-eval';"));
+        assert!(csp.contains("object-src 'none'"));
+        assert!(csp.ends_with("frame-ancestors 'none'"));
+    }
+
+    #[test]
+    fn test_resources() {
+        let responses = [
+            Response::from_json(&"abc").unwrap(),
+            Response::ok("eNrt"),
+            Response::ok("")
+                .with_content_type("text/javascript")
+                .unwrap(),
+            Response::from_bytes(vec![0x89]),
+        ];
+
+        for response in responses {
+            let response = headers(response);
+            assert_common(&response);
+            assert_eq!(
+                response.header(CONTENT_SECURITY_POLICY),
+                Some("default-src 'none'; frame-ancestors 'none'")
+            );
+        }
+    }
+
+    #[test]
+    fn test_configured() {
+        let policy = Policy {
+            frame_ancestors: "https://example.com".to_owned(),
+            report_uri: Some("/csp".to_owned()),
+        };
+
+        let csp = policy.page(&[]);
+        assert!(csp.contains("frame-ancestors https://example.com;"));
+        assert!(csp.ends_with("; report-uri /csp"));
+        assert!(policy
+            .resource()
+            .ends_with("frame-ancestors https://example.com"));
+    }
+
+    #[test]
+    fn test_script_hashes() {
+        let template = r#"<head><script type="module">init()</script><script src="/index.js"></script></head><!-- %app% --><script>"#;
+        assert_eq!(
+            script_hashes(template),
+            vec!["'sha256-w4ujnOpjBoH2vcasx+reJRUwYivG8Q3afx/XevGJod8='".to_owned()]
+        );
+        assert!(script_hashes("<html></html>").is_empty());
+    }
+}
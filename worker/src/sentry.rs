@@ -1,24 +1,89 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::panic::PanicInfo;
+use std::rc::Rc;
 
 use git_version::git_version;
 use serde::Serialize;
-use wasm_bindgen::JsValue;
-use worker::{Context, Env, Fetch, Headers, Method, RequestInit};
+use wasm_bindgen::{JsCast, JsValue};
+use worker::{Context, Env, Fetch, Headers, Method, RequestInit, Url};
 
-use crate::{consts, Error, Result};
+use crate::{consts, future, storage, Error, Result};
+
+/// Replaces secrets before they are sent to Sentry.
+const REDACTED: &str = "[redacted]";
+/// Request headers which carry credentials, e.g. API keys or the admin token.
+const SECRET_HEADERS: &[&str] = &["authorization", "cookie"];
+
+const MAX_BREADCRUMBS: usize = 50;
+
+thread_local! {
+    /// Scope of the request currently being polled, see [`Scope::run`].
+    static ACTIVE: RefCell<Option<Rc<Scope>>> = RefCell::new(None);
+}
+
+/// State Sentry reports of a single request or scheduled run.
+///
+/// The log macros and the panic hook cannot be handed the scope, they use the scope of the
+/// future currently being polled, see [`Scope::run`]. Everything else takes it explicitly.
+#[derive(Default)]
+pub struct Scope {
+    breadcrumbs: RefCell<VecDeque<Breadcrumb>>,
+    /// Where panics are reported to, see [`panic_hook`].
+    panic: Option<(Sentry, RequestInfo)>,
+}
+
+impl Scope {
+    pub fn for_request(sentry: Option<Sentry>, req: &worker::Request, request_id: &str) -> Self {
+        let panic = sentry.and_then(|sentry| {
+            RequestInfo::from_request(req, request_id)
+                .ok()
+                .map(|info| (sentry, info))
+        });
+
+        Self {
+            panic,
+            ..Default::default()
+        }
+    }
+
+    /// Runs `future` with this scope receiving its breadcrumbs and panics.
+    pub fn run<'a, T: 'a>(
+        self: &Rc<Self>,
+        future: impl Future<Output = T> + 'a,
+    ) -> impl Future<Output = T> + 'a {
+        future::scoped(&ACTIVE, Rc::clone(self), future)
+    }
+
+    fn add_breadcrumb(&self, breadcrumb: Breadcrumb) {
+        let mut breadcrumbs = self.breadcrumbs.borrow_mut();
+        if breadcrumbs.len() >= MAX_BREADCRUMBS {
+            breadcrumbs.pop_front();
+        }
+        breadcrumbs.push_back(breadcrumb);
+    }
+}
 
 #[derive(Serialize)]
 struct Store<'a> {
     logger: &'a str,
     platform: &'a str,
     level: &'a str,
-    // extra: Option<HashMap<&'a str, >,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extra: Option<&'a HashMap<&'a str, &'a str>>,
     exception: &'a Exception<'a>,
-    request: &'a Request<'a>,
-    user: &'a User<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request: Option<&'a Request<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<&'a User<'a>>,
     server_name: &'a str,
     release: &'a str,
+    environment: &'a str,
     transaction: &'a str,
+    tags: &'a HashMap<&'a str, &'a str>,
+    fingerprint: &'a [&'a str],
+    breadcrumbs: &'a Breadcrumbs,
 }
 
 #[derive(Serialize)]
@@ -30,14 +95,31 @@ struct Exception<'a> {
 struct ExceptionValue<'a> {
     r#type: &'a str,
     value: &'a str,
-    // stacktrace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stacktrace: Option<Stacktrace>,
+}
+
+#[derive(Serialize)]
+struct Stacktrace {
+    frames: Vec<Frame>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Frame {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<String>,
+    filename: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lineno: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    colno: Option<u32>,
 }
 
 #[derive(Serialize)]
 struct Request<'a> {
     url: &'a str,
     method: &'a str,
-    headers: HashMap<String, String>,
+    headers: &'a HashMap<String, String>,
     data: Option<&'a str>,
 }
 
@@ -47,72 +129,202 @@ struct User<'a> {
     country: &'a str,
 }
 
+#[derive(Serialize)]
+struct Breadcrumbs {
+    values: Vec<Breadcrumb>,
+}
+
+#[derive(Clone, Serialize)]
+struct Breadcrumb {
+    timestamp: f64,
+    category: String,
+    level: &'static str,
+    message: String,
+}
+
+/// Request information collected upfront, so it is still available in a panic handler.
+pub struct RequestInfo {
+    url: String,
+    method: String,
+    headers: HashMap<String, String>,
+    ip_address: String,
+    country: String,
+    server_name: String,
+    transaction: &'static str,
+    request_id: String,
+}
+
+impl RequestInfo {
+    pub fn from_request(req: &worker::Request, request_id: &str) -> Result<Self> {
+        let headers = req.headers();
+        Ok(Self {
+            url: redact_url(&req.inner().url()),
+            method: req.inner().method(),
+            headers: redact_headers(headers.into_iter()),
+            ip_address: headers.get("cf-connecting-ip")?.unwrap_or_default(),
+            country: headers.get("cf-ipcountry")?.unwrap_or_default(),
+            server_name: req.url()?.host_str().unwrap_or("").to_owned(),
+            transaction: crate::transaction(req),
+            request_id: request_id.to_owned(),
+        })
+    }
+}
+
+/// The request headers with the values of credentials replaced.
+fn redact_headers(headers: impl Iterator<Item = (String, String)>) -> HashMap<String, String> {
+    headers
+        .map(|(name, value)| {
+            match SECRET_HEADERS
+                .iter()
+                .any(|secret| name.eq_ignore_ascii_case(secret))
+            {
+                true => (name, REDACTED.to_owned()),
+                false => (name, value),
+            }
+        })
+        .collect()
+}
+
+/// The url without the view key of private pastes.
+fn redact_url(url: &str) -> String {
+    let mut url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return url.to_owned(),
+    };
+    if !url.query_pairs().any(|(name, _)| name == "key") {
+        return url.into();
+    }
+
+    let query = url
+        .query_pairs()
+        .map(|(name, value)| match name == "key" {
+            true => (name.into_owned(), REDACTED.to_owned()),
+            false => (name.into_owned(), value.into_owned()),
+        })
+        .collect::<Vec<_>>();
+    url.query_pairs_mut().clear().extend_pairs(query);
+
+    url.into()
+}
+
+#[derive(Clone)]
 pub struct Sentry {
     token: String,
     store_url: String,
+    environment: String,
 }
 
 impl Sentry {
     pub fn from_env(env: &Env) -> Option<Self> {
         let project = env.var(consts::ENV_SENTRY_PROJECT).ok()?.to_string();
         let store_url = format!("https://sentry.io/api/{}/store/", project);
+        let environment = env
+            .var(consts::ENV_SENTRY_ENVIRONMENT)
+            .map(|env| env.to_string())
+            .unwrap_or_else(|_| "production".to_owned());
         Some(Self {
             token: env.var(consts::ENV_SENTRY_TOKEN).ok()?.to_string(),
             store_url,
+            environment,
         })
     }
 
-    pub fn capture_err(&self, err: &Error, req: &worker::Request, ctx: &Context) {
-        if let Err(err) = self.do_capture_err(err, req, ctx) {
+    pub fn capture_err(
+        &self,
+        scope: &Scope,
+        err: &Error,
+        req: &worker::Request,
+        request_id: &str,
+        ctx: &Context,
+    ) {
+        if let Err(err) = self.do_capture_err(scope, err, req, request_id, ctx) {
             log::warn!("failed to caputre error with sentry: {:?}", err);
         }
     }
 
-    fn do_capture_err(&self, err: &Error, req: &worker::Request, ctx: &Context) -> Result<()> {
-        let mut headers = Headers::new();
-        headers.set("Content-Type", "application/json")?;
-        headers.set("User-Agent", "pobb.bin/1.0")?;
-        headers.set(
-            "X-Sentry-Auth",
-            &format!(
-                "Sentry sentry_version=7, sentry_client=pobb.in/1.0, sentry_key={}",
-                self.token,
-            ),
+    fn do_capture_err(
+        &self,
+        scope: &Scope,
+        err: &Error,
+        req: &worker::Request,
+        request_id: &str,
+        ctx: &Context,
+    ) -> Result<()> {
+        let info = RequestInfo::from_request(req, request_id)?;
+        let exception = ExceptionValue {
+            r#type: err.name(),
+            value: &err.to_string(),
+            stacktrace: None,
+        };
+        let body = self.store(
+            scope,
+            err.level(),
+            exception,
+            Some(&info),
+            info.transaction,
+            err.payload(),
         )?;
+        let request = self.request(body)?;
 
-        let url = req.url()?;
+        ctx.wait_until(async move {
+            let r = Fetch::Request(request).send().await;
+            if let Err(err) = r {
+                log::warn!("failed to caputre error with sentry: {:?}", err);
+            } else {
+                log::info!("successfully captured error");
+            }
+        });
 
-        let body = JsValue::from_str(&serde_json::to_string(&Store {
-            logger: "worker",
-            platform: "other",
-            level: err.level(),
-            exception: &Exception {
-                values: &[ExceptionValue {
-                    r#type: err.name(),
-                    value: &err.to_string(),
-                }],
-            },
-            request: &Request {
-                url: &req.inner().url(),
-                method: &req.inner().method(),
-                headers: req.headers().into_iter().collect(),
-                data: err.payload(),
-            },
-            user: &User {
-                ip_address: &req
-                    .headers()
-                    .get("cf-connecting-ip")?
-                    .unwrap_or_else(String::new),
-                country: &req
-                    .headers()
-                    .get("cf-ipcountry")?
-                    .unwrap_or_else(String::new),
-            },
-            release: git_version!(),
-            server_name: url.host_str().unwrap_or(""),
-            // TODO: use route information here
-            transaction: "",
-        })?);
+        Ok(())
+    }
+
+    /// Captures an error of a scheduled job, there is no request associated with it.
+    ///
+    /// `paste` is the paste the job failed for, if it failed for a single paste.
+    pub async fn capture_job_err(
+        &self,
+        scope: &Scope,
+        job: &str,
+        paste: Option<&str>,
+        err: &Error,
+    ) {
+        let value = match paste {
+            Some(paste) => format!("paste '{}': {}", paste, err),
+            None => err.to_string(),
+        };
+        let exception = ExceptionValue {
+            r#type: err.name(),
+            value: &value,
+            stacktrace: None,
+        };
+        let transaction = format!("job:{}", job);
+
+        let result = self
+            .store(
+                scope,
+                err.level(),
+                exception,
+                None,
+                &transaction,
+                err.payload(),
+            )
+            .and_then(|body| self.request(body));
+        let result = match result {
+            Ok(request) => Fetch::Request(request).send().await.map_err(Error::from),
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(_) => log::info!("successfully captured error"),
+            Err(err) => log::warn!("failed to caputre error with sentry: {:?}", err),
+        }
+    }
+
+    fn request(&self, body: JsValue) -> Result<worker::Request> {
+        let mut headers = Headers::new();
+        for (name, value) in self.headers() {
+            headers.set(name, &value)?;
+        }
 
         let request = worker::Request::new_with_init(
             &self.store_url,
@@ -124,15 +336,291 @@ impl Sentry {
             },
         )?;
 
-        ctx.wait_until(async move {
-            let r = Fetch::Request(request).send().await;
-            if let Err(err) = r {
-                log::warn!("failed to caputre error with sentry: {:?}", err);
-            } else {
-                log::info!("successfully captured error");
-            }
-        });
+        Ok(request)
+    }
+
+    fn capture_panic(&self, scope: &Scope, panic: &PanicInfo, info: &RequestInfo) -> Result<()> {
+        let message = panic
+            .payload()
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.payload().downcast_ref::<String>().map(|s| s.as_str()))
+            .unwrap_or("Box<Any>");
+
+        let mut frames = js_stacktrace();
+        if let Some(location) = panic.location() {
+            frames.push(Frame {
+                function: None,
+                filename: location.file().to_owned(),
+                lineno: Some(location.line()),
+                colno: Some(location.column()),
+            });
+        }
+
+        let exception = ExceptionValue {
+            r#type: "Panic",
+            value: message,
+            stacktrace: Some(Stacktrace { frames }),
+        };
+        let body = self.store(
+            scope,
+            "fatal",
+            exception,
+            Some(info),
+            info.transaction,
+            None,
+        )?;
+
+        let headers = js_sys::Object::new();
+        for (name, value) in self.headers() {
+            js_sys::Reflect::set(&headers, &name.into(), &value.into())?;
+        }
+
+        let mut init = web_sys::RequestInit::new();
+        init.method("POST").headers(&headers).body(Some(&body));
+
+        // The instance is about to abort, there is no chance to await anything here,
+        // just start the request and hope for the best.
+        let worker: web_sys::WorkerGlobalScope = js_sys::global().unchecked_into();
+        let _ = worker.fetch_with_str_and_init(&self.store_url, &init);
 
         Ok(())
     }
+
+    fn headers(&self) -> [(&'static str, String); 3] {
+        [
+            ("Content-Type", "application/json".to_owned()),
+            ("User-Agent", "pobb.bin/1.0".to_owned()),
+            (
+                "X-Sentry-Auth",
+                format!(
+                    "Sentry sentry_version=7, sentry_client=pobb.in/1.0, sentry_key={}",
+                    self.token,
+                ),
+            ),
+        ]
+    }
+
+    fn store(
+        &self,
+        scope: &Scope,
+        level: &str,
+        exception: ExceptionValue,
+        info: Option<&RequestInfo>,
+        transaction: &str,
+        data: Option<&str>,
+    ) -> Result<JsValue> {
+        let mut tags = HashMap::new();
+        tags.insert("storage", storage::BACKEND);
+        tags.insert("release", git_version!());
+        if let Some(info) = info {
+            tags.insert("request_id", &info.request_id);
+        }
+
+        let breadcrumbs = Breadcrumbs {
+            values: scope.breadcrumbs.borrow().iter().cloned().collect(),
+        };
+
+        // Without a request, e.g. for scheduled jobs, the data is attached as extra
+        let request = info.map(|info| Request {
+            url: &info.url,
+            method: &info.method,
+            headers: &info.headers,
+            data,
+        });
+        let user = info.map(|info| User {
+            ip_address: &info.ip_address,
+            country: &info.country,
+        });
+        let extra = match info {
+            Some(_) => None,
+            None => data.map(|data| HashMap::from([("data", data)])),
+        };
+
+        let body = serde_json::to_string(&Store {
+            logger: "worker",
+            platform: "other",
+            level,
+            fingerprint: &[exception.r#type],
+            exception: &Exception {
+                values: &[exception],
+            },
+            extra: extra.as_ref(),
+            request: request.as_ref(),
+            user: user.as_ref(),
+            release: git_version!(),
+            environment: &self.environment,
+            server_name: info
+                .map(|info| info.server_name.as_str())
+                .unwrap_or_default(),
+            transaction,
+            tags: &tags,
+            breadcrumbs: &breadcrumbs,
+        })?;
+
+        Ok(JsValue::from_str(&body))
+    }
+}
+
+/// Panic hook reporting the panic to Sentry, for the request of the active [`Scope`].
+pub fn panic_hook(panic: &PanicInfo) {
+    let scope = match ACTIVE.with(|active| active.borrow().clone()) {
+        Some(scope) => scope,
+        None => return,
+    };
+
+    if let Some((sentry, info)) = &scope.panic {
+        if let Err(err) = sentry.capture_panic(&scope, panic, info) {
+            log::warn!("failed to capture panic with sentry: {:?}", err);
+        }
+    }
+}
+
+/// Records a log message as a breadcrumb of the active [`Scope`].
+///
+/// Messages logged outside of a scope, e.g. in `wait_until` tasks, are dropped.
+pub fn add_breadcrumb(record: &log::Record) {
+    let scope = match ACTIVE.with(|active| active.borrow().clone()) {
+        Some(scope) => scope,
+        None => return,
+    };
+
+    let breadcrumb = Breadcrumb {
+        timestamp: worker::Date::now().as_millis() as f64 / 1000.0,
+        category: record.target().to_owned(),
+        level: match record.level() {
+            log::Level::Error => "error",
+            log::Level::Warn => "warning",
+            log::Level::Info => "info",
+            log::Level::Debug | log::Level::Trace => "debug",
+        },
+        message: record.args().to_string(),
+    };
+
+    scope.add_breadcrumb(breadcrumb);
+}
+
+/// Logger which only records breadcrumbs.
+pub struct BreadcrumbLogger;
+
+impl log::Log for BreadcrumbLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Debug
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            add_breadcrumb(record);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn js_stacktrace() -> Vec<Frame> {
+    let error = js_sys::Error::new("");
+    js_sys::Reflect::get(&error, &"stack".into())
+        .ok()
+        .and_then(|stack| stack.as_string())
+        .map(|stack| parse_stacktrace(&stack))
+        .unwrap_or_default()
+}
+
+/// Parses a V8 stacktrace into Sentry frames, the oldest frame first.
+fn parse_stacktrace(stack: &str) -> Vec<Frame> {
+    let mut frames = stack
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("at "))
+        .map(|line| {
+            let (function, location) = match line.strip_suffix(')') {
+                Some(line) => match line.split_once(" (") {
+                    Some((function, location)) => (Some(function.to_owned()), location),
+                    None => (None, line),
+                },
+                None => (None, line),
+            };
+
+            let mut parts = location.rsplitn(3, ':');
+            let (colno, lineno) = (parts.next(), parts.next());
+            match (parts.next(), lineno.and_then(|l| l.parse().ok()), colno) {
+                (Some(filename), Some(lineno), Some(colno)) => Frame {
+                    function,
+                    filename: filename.to_owned(),
+                    lineno: Some(lineno),
+                    colno: colno.parse().ok(),
+                },
+                _ => Frame {
+                    function,
+                    filename: location.to_owned(),
+                    lineno: None,
+                    colno: None,
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    frames.reverse();
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_headers() {
+        let headers = [
+            ("authorization", "Bearer api-key"),
+            ("Cookie", "session=1"),
+            ("user-agent", "Path of Building"),
+        ];
+        let headers = redact_headers(
+            headers
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value.to_owned())),
+        );
+
+        assert_eq!(headers["authorization"], REDACTED);
+        assert_eq!(headers["Cookie"], REDACTED);
+        assert_eq!(headers["user-agent"], "Path of Building");
+    }
+
+    #[test]
+    fn test_redact_url() {
+        assert_eq!(redact_url("https://pobb.in/abc"), "https://pobb.in/abc");
+        assert_eq!(
+            redact_url("https://pobb.in/abc/raw?key=secret&v=1"),
+            "https://pobb.in/abc/raw?key=%5Bredacted%5D&v=1"
+        );
+    }
+
+    #[test]
+    fn test_parse_stacktrace() {
+        let stack = "Error\n    at foo (worker.js:10:5)\n    at wasm://wasm/0012:wasm-function[42]:0x1f\n    at worker.js:3:1";
+        let frames = parse_stacktrace(stack);
+
+        assert_eq!(
+            frames,
+            vec![
+                Frame {
+                    function: None,
+                    filename: "worker.js".to_owned(),
+                    lineno: Some(3),
+                    colno: Some(1),
+                },
+                Frame {
+                    function: None,
+                    filename: "wasm://wasm/0012:wasm-function[42]:0x1f".to_owned(),
+                    lineno: None,
+                    colno: None,
+                },
+                Frame {
+                    function: Some("foo".to_owned()),
+                    filename: "worker.js".to_owned(),
+                    lineno: Some(10),
+                    colno: Some(5),
+                },
+            ]
+        );
+    }
 }
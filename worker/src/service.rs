@@ -0,0 +1,126 @@
+//! Request handling shared by all platforms: the API, static assets and server side rendering.
+//!
+//! Platform specific routes, e.g. oEmbed and previews on Cloudflare, are handled before.
+
+use crate::{
+    api, assets, consts,
+    http::{Request, Response},
+    moderation,
+    platform::Platform,
+    recent, security, sitemap,
+    utils::{self, ResponseExt},
+    Error, Result,
+};
+
+pub async fn handle(platform: &dyn Platform, req: &Request) -> Result<Response> {
+    if let Some(response) = api::try_handle(platform, req).await? {
+        return Ok(response);
+    }
+
+    if let Some(response) = sitemap::try_handle(platform, req).await? {
+        return Ok(response);
+    }
+
+    if let Some(response) = assets::try_handle(platform.assets(), req).await? {
+        return Ok(response);
+    }
+
+    render(platform, req).await
+}
+
+/// Renders the app for the requested route.
+async fn render(platform: &dyn Platform, req: &Request) -> Result<Response> {
+    let route = app::Route::resolve(req.path());
+    let ttl = match route {
+        app::Route::Recent => consts::CACHE_RECENT,
+        _ => 3_600,
+    };
+    let ctx = build_context(platform, req, route)
+        .await?
+        .with_request_id(req.request_id());
+    let private = ctx
+        .get_paste()
+        .map(|paste| paste.metadata().private)
+        .unwrap_or(false);
+
+    let (app, rctx) = app::render_to_string(ctx);
+    let url = req.url();
+    let origin = url.origin().ascii_serialization();
+    let oembed = worker::Url::parse_with_params(
+        &format!("{}/oembed.json", origin),
+        &[("url", url.as_str())],
+    )
+    .map_err(|e| e.to_string())?;
+    let head = app::render_head(rctx.meta.unwrap_or_default(), &origin, oembed.into());
+
+    let index = platform
+        .assets()
+        .get("index.html")
+        .await?
+        .ok_or_else(|| Error::NotFound("asset", "index.html".to_owned()))?;
+    let index = String::from_utf8(index.data).map_err(|e| e.to_string())?;
+    let script_hashes = security::script_hashes(&index);
+    let index = index.replace("<!-- %head% -->", &head);
+    let index = index.replace("<!-- %app% -->", &app);
+
+    let response = Response::from_html(index)?.with_status(rctx.status_code);
+    let response = security::with_page_policy(response, platform.security(), &script_hashes)?;
+    // Server error pages show the id of the request, they must not be served to anyone else
+    match private || rctx.status_code >= 500 {
+        true => response.cache_private(),
+        false => response.cache_for(ttl),
+    }
+}
+
+async fn build_context(
+    platform: &dyn Platform,
+    req: &Request,
+    route: app::Route,
+) -> Result<app::Context> {
+    // TODO: refactor this context garbage, maybe make it into a trait?
+    let host = req.url().host_str().unwrap_or_default().to_owned();
+    use app::{Context, Route::*};
+    let ctx = match route {
+        Index => Context::index(host),
+        Recent => Context::recent(host, recent::list(platform.index()).await?),
+        Search => Context::search(host),
+        NotFound => Context::not_found(host),
+        Paste(name) => match utils::to_path(&name) {
+            Err(_) => Context::not_found(host),
+            Ok(_) => {
+                match moderation::ensure_available(platform.moderation(), &name).await {
+                    Ok(()) => (),
+                    Err(Error::Gone(..)) => return Ok(Context::gone(host)),
+                    Err(err) => return Err(err),
+                }
+
+                let paste = match platform.pastes().get(&name).await? {
+                    Some(paste) => {
+                        let key = req.query_param("key");
+                        match paste.verify_access(platform, &name, key.as_deref()).await {
+                            Ok(()) => Some(paste),
+                            Err(Error::NotFound(..)) => None,
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    None => None,
+                };
+
+                if let Some(paste) = paste {
+                    let content = String::from_utf8(paste.data).map_err(|e| e.to_string())?;
+                    let metadata = app::PasteMetadata {
+                        title: paste.metadata.title,
+                        description: paste.metadata.description,
+                        tags: paste.metadata.tags,
+                        private: paste.metadata.private,
+                    };
+                    Context::paste(host, name, content, metadata)
+                } else {
+                    Context::not_found(host)
+                }
+            }
+        },
+    };
+
+    Ok(ctx)
+}
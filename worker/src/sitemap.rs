@@ -0,0 +1,251 @@
+//! `sitemap.xml` and `robots.txt` for search engines.
+//!
+//! `/sitemap.xml` is a sitemap index with a page for every period of the search index, e.g.
+//! `/sitemap.xml?page=..`, newest first. Pastes never move to another period, cached pages
+//! stay valid while new pastes are added.
+
+use crate::{
+    consts,
+    http::{Method, Request, Response},
+    platform::Platform,
+    search,
+    store::Store,
+    utils::{self, html_escape, ResponseExt},
+    Result,
+};
+use std::fmt::Write;
+use worker::{Env, Url};
+
+/// Routes which are never crawled, raw exports and API responses are not meant for humans.
+const DISALLOW: &[&str] = &["/api/", "/pob/", "/*/raw$", "/oembed.json"];
+
+/// Configuration of the `robots.txt`.
+#[derive(Debug, Clone, Default)]
+pub struct Robots {
+    /// Paths excluded in addition to [`DISALLOW`], `/` excludes everything.
+    pub disallow: Vec<String>,
+}
+
+impl Robots {
+    /// Parses a comma separated list of paths to exclude.
+    pub fn parse(config: &str) -> Self {
+        Self {
+            disallow: config
+                .split(',')
+                .map(str::trim)
+                .filter(|path| !path.is_empty())
+                .map(ToOwned::to_owned)
+                .collect(),
+        }
+    }
+
+    pub fn from_env(env: &Env) -> Self {
+        match env.var(consts::ENV_ROBOTS_DISALLOW) {
+            Ok(config) => Self::parse(&config.to_string()),
+            Err(_) => Self::default(),
+        }
+    }
+}
+
+pub async fn try_handle(platform: &dyn Platform, req: &Request) -> Result<Option<Response>> {
+    if req.method() != Method::Get {
+        return Ok(None);
+    }
+
+    let host = req.url().host_str().unwrap_or_default();
+    match req.path() {
+        "/robots.txt" => Response::ok(robots(host, platform.robots()))
+            .with_content_type("text/plain; charset=utf-8")?
+            .cache_for(consts::CACHE_ROBOTS)
+            .map(Some),
+        "/sitemap.xml" => {
+            let sitemap = match req.query_param("page").and_then(|p| p.parse().ok()) {
+                Some(period) => {
+                    page(platform.index(), host, period, consts::SITEMAP_MAX_URLS).await?
+                }
+                None => index(platform.index(), host).await?,
+            };
+            Response::ok(sitemap)
+                .with_content_type("application/xml; charset=utf-8")?
+                .cache_for(consts::CACHE_SITEMAP)
+                .map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+pub fn is_sitemap_url(path: &str) -> bool {
+    path == "/sitemap.xml"
+}
+
+fn robots(host: &str, robots: &Robots) -> String {
+    let mut txt = String::from("User-agent: *\n");
+    for path in DISALLOW
+        .iter()
+        .copied()
+        .chain(robots.disallow.iter().map(String::as_str))
+    {
+        let _ = writeln!(txt, "Disallow: {}", path);
+    }
+    let _ = writeln!(txt, "\nSitemap: https://{}/sitemap.xml", host);
+
+    txt
+}
+
+/// The sitemap index, with a page for every period from the newest paste back to the
+/// [`consts::SITEMAP_EPOCH`].
+async fn index(store: &dyn Store, host: &str) -> Result<String> {
+    let newest = match search::all(store, None, 1).await?.0.first() {
+        Some(entry) => search::period_of(entry.created),
+        None => return Ok(urlset(host, &[])),
+    };
+    let oldest = search::period_of(consts::SITEMAP_EPOCH);
+
+    let mut periods = (newest..=oldest).collect::<Vec<_>>();
+    if periods.len() > consts::SITEMAP_MAX_URLS {
+        log::warn!(
+            "sitemap index truncated to {} of {} pages",
+            consts::SITEMAP_MAX_URLS,
+            periods.len()
+        );
+        periods.truncate(consts::SITEMAP_MAX_URLS);
+    }
+
+    sitemap_index(host, &periods)
+}
+
+/// The sitemap page of the period, with at most `max_urls` pastes.
+async fn page(store: &dyn Store, host: &str, period: u64, max_urls: usize) -> Result<String> {
+    let mut entries = Vec::new();
+    let mut cursor = None;
+    loop {
+        let limit = consts::SITEMAP_LIST_LIMIT;
+        let (page, next) = search::all_in_period(store, period, cursor, limit).await?;
+        entries.extend(page);
+
+        if entries.len() >= max_urls {
+            if next.is_some() || entries.len() > max_urls {
+                log::warn!("sitemap page {} truncated to {} urls", period, max_urls);
+            }
+            entries.truncate(max_urls);
+            break;
+        }
+        match next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+
+    Ok(urlset(host, &entries))
+}
+
+fn urlset(host: &str, entries: &[search::Entry]) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="utf-8"?><urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+    );
+    for entry in entries {
+        let _ = write!(
+            xml,
+            "<url><loc>https://{host}/{}</loc><lastmod>{}</lastmod></url>",
+            html_escape(&entry.id),
+            utils::rfc3339(entry.created),
+            host = html_escape(host),
+        );
+    }
+    xml.push_str("</urlset>");
+
+    xml
+}
+
+fn sitemap_index(host: &str, periods: &[u64]) -> Result<String> {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="utf-8"?><sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+    );
+    for period in periods {
+        let url = Url::parse_with_params(
+            &format!("https://{}/sitemap.xml", host),
+            &[("page", period.to_string())],
+        )
+        .map_err(|e| e.to_string())?;
+        let _ = write!(
+            xml,
+            "<sitemap><loc>{}</loc></sitemap>",
+            html_escape(url.as_str())
+        );
+    }
+    xml.push_str("</sitemapindex>");
+
+    Ok(xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{future::block_on, store::Memory};
+
+    fn entry(id: &str, created: u64) -> search::Entry {
+        search::Entry {
+            id: id.to_owned(),
+            title: "Build".to_owned(),
+            ascendancy_or_class: "Slayer".to_owned(),
+            main_skill: None,
+            keystones: Vec::new(),
+            level: 90,
+            created,
+        }
+    }
+
+    #[test]
+    fn test_sitemap() {
+        let store = Memory::default();
+        let xml = block_on(index(&store, "pobb.in")).unwrap();
+        assert!(xml.contains("<urlset") && !xml.contains("<url>"));
+
+        let epoch = consts::SITEMAP_EPOCH;
+        let period = search::period_of(epoch);
+        block_on(search::add(&store, &entry("a", epoch))).unwrap();
+        let xml = block_on(index(&store, "pobb.in")).unwrap();
+        assert!(xml.contains("<sitemapindex"));
+        assert_eq!(
+            xml.matches("<sitemap>").count(),
+            1,
+            "only the period of the epoch"
+        );
+        assert!(xml.contains(&format!(
+            "<loc>https://pobb.in/sitemap.xml?page={}</loc>",
+            period
+        )));
+
+        // Two days later, the first page is unaffected
+        let later = epoch + 2 * 24 * 3_600 * 1_000;
+        for (id, created) in [("b", epoch + 1), ("c", later)] {
+            block_on(search::add(&store, &entry(id, created))).unwrap();
+        }
+        let xml = block_on(index(&store, "pobb.in")).unwrap();
+        let pages = (search::period_of(later)..=period).count();
+        assert_eq!(xml.matches("<sitemap>").count(), pages);
+
+        let first = block_on(page(&store, "pobb.in", period, 10)).unwrap();
+        assert!(first.contains(
+            "<url><loc>https://pobb.in/a</loc><lastmod>2022-01-01T00:00:00Z</lastmod></url>"
+        ));
+        assert!(first.contains("https://pobb.in/b<") && !first.contains("https://pobb.in/c<"));
+
+        let newest = block_on(page(&store, "pobb.in", search::period_of(later), 10)).unwrap();
+        assert_eq!(newest.matches("<url>").count(), 1);
+
+        // Pages never list more than the maximum amount of urls
+        let truncated = block_on(page(&store, "pobb.in", period, 1)).unwrap();
+        assert_eq!(truncated.matches("<url>").count(), 1);
+        assert!(truncated.contains("https://pobb.in/b<"));
+    }
+
+    #[test]
+    fn test_robots() {
+        let txt = robots("pobb.in", &Robots::parse(" /search, "));
+        assert!(txt.starts_with("User-agent: *\nDisallow: /api/\n"));
+        assert!(txt.contains("Disallow: /*/raw$\n"));
+        assert!(txt.contains("Disallow: /search\n"));
+        assert!(txt.ends_with("\nSitemap: https://pobb.in/sitemap.xml\n"));
+    }
+}
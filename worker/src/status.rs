@@ -0,0 +1,312 @@
+//! Health of the service and its dependencies, for uptime monitors.
+//!
+//! `/api/v1/status` responds with `503` if any check failed, degraded checks still respond
+//! with `200`, monitors which care about them can look at the `state` in the body.
+//!
+//! The endpoint is public, checks only report generic messages and log the actual errors.
+
+use crate::{
+    assets::Assets, cache, consts, http::Response, jobs::Credentials, memo::Memo,
+    platform::Platform, store::Store, utils::ResponseExt, Error, Result,
+};
+use flate2::{write::ZlibEncoder, Compression};
+use pob::{PathOfBuilding, SerdePathOfBuilding};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Display, future::Future, io::Write, rc::Rc};
+
+thread_local! {
+    /// The last status of the isolate, see [`remembered`].
+    static LAST: Rc<Memo<Status>> = Rc::new(Memo::new(consts::STATUS_TTL, 1));
+}
+
+/// Key which is read to verify KV is reachable, it does not need to exist.
+const PROBE_KEY: &str = "status:probe";
+
+/// Credentials are refreshed by the hourly job, older credentials mean it failed twice.
+const CREDENTIALS_STALE: u64 = consts::JOB_CREDENTIALS_MAX_AGE + 2 * 3_600 * 1000;
+
+/// Build which is exported and parsed again, like an upload.
+const FIXTURE: &str = include_str!("../../pob/test/316_empty.xml");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Ok,
+    /// The service works, but needs attention.
+    Degraded,
+    /// Requests depending on the check are failing.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub state: State,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<&'static str>,
+    /// Time the check took in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl Check {
+    fn ok() -> Self {
+        Self::new(State::Ok, None)
+    }
+
+    fn degraded(message: &'static str) -> Self {
+        Self::new(State::Degraded, Some(message))
+    }
+
+    fn failed(message: &'static str) -> Self {
+        Self::new(State::Failed, Some(message))
+    }
+
+    /// A failed check, the error is only logged.
+    fn error(message: &'static str, err: impl Display) -> Self {
+        log::warn!("status check failed, {}: {}", message, err);
+        Self::failed(message)
+    }
+
+    fn new(state: State, message: Option<&'static str>) -> Self {
+        Self {
+            state,
+            message,
+            duration_ms: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    /// The worst state of all checks.
+    pub state: State,
+    pub version: &'static str,
+    pub storage: &'static str,
+    /// Age of the cached storage credentials in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credentials_age_ms: Option<u64>,
+    pub checks: BTreeMap<&'static str, Check>,
+}
+
+pub async fn handle(platform: &dyn Platform) -> Result<Response> {
+    let last = LAST.with(Rc::clone);
+    let status = remembered(&last, platform.now(), status(platform)).await;
+
+    let code = match status.state {
+        State::Failed => 503,
+        State::Ok | State::Degraded => 200,
+    };
+    Response::from_json(&status)?
+        .with_status(code)
+        .cache_private()
+}
+
+pub async fn status(platform: &dyn Platform) -> Status {
+    let mut checks = BTreeMap::new();
+    checks.insert(
+        "kv",
+        timed(platform, check_kv(platform.index(), platform.moderation())).await,
+    );
+    checks.insert(
+        "assets",
+        timed(platform, check_assets(platform.assets())).await,
+    );
+    checks.insert("parse", timed(platform, async { check_parse() }).await);
+
+    let mut credentials_age_ms = None;
+    match platform.credentials() {
+        Ok(Some(credentials)) => {
+            let (check, age) = check_credentials(&*credentials, platform.now()).await;
+            checks.insert("credentials", check);
+            credentials_age_ms = age;
+        }
+        Ok(None) => (),
+        Err(err) => {
+            checks.insert(
+                "credentials",
+                Check::error("credentials are unavailable", err),
+            );
+        }
+    }
+
+    Status {
+        state: checks
+            .values()
+            .map(|check| check.state)
+            .max()
+            .unwrap_or(State::Ok),
+        version: cache::VERSION,
+        storage: platform.pastes().backend(),
+        credentials_age_ms,
+        checks,
+    }
+}
+
+/// The last status, the checks run at most once per [`consts::STATUS_TTL`].
+async fn remembered(last: &Memo<Status>, now: u64, status: impl Future<Output = Status>) -> Status {
+    if let Some(status) = last.get("status", now) {
+        return status;
+    }
+
+    let status = status.await;
+    last.insert("status", status.clone(), now);
+    status
+}
+
+async fn timed(platform: &dyn Platform, check: impl Future<Output = Check>) -> Check {
+    let start = platform.now();
+    let mut check = check.await;
+    check.duration_ms = platform.now().saturating_sub(start);
+    check
+}
+
+async fn check_kv(index: &dyn Store, moderation: &dyn Store) -> Check {
+    let stores = [
+        (index, "index is unreachable"),
+        (moderation, "moderation is unreachable"),
+    ];
+    for (store, message) in stores {
+        if let Err(err) = store.get(PROBE_KEY).await {
+            return Check::error(message, err);
+        }
+    }
+    Check::ok()
+}
+
+async fn check_assets(assets: &dyn Assets) -> Check {
+    // Every page is rendered into the index
+    match assets.get("index.html").await {
+        Ok(Some(_)) => Check::ok(),
+        Ok(None) => Check::failed("index.html is missing"),
+        Err(err) => Check::error("assets are unavailable", err),
+    }
+}
+
+fn check_parse() -> Check {
+    match round_trip() {
+        Ok(()) => Check::ok(),
+        Err(err) => Check::error("fixture could not be parsed", err),
+    }
+}
+
+fn round_trip() -> Result<()> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(FIXTURE.as_bytes())
+        .map_err(|e| e.to_string())?;
+    let compressed = encoder.finish().map_err(|e| e.to_string())?;
+    let export = base64::encode_config(compressed, base64::URL_SAFE);
+
+    let pob = SerdePathOfBuilding::from_export(&export).map_err(Error::Pob)?;
+    if pob.class_name() != "Scion" || pob.level() != 1 {
+        return Err(Error::Error("fixture parsed incorrectly".to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Credentials are requested again when they are missing, stale credentials are only degraded.
+async fn check_credentials(credentials: &dyn Credentials, now: u64) -> (Check, Option<u64>) {
+    if let Err(err) = credentials.verify().await {
+        return (Check::error("credentials were rejected", err), None);
+    }
+
+    let age = match credentials.created().await {
+        Ok(Some(created)) => now.saturating_sub(created),
+        Ok(None) => return (Check::degraded("no cached credentials"), None),
+        Err(err) => return (Check::error("credentials are unavailable", err), None),
+    };
+
+    let check = match age > CREDENTIALS_STALE {
+        true => Check::degraded("credentials were not refreshed"),
+        false => Check::ok(),
+    };
+    (check, Some(age))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::future::{block_on, LocalBoxFuture};
+
+    struct Created(Option<u64>);
+
+    impl Credentials for Created {
+        fn created(&self) -> LocalBoxFuture<'_, Result<Option<u64>>> {
+            Box::pin(async move { Ok(self.0) })
+        }
+
+        fn refresh(&self) -> LocalBoxFuture<'_, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn verify(&self) -> LocalBoxFuture<'_, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    /// Credentials which are no longer accepted by the storage.
+    struct Rejected;
+
+    impl Credentials for Rejected {
+        fn created(&self) -> LocalBoxFuture<'_, Result<Option<u64>>> {
+            Box::pin(async { Ok(Some(0)) })
+        }
+
+        fn refresh(&self) -> LocalBoxFuture<'_, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn verify(&self) -> LocalBoxFuture<'_, Result<()>> {
+            Box::pin(async { Err(Error::RemoteFailed(401, "verify credentials".into())) })
+        }
+    }
+
+    fn checked(state: State) -> Status {
+        Status {
+            state,
+            version: cache::VERSION,
+            storage: "memory",
+            credentials_age_ms: None,
+            checks: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_remembered() {
+        let last = Memo::new(consts::STATUS_TTL, 1);
+
+        let status = block_on(remembered(&last, 0, async { checked(State::Failed) }));
+        assert_eq!(status.state, State::Failed);
+        let status = block_on(remembered(&last, 1_000, async { checked(State::Ok) }));
+        assert_eq!(status.state, State::Failed);
+
+        let now = consts::STATUS_TTL;
+        let status = block_on(remembered(&last, now, async { checked(State::Ok) }));
+        assert_eq!(status.state, State::Ok);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip().unwrap();
+        assert_eq!(check_parse().state, State::Ok);
+    }
+
+    #[test]
+    fn test_check_credentials() {
+        let now = CREDENTIALS_STALE + 1_000;
+
+        let (check, age) = block_on(check_credentials(&Created(Some(now - 1_000)), now));
+        assert_eq!((check.state, age), (State::Ok, Some(1_000)));
+
+        let (check, age) = block_on(check_credentials(&Created(Some(0)), now));
+        assert_eq!((check.state, age), (State::Degraded, Some(now)));
+
+        let (check, age) = block_on(check_credentials(&Created(None), now));
+        assert_eq!((check.state, age), (State::Degraded, None));
+
+        let (check, age) = block_on(check_credentials(&Rejected, now));
+        assert_eq!((check.state, age), (State::Failed, None));
+        // The error of the storage is not part of the public status
+        assert_eq!(check.message, Some("credentials were rejected"));
+    }
+}
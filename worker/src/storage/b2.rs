@@ -1,25 +1,62 @@
+use super::{Format, Metadata, StoredPaste};
 use crate::{
     consts,
     crypto::sha1,
-    retry::{retry, Retry},
+    metrics,
+    retry::{self, retry, Backoff, Policy, Retry, WorkerTimer},
     utils,
     utils::hex,
     Error, Result,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::borrow::Cow;
+use std::{borrow::Cow, cell::Cell};
 use worker::{
     kv::KvStore, wasm_bindgen::JsValue, Env, Fetch, Headers, Method, Request, RequestInit,
 };
 
+/// KV key of the cached [`AuthDetails`].
+const CREDENTIALS: &str = "credentials";
+/// KV key of the time the cached credentials were requested.
+const CREDENTIALS_CREATED: &str = "credentials_created";
+/// Authorization tokens are valid for 24 hours, cached credentials expire well before.
+const CREDENTIALS_TTL: u64 = 12 * 3_600;
+/// File requested to verify the credentials, it does not need to exist.
+const PROBE_FILE: &str = "status/probe";
+
+/// File info key storing the SHA-1 of the original (uncompressed) paste.
+const INFO_PASTE_SHA1: &str = "paste-sha1";
+/// File info key storing the URI encoded JSON paste [`Metadata`].
+const INFO_PASTE_METADATA: &str = "paste-meta";
+/// File info key storing the [`Format`] of the stored paste.
+const INFO_PASTE_FORMAT: &str = "paste-format";
+/// B2 limits the names and values of all file info headers to 7000 bytes in total.
+const MAX_INFO_SIZE: usize = 7000;
+
 #[allow(dead_code)]
-pub async fn get(env: &Env, path: &str) -> Result<Option<worker::Response>> {
+pub async fn get(env: &Env, path: &str) -> Result<Option<StoredPaste>> {
     let b2 = B2::from_env(env)?;
-    let response = b2.download(path).await?;
+    let mut response = b2.download(path).await?;
 
     match response.status_code() {
-        200 => Ok(Some(response)),
+        200 => {
+            let metadata = match response
+                .headers()
+                .get(&format!("x-bz-info-{}", INFO_PASTE_METADATA))?
+            {
+                Some(metadata) => {
+                    let metadata: String = js_sys::decode_uri_component(&metadata)?.into();
+                    serde_json::from_str(&metadata)?
+                }
+                None => Metadata::default(),
+            };
+            let format = response
+                .headers()
+                .get(&format!("x-bz-info-{}", INFO_PASTE_FORMAT))?;
+            let data = super::unpack(Format::parse(format.as_deref()), response.bytes().await?);
+
+            Ok(Some(StoredPaste { data, metadata }))
+        }
         404 => Ok(None),
         status => Err(Error::RemoteFailed(
             status,
@@ -29,17 +66,81 @@ pub async fn get(env: &Env, path: &str) -> Result<Option<worker::Response>> {
 }
 
 #[allow(dead_code)]
-pub async fn put(env: &Env, filename: &str, sha1: &[u8], data: &mut [u8]) -> Result<()> {
+pub async fn sha1(env: &Env, path: &str) -> Result<Option<String>> {
     let b2 = B2::from_env(env)?;
+    let response = b2.head(path).await?;
 
+    match response.status_code() {
+        200 => {
+            let headers = response.headers();
+            let sha1 = match headers.get(&format!("x-bz-info-{}", INFO_PASTE_SHA1))? {
+                Some(sha1) => Some(sha1),
+                // pastes stored before compression was introduced
+                None => headers.get("x-bz-content-sha1")?,
+            };
+            Ok(sha1)
+        }
+        404 => Ok(None),
+        status => Err(Error::RemoteFailed(
+            status,
+            "failed to get paste info".to_owned(),
+        )),
+    }
+}
+
+#[allow(dead_code)]
+pub async fn put(
+    env: &Env,
+    filename: &str,
+    sha1: &[u8],
+    data: &mut [u8],
+    metadata: &Metadata,
+) -> Result<()> {
+    let b2 = B2::from_env(env)?;
+
+    // The paste SHA-1 is stored separately, the content SHA-1 is the one of the stored data
     let hex = utils::hex(sha1);
+    let metadata: Option<String> = match metadata.is_empty() {
+        true => None,
+        false => Some(js_sys::encode_uri_component(&serde_json::to_string(metadata)?).into()),
+    };
+    let (format, mut content) = super::pack(data);
+    let mut info = vec![
+        (INFO_PASTE_SHA1, hex.as_str()),
+        (INFO_PASTE_FORMAT, format.name()),
+    ];
+    if let Some(ref metadata) = metadata {
+        info.push((INFO_PASTE_METADATA, metadata));
+    }
+    // Metadata is limited in characters, URI encoding grows a character up to 9 bytes
+    if info_size(&info) > MAX_INFO_SIZE {
+        return Err(Error::BadRequest(
+            "Title, description and tags are too long".to_owned(),
+        ));
+    }
+
+    let content_sha1 = utils::hex(&crate::crypto::sha1(&mut content).await?);
     let settings = UploadSettings {
         filename,
-        content_type: "text/plain",
-        sha1: Some(&hex),
+        content_type: format.content_type(),
+        sha1: Some(&content_sha1),
+        info: &info,
     };
 
-    b2.upload(&settings, data).await.map(|_| ())
+    b2.upload(&settings, &mut content).await.map(|_| ())
+}
+
+/// Size of the file info as it is sent in headers.
+fn info_size(info: &[(&str, &str)]) -> usize {
+    info.iter()
+        .map(|(name, value)| "X-Bz-Info-".len() + name.len() + value.len())
+        .sum()
+}
+
+/// Hides the paste, B2 keeps the file version but it can no longer be downloaded.
+#[allow(dead_code)]
+pub async fn delete(env: &Env, path: &str) -> Result<()> {
+    B2::from_env(env)?.hide(path).await
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -85,35 +186,88 @@ pub struct UploadSettings<'a> {
     pub filename: &'a str,
     pub content_type: &'a str,
     pub sha1: Option<&'a str>,
+    /// Custom file information, stored as `X-Bz-Info-*` headers.
+    pub info: &'a [(&'a str, &'a str)],
 }
 
 const AUTH_DETAILS_URL: &str = "https://api.backblazeb2.com/b2api/v2/b2_authorize_account";
 
+/// Backoff between attempts, B2 asks clients to back off exponentially on 429 and 503.
+const BACKOFF: Backoff = Backoff {
+    initial_ms: 250,
+    max_ms: 4_000,
+    jitter: 0.5,
+};
+const UPLOAD_RETRY: Policy = Policy {
+    max_attempts: 5,
+    backoff: BACKOFF,
+    deadline_ms: 15_000,
+};
+const DOWNLOAD_RETRY: Policy = Policy {
+    max_attempts: 3,
+    backoff: BACKOFF,
+    deadline_ms: 5_000,
+};
+/// Retries once with new credentials, in case the cached ones expired.
+const API_RETRY: Policy = Policy {
+    max_attempts: 2,
+    backoff: BACKOFF,
+    deadline_ms: 5_000,
+};
+
+/// Delay requested by B2 in the `Retry-After` header.
+fn retry_after(response: &worker::Response) -> Option<u64> {
+    let value = response.headers().get("Retry-After").ok().flatten()?;
+    retry::parse_retry_after(&value)
+}
+
 macro_rules! retry_if {
-    ($response:ident, $err:expr, $($status:literal)|+, $map:expr) => {{
+    ($response:ident, $err:expr, $map:expr) => {{
         let status_code = $response.status_code();
-        if $($status == status_code)||+ {
-            log::info!("request failed {}, retrying '{}' if more attempts are available", status_code, $err);
-            Retry::err(Error::RemoteFailed(status_code, $err.into()))
-        } else if status_code >= 300 {
-            log::info!("request failed {}, not retrying '{}'", status_code, $err);
-            Err(Error::RemoteFailed(status_code, $err.into()))
-        } else {
-            Retry::ok($map)
+        let err = || Error::RemoteFailed(status_code, $err.into());
+        match status_code {
+            // Expired authorization token or upload url, the next attempt gets new ones
+            401 => {
+                log::info!(
+                    "request failed {}, retrying '{}' immediately",
+                    status_code,
+                    $err
+                );
+                Retry::now(err())
+            }
+            429 | 503 => {
+                log::info!(
+                    "request failed {}, retrying '{}' if more attempts are available",
+                    status_code,
+                    $err
+                );
+                Retry::after(err(), retry_after(&$response))
+            }
+            _ if status_code >= 300 => {
+                log::info!("request failed {}, not retrying '{}'", status_code, $err);
+                Err(err())
+            }
+            _ => Retry::ok($map),
         }
     }};
 }
 
 pub struct B2 {
     credentials: Credentials,
-    public_file_url: String,
+    /// Files are downloaded from the public url of the bucket, if configured.
+    /// Otherwise downloads are authorized, which also works for private buckets.
+    public_file_url: Option<String>,
 }
 
 impl B2 {
     pub fn from_env(env: &Env) -> Result<Self> {
         Ok(Self {
             credentials: Credentials::from_env(env)?,
-            public_file_url: env.var(consts::ENV_B2_PUBLIC_FILE_URL)?.to_string(),
+            public_file_url: env
+                .var(consts::ENV_B2_PUBLIC_FILE_URL)
+                .map(|url| url.to_string())
+                .ok()
+                .filter(|url| !url.is_empty()),
         })
     }
 
@@ -127,7 +281,7 @@ impl B2 {
             None => Cow::Owned(hex(&sha1(content).await?)),
         };
 
-        retry(5, |_| async {
+        retry("upload", &UPLOAD_RETRY, &WorkerTimer, |_| async {
             let upload = self.get_upload_url().await?;
 
             let mut headers = Headers::new();
@@ -135,6 +289,9 @@ impl B2 {
             headers.set("X-Bz-File-Name", settings.filename)?;
             headers.set("Content-Type", settings.content_type)?;
             headers.set("X-Bz-Content-Sha1", &sha1)?;
+            for (name, value) in settings.info {
+                headers.set(&format!("X-Bz-Info-{}", name), value)?;
+            }
 
             let request = Request::new_with_init(
                 &upload.upload_url,
@@ -146,45 +303,95 @@ impl B2 {
                 },
             )?;
 
+            let start = worker::Date::now().as_millis();
             let mut r = Fetch::Request(request).send().await?;
-            retry_if!(r, "upload", 401 | 503, r.json().await?)
+            metrics::emit(metrics::Event::Storage {
+                operation: "upload",
+                status: r.status_code(),
+                duration_ms: worker::Date::now().as_millis() - start,
+            });
+            retry_if!(r, "upload", r.json().await?)
         })
         .await
     }
 
     pub async fn download(&self, path: &str) -> Result<worker::Response> {
-        // Requires a public url for now ...
-        let mut url = self.public_file_url.to_owned();
-        url.push('/');
-        url.push_str(path);
-
-        retry(3, |_| async {
-            let request = Request::new(&url, Method::Get)?;
-            let response = Fetch::Request(request).send().await?;
-            if response.status_code() >= 500 {
-                log::info!("download failed {}", response.status_code());
-                Retry::err(Error::RemoteFailed(response.status_code(), "upload".into()))
-            } else {
-                Retry::ok(response)
+        self.request_file("download", path, Method::Get).await
+    }
+
+    /// Same as [`B2::download`] but only fetches the headers, B2 still returns
+    /// all file information, e.g. the content SHA-1.
+    pub async fn head(&self, path: &str) -> Result<worker::Response> {
+        self.request_file("head", path, Method::Head).await
+    }
+
+    async fn request_file(
+        &self,
+        operation: &'static str,
+        path: &str,
+        method: Method,
+    ) -> Result<worker::Response> {
+        // Set after a 401, the cached authorization token is no longer valid
+        let refresh = &Cell::new(false);
+
+        retry(operation, &DOWNLOAD_RETRY, &WorkerTimer, |_| {
+            let method = method.clone();
+            async move {
+                let mut headers = Headers::new();
+                let url = match &self.public_file_url {
+                    Some(public_file_url) => format!("{}/{}", public_file_url, path),
+                    None => {
+                        let auth = self.credentials.get_auth_details(refresh.get()).await?;
+                        headers.set("Authorization", &auth.authorization_token)?;
+                        download_url(&auth, path)
+                    }
+                };
+
+                let request = Request::new_with_init(
+                    &url,
+                    &RequestInit {
+                        method,
+                        headers,
+                        ..Default::default()
+                    },
+                )?;
+                let start = worker::Date::now().as_millis();
+                let response = Fetch::Request(request).send().await?;
+                metrics::emit(metrics::Event::Storage {
+                    operation,
+                    status: response.status_code(),
+                    duration_ms: worker::Date::now().as_millis() - start,
+                });
+
+                let status_code = response.status_code();
+                let err = || Error::RemoteFailed(status_code, "download".into());
+                if status_code == 401 && self.public_file_url.is_none() {
+                    log::info!("download unauthorized, retrying with new credentials");
+                    refresh.set(true);
+                    Retry::now(err())
+                } else if status_code == 429 || status_code >= 500 {
+                    log::info!("download failed {}", status_code);
+                    Retry::after(err(), retry_after(&response))
+                } else {
+                    Retry::ok(response)
+                }
             }
         })
         .await
     }
 
-    async fn get_upload_url(&self) -> Result<UploadDetails> {
-        // Retry once just in case the credentials expired and on the 2nd attempt force new
-        // credentials.
-        retry(2, |attempt| async move {
+    pub async fn hide(&self, path: &str) -> Result<()> {
+        retry("hide", &API_RETRY, &WorkerTimer, |attempt| async move {
             let auth = self.credentials.get_auth_details(attempt > 1).await?;
 
             let mut url = auth.api_url.to_owned();
-            url.push_str("/b2api/v2/b2_get_upload_url");
+            url.push_str("/b2api/v2/b2_hide_file");
 
             let mut headers = Headers::new();
             headers.set("Authorization", &auth.authorization_token)?;
 
             let body = JsValue::from_str(&serde_json::to_string(
-                &json!({"bucketId": auth.allowed.bucket_id}),
+                &json!({"bucketId": auth.allowed.bucket_id, "fileName": path}),
             )?);
             let request = Request::new_with_init(
                 &url,
@@ -196,11 +403,55 @@ impl B2 {
                 },
             )?;
 
-            let mut r = Fetch::Request(request).send().await?;
-            retry_if!(r, "upload_url", 401 | 503, r.json().await?)
+            let r = Fetch::Request(request).send().await?;
+            retry_if!(r, "hide", ())
         })
         .await
     }
+
+    async fn get_upload_url(&self) -> Result<UploadDetails> {
+        // Retry once just in case the credentials expired and on the 2nd attempt force new
+        // credentials.
+        retry(
+            "upload_url",
+            &API_RETRY,
+            &WorkerTimer,
+            |attempt| async move {
+                let auth = self.credentials.get_auth_details(attempt > 1).await?;
+
+                let mut url = auth.api_url.to_owned();
+                url.push_str("/b2api/v2/b2_get_upload_url");
+
+                let mut headers = Headers::new();
+                headers.set("Authorization", &auth.authorization_token)?;
+
+                let body = JsValue::from_str(&serde_json::to_string(
+                    &json!({"bucketId": auth.allowed.bucket_id}),
+                )?);
+                let request = Request::new_with_init(
+                    &url,
+                    &RequestInit {
+                        method: Method::Post,
+                        headers,
+                        body: Some(body),
+                        ..Default::default()
+                    },
+                )?;
+
+                let mut r = Fetch::Request(request).send().await?;
+                retry_if!(r, "upload_url", r.json().await?)
+            },
+        )
+        .await
+    }
+}
+
+/// Url of `b2_download_file_by_name`, requests must be authorized with the token.
+fn download_url(auth: &AuthDetails, path: &str) -> String {
+    format!(
+        "{}/file/{}/{}",
+        auth.download_url, auth.allowed.bucket_name, path
+    )
 }
 
 pub struct Credentials {
@@ -218,9 +469,43 @@ impl Credentials {
         })
     }
 
+    /// Time in milliseconds since the epoch the cached credentials were requested.
+    pub async fn created(&self) -> Result<Option<u64>> {
+        let created = self.kv.get(CREDENTIALS_CREATED).text().await?;
+        Ok(created.and_then(|created| created.parse().ok()))
+    }
+
+    /// Requests new credentials, replacing the cached ones.
+    pub async fn refresh(&self) -> Result<()> {
+        self.get_auth_details(true).await.map(|_| ())
+    }
+
+    /// Requests a file with the cached credentials, B2 answers authorized requests for missing
+    /// files with a `404` and rejects invalid tokens with a `401`.
+    pub async fn verify(&self) -> Result<()> {
+        let auth = self.get_auth_details(false).await?;
+
+        let mut headers = Headers::new();
+        headers.set("Authorization", &auth.authorization_token)?;
+        let request = Request::new_with_init(
+            &download_url(&auth, PROBE_FILE),
+            &RequestInit {
+                method: Method::Head,
+                headers,
+                ..Default::default()
+            },
+        )?;
+
+        let response = Fetch::Request(request).send().await?;
+        match response.status_code() {
+            200..=299 | 404 => Ok(()),
+            status => Err(Error::RemoteFailed(status, "verify credentials".into())),
+        }
+    }
+
     async fn get_auth_details(&self, force_refresh: bool) -> Result<AuthDetails> {
         if !force_refresh {
-            if let Some(auth_details) = self.kv.get("credentials").cache_ttl(3_600).json().await? {
+            if let Some(auth_details) = self.kv.get(CREDENTIALS).cache_ttl(3_600).json().await? {
                 log::debug!("using cached auth details");
                 return Ok(auth_details);
             }
@@ -233,10 +518,14 @@ impl Credentials {
         let auth_details = self.get_new_auth_details().await?;
         log::info!("<-- got auth details");
 
-        // TODO: maybe persist creation time with the key?
         self.kv
-            .put("credentials", &auth_details)?
-            .expiration_ttl(12 * 3_600)
+            .put(CREDENTIALS, &auth_details)?
+            .expiration_ttl(CREDENTIALS_TTL)
+            .execute()
+            .await?;
+        self.kv
+            .put(CREDENTIALS_CREATED, worker::Date::now().as_millis())?
+            .expiration_ttl(CREDENTIALS_TTL)
             .execute()
             .await?;
 
@@ -266,3 +555,14 @@ impl Credentials {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_info_size() {
+        let info = [(INFO_PASTE_SHA1, "ABC"), (INFO_PASTE_METADATA, "%E2%82%AC")];
+        assert_eq!(info_size(&info), 10 + 10 + 3 + 10 + 10 + 9);
+    }
+}
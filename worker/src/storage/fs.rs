@@ -0,0 +1,121 @@
+use super::{Metadata, Pastes, StoredPaste};
+use crate::{future::LocalBoxFuture, utils, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Stored next to the paste with an additional `.meta` extension.
+#[derive(Serialize, Deserialize)]
+struct Info {
+    sha1: String,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+/// Pastes stored as plain files in a directory, for the native server.
+pub struct FsPastes {
+    root: PathBuf,
+}
+
+impl FsPastes {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn paths(&self, id: &str) -> Result<(PathBuf, PathBuf)> {
+        // Ids end up in the path, never allow them to escape the directory
+        if !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(crate::Error::NotFound("paste", id.to_owned()));
+        }
+        let path = utils::to_path(id)?;
+        let meta = format!("{}.meta", path);
+        Ok((self.root.join(path), self.root.join(meta)))
+    }
+
+    fn info(path: &Path) -> Result<Option<Info>> {
+        match read(path)? {
+            Some(info) => Ok(Some(serde_json::from_slice(&info)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Pastes for FsPastes {
+    fn backend(&self) -> &'static str {
+        "fs"
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<Option<StoredPaste>>> {
+        Box::pin(async move {
+            let (path, meta) = self.paths(id)?;
+            let data = match read(&path)? {
+                Some(data) => data,
+                None => return Ok(None),
+            };
+            let metadata = Self::info(&meta)?
+                .map(|info| info.metadata)
+                .unwrap_or_default();
+
+            Ok(Some(StoredPaste { data, metadata }))
+        })
+    }
+
+    fn sha1<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            let (_, meta) = self.paths(id)?;
+            Ok(Self::info(&meta)?.map(|info| info.sha1))
+        })
+    }
+
+    fn put<'a>(
+        &'a self,
+        id: &'a str,
+        sha1: &'a [u8],
+        data: &'a mut [u8],
+        metadata: &'a Metadata,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let (path, meta) = self.paths(id)?;
+            let info = Info {
+                sha1: utils::hex(sha1),
+                metadata: metadata.clone(),
+            };
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::write(&path, data).map_err(|e| e.to_string())?;
+            fs::write(&meta, serde_json::to_vec(&info)?).map_err(|e| e.to_string())?;
+
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let (path, meta) = self.paths(id)?;
+            for path in [path, meta] {
+                match fs::remove_file(path) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                        return Err(err.to_string().into())
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+fn read(path: &Path) -> Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.to_string().into()),
+    }
+}
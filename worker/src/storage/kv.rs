@@ -1,17 +1,75 @@
-use crate::{consts, Result};
-use worker::{Env, Response};
+use super::{Format, Metadata, StoredPaste};
+use crate::{consts, crypto, utils, Result};
+use worker::Env;
 
-pub async fn get(env: &Env, path: &str) -> Result<Option<Response>> {
+/// Pastes stored as [`Format::Zlib`] use a separate key, KV has no file info.
+fn zlib_key(path: &str) -> String {
+    format!("{}.zlib", path)
+}
+
+pub async fn get(env: &Env, path: &str) -> Result<Option<StoredPaste>> {
+    let kv = env.kv(consts::KV_PASTE_STORAGE)?;
+
+    let data = match kv.get(&zlib_key(path)).bytes().await? {
+        Some(data) => super::unpack(Format::Zlib, data),
+        None => match kv.get(path).bytes().await? {
+            Some(data) => super::unpack(Format::Export, data),
+            None => return Ok(None),
+        },
+    };
+    let metadata = kv
+        .get(&format!("{}.meta", path))
+        .json()
+        .await?
+        .unwrap_or_default();
+
+    Ok(Some(StoredPaste { data, metadata }))
+}
+
+pub async fn sha1(env: &Env, path: &str) -> Result<Option<String>> {
+    match get(env, path).await? {
+        Some(paste) => {
+            let mut identity = super::identity(&paste.data, &paste.metadata)?;
+            Ok(Some(utils::hex(&crypto::sha1(&mut identity).await?)))
+        }
+        None => Ok(None),
+    }
+}
+
+pub async fn put(
+    env: &Env,
+    filename: &str,
+    _sha1: &[u8],
+    data: &mut [u8],
+    metadata: &Metadata,
+) -> Result<()> {
     let kv = env.kv(consts::KV_PASTE_STORAGE)?;
 
-    let data = kv.get(path).text().await?;
-    Ok(data.map(|data| Response::ok(data).unwrap()))
+    let (format, content) = super::pack(data);
+    let key = match format {
+        Format::Zlib => zlib_key(filename),
+        Format::Export => filename.to_owned(),
+    };
+    kv.put_bytes(&key, &content)?.execute().await?;
+
+    if !metadata.is_empty() {
+        kv.put(
+            &format!("{}.meta", filename),
+            serde_json::to_string(metadata)?,
+        )?
+        .execute()
+        .await?;
+    }
+
+    Ok(())
 }
 
-pub async fn put(env: &Env, filename: &str, _sha1: &[u8], data: &mut [u8]) -> Result<()> {
+pub async fn delete(env: &Env, path: &str) -> Result<()> {
     let kv = env.kv(consts::KV_PASTE_STORAGE)?;
 
-    kv.put_bytes(filename, data)?.execute().await?;
+    kv.delete(path).await?;
+    kv.delete(&zlib_key(path)).await?;
+    kv.delete(&format!("{}.meta", path)).await?;
 
     Ok(())
 }
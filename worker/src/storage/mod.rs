@@ -1,10 +1,301 @@
+use crate::{future::LocalBoxFuture, platform::Platform, utils, Error, Result};
+use serde::{Deserialize, Serialize};
+use worker::Env;
+
 mod b2;
 
 // Only supposed to be used for local development
 #[cfg(feature = "storage-kv")]
 mod kv;
 
+#[cfg(feature = "server")]
+mod fs;
+
+/// Name of the storage backend in use.
+#[cfg(not(feature = "storage-kv"))]
+pub const BACKEND: &str = "b2";
+#[cfg(feature = "storage-kv")]
+pub const BACKEND: &str = "kv";
+
 #[cfg(not(feature = "storage-kv"))]
-pub use b2::{get, put};
+pub use b2::{delete, get, put, sha1};
 #[cfg(feature = "storage-kv")]
-pub use kv::{get, put};
+pub use kv::{delete, get, put, sha1};
+
+#[cfg(not(feature = "storage-kv"))]
+pub use b2::Credentials;
+#[cfg(feature = "server")]
+pub use fs::FsPastes;
+
+/// How the content of a paste is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The export as it was uploaded.
+    Export,
+    /// The base64 decoded export, which is the zlib compressed XML of the build.
+    ///
+    /// Only used if encoding it again results in the exact same export.
+    Zlib,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Export => "export",
+            Self::Zlib => "zlib",
+        }
+    }
+
+    /// Parses a stored format name, pastes stored without a format are exports.
+    pub fn parse(name: Option<&str>) -> Self {
+        match name {
+            Some("zlib") => Self::Zlib,
+            _ => Self::Export,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Export => "text/plain",
+            Self::Zlib => "application/zlib",
+        }
+    }
+}
+
+/// Converts an export into its stored form.
+///
+/// Base64 inflates the export by a third and the compressed XML does not compress any
+/// further, the decoded export is stored whenever the export can be restored byte for byte.
+pub fn pack(export: &[u8]) -> (Format, Vec<u8>) {
+    if let Ok(decoded) = base64::decode_config(export, base64::URL_SAFE) {
+        if base64::encode_config(&decoded, base64::URL_SAFE).as_bytes() == export {
+            return (Format::Zlib, decoded);
+        }
+    }
+
+    (Format::Export, export.to_vec())
+}
+
+/// Restores the exact export from its stored form, see [`pack`].
+pub fn unpack(format: Format, data: Vec<u8>) -> Vec<u8> {
+    match format {
+        Format::Export => data,
+        Format::Zlib => base64::encode_config(data, base64::URL_SAFE).into_bytes(),
+    }
+}
+
+/// A paste as it is stored.
+pub struct StoredPaste {
+    /// The original, uncompressed export.
+    pub data: Vec<u8>,
+    pub metadata: Metadata,
+}
+
+impl StoredPaste {
+    /// Verifies the paste can be accessed with the given view key.
+    ///
+    /// Pastes which cannot be accessed are treated as if they do not exist.
+    pub async fn verify_access(
+        &self,
+        platform: &dyn Platform,
+        id: &str,
+        key: Option<&str>,
+    ) -> Result<()> {
+        let expected = match self.metadata.view_key {
+            Some(ref expected) => expected,
+            None => return Ok(()),
+        };
+
+        // Both digests are compared upper case hex, in constant time
+        let key = key.unwrap_or_default().as_bytes();
+        let key = utils::hex(&platform.sha1(key).await?);
+        let expected = expected.to_ascii_uppercase();
+        if utils::constant_time_eq(key.as_bytes(), expected.as_bytes()) {
+            Ok(())
+        } else {
+            Err(Error::NotFound("paste", id.to_owned()))
+        }
+    }
+}
+
+/// Additional information stored alongside a paste.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Url the paste was imported from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Private pastes have a random id and are never listed or publicly cached.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub private: bool,
+    /// Hex encoded SHA-1 of the key required to view the paste.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_key: Option<String>,
+    /// Name of the API key the paste was uploaded with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploader: Option<String>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Access to stored pastes by id, abstracted over the storage backend.
+pub trait Pastes {
+    /// Name of the storage backend, e.g. for the status.
+    fn backend(&self) -> &'static str;
+
+    fn get<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<Option<StoredPaste>>>;
+    /// Hex encoded SHA-1 of the [`identity`] of a stored paste.
+    fn sha1<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<Option<String>>>;
+    fn put<'a>(
+        &'a self,
+        id: &'a str,
+        sha1: &'a [u8],
+        data: &'a mut [u8],
+        metadata: &'a Metadata,
+    ) -> LocalBoxFuture<'a, Result<()>>;
+    fn delete<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<()>>;
+
+    fn exists<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<bool>> {
+        Box::pin(async move { Ok(self.sha1(id).await?.is_some()) })
+    }
+}
+
+/// The configured storage backend.
+pub struct EnvPastes<'e>(pub &'e Env);
+
+impl Pastes for EnvPastes<'_> {
+    fn backend(&self) -> &'static str {
+        BACKEND
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<Option<StoredPaste>>> {
+        Box::pin(async move { get(self.0, &utils::to_path(id)?).await })
+    }
+
+    fn sha1<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move { sha1(self.0, &utils::to_path(id)?).await })
+    }
+
+    fn put<'a>(
+        &'a self,
+        id: &'a str,
+        sha1: &'a [u8],
+        data: &'a mut [u8],
+        metadata: &'a Metadata,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move { put(self.0, &utils::to_path(id)?, sha1, data, metadata).await })
+    }
+
+    fn delete<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move { delete(self.0, &utils::to_path(id)?).await })
+    }
+}
+
+/// In-memory storage, the SHA-1 is stored as given.
+#[cfg(test)]
+#[derive(Default)]
+pub struct Memory {
+    pastes: std::cell::RefCell<std::collections::BTreeMap<String, (Vec<u8>, Metadata, String)>>,
+}
+
+#[cfg(test)]
+impl Memory {
+    pub fn with(self, id: &str, data: &str, metadata: Metadata) -> Self {
+        self.pastes.borrow_mut().insert(
+            id.to_owned(),
+            (data.as_bytes().to_vec(), metadata, String::new()),
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+impl Pastes for Memory {
+    fn backend(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<Option<StoredPaste>>> {
+        let paste = self
+            .pastes
+            .borrow()
+            .get(id)
+            .cloned()
+            .map(|(data, metadata, _)| StoredPaste { data, metadata });
+        Box::pin(async move { Ok(paste) })
+    }
+
+    fn sha1<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<Option<String>>> {
+        let sha1 = self
+            .pastes
+            .borrow()
+            .get(id)
+            .map(|(_, _, sha1)| sha1.clone());
+        Box::pin(async move { Ok(sha1) })
+    }
+
+    fn put<'a>(
+        &'a self,
+        id: &'a str,
+        sha1: &'a [u8],
+        data: &'a mut [u8],
+        metadata: &'a Metadata,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        self.pastes.borrow_mut().insert(
+            id.to_owned(),
+            (data.to_vec(), metadata.clone(), utils::hex(sha1)),
+        );
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn delete<'a>(&'a self, id: &'a str) -> LocalBoxFuture<'a, Result<()>> {
+        self.pastes.borrow_mut().remove(id);
+        Box::pin(async move { Ok(()) })
+    }
+}
+
+/// The data the SHA-1 of a paste is calculated from.
+///
+/// Metadata is part of the identity of a paste, the same build uploaded
+/// with a different title results in a different paste.
+pub fn identity(data: &[u8], metadata: &Metadata) -> Result<Vec<u8>> {
+    let mut result = data.to_vec();
+    if !metadata.is_empty() {
+        result.push(b'\n');
+        result.extend(serde_json::to_vec(metadata)?);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack() {
+        let export = base64::encode_config(b"x\x9c\x01\x02\x03", base64::URL_SAFE);
+        let (format, packed) = pack(export.as_bytes());
+        assert_eq!(format, Format::Zlib);
+        assert_eq!(packed, b"x\x9c\x01\x02\x03");
+        assert_eq!(unpack(format, packed), export.as_bytes());
+
+        // Anything which would not restore the exact export is stored as is
+        for export in [
+            format!("{}\n", export),
+            export.trim_end_matches('=').to_owned(),
+        ] {
+            let (format, packed) = pack(export.as_bytes());
+            assert_eq!(format, Format::Export);
+            assert_eq!(unpack(format, packed), export.as_bytes());
+        }
+    }
+}
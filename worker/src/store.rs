@@ -0,0 +1,242 @@
+use crate::{future::LocalBoxFuture, Result};
+use serde_json::Value;
+use worker::{kv::KvStore, Env};
+
+/// A key as returned from a listing, including its metadata.
+#[derive(Debug, Clone)]
+pub struct ListKey {
+    pub name: String,
+    pub metadata: Option<Value>,
+}
+
+/// One page of a listing, the cursor continues the listing if there are more keys.
+#[derive(Debug, Default)]
+pub struct ListPage {
+    pub keys: Vec<ListKey>,
+    pub cursor: Option<String>,
+}
+
+/// Key-value storage, abstracted so indexes can be tested without Workers KV.
+///
+/// Listings are ordered lexicographically by key.
+pub trait Store {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<Option<String>>>;
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        value: String,
+        metadata: Option<Value>,
+    ) -> LocalBoxFuture<'a, Result<()>>;
+    fn delete<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<()>>;
+    fn list<'a>(
+        &'a self,
+        prefix: &'a str,
+        cursor: Option<String>,
+        limit: u64,
+    ) -> LocalBoxFuture<'a, Result<ListPage>>;
+}
+
+/// Workers KV namespace.
+pub struct WorkerKv(KvStore);
+
+impl WorkerKv {
+    pub fn new(env: &Env, binding: &str) -> Result<Self> {
+        Ok(Self(env.kv(binding)?))
+    }
+}
+
+impl Store for WorkerKv {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move { Ok(self.0.get(key).text().await?) })
+    }
+
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        value: String,
+        metadata: Option<Value>,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut put = self.0.put(key, value)?;
+            if let Some(metadata) = metadata {
+                put = put.metadata(metadata)?;
+            }
+            put.execute().await?;
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move { Ok(self.0.delete(key).await?) })
+    }
+
+    fn list<'a>(
+        &'a self,
+        prefix: &'a str,
+        cursor: Option<String>,
+        limit: u64,
+    ) -> LocalBoxFuture<'a, Result<ListPage>> {
+        Box::pin(async move {
+            let mut list = self.0.list().prefix(prefix.to_owned()).limit(limit);
+            if let Some(cursor) = cursor {
+                list = list.cursor(cursor);
+            }
+            let response = list.execute().await?;
+
+            Ok(ListPage {
+                keys: response
+                    .keys
+                    .into_iter()
+                    .map(|key| ListKey {
+                        name: key.name,
+                        metadata: key.metadata,
+                    })
+                    .collect(),
+                cursor: response.cursor.filter(|_| !response.list_complete),
+            })
+        })
+    }
+}
+
+/// In-memory store, the cursor is the last key of the previous page.
+#[cfg(any(test, feature = "server"))]
+#[derive(Default)]
+pub struct Memory {
+    values: std::cell::RefCell<std::collections::BTreeMap<String, (String, Option<Value>)>>,
+}
+
+#[cfg(test)]
+impl Memory {
+    pub fn keys(&self) -> Vec<String> {
+        self.values.borrow().keys().cloned().collect()
+    }
+}
+
+#[cfg(any(test, feature = "server"))]
+impl Store for Memory {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<Option<String>>> {
+        let value = self
+            .values
+            .borrow()
+            .get(key)
+            .map(|(value, _)| value.clone());
+        Box::pin(async move { Ok(value) })
+    }
+
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        value: String,
+        metadata: Option<Value>,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        self.values
+            .borrow_mut()
+            .insert(key.to_owned(), (value, metadata));
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<()>> {
+        self.values.borrow_mut().remove(key);
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn list<'a>(
+        &'a self,
+        prefix: &'a str,
+        cursor: Option<String>,
+        limit: u64,
+    ) -> LocalBoxFuture<'a, Result<ListPage>> {
+        use std::ops::Bound;
+
+        let start = match cursor {
+            Some(cursor) => Bound::Excluded(cursor),
+            None => Bound::Included(prefix.to_owned()),
+        };
+        let values = self.values.borrow();
+        let mut keys = values
+            .range((start, Bound::Unbounded))
+            .take_while(|(name, _)| name.starts_with(prefix))
+            .map(|(name, (_, metadata))| ListKey {
+                name: name.clone(),
+                metadata: metadata.clone(),
+            });
+
+        let page = (&mut keys).take(limit as usize).collect::<Vec<_>>();
+        let cursor = match keys.next() {
+            Some(_) => page.last().map(|key| key.name.clone()),
+            None => None,
+        };
+
+        Box::pin(async move { Ok(ListPage { keys: page, cursor }) })
+    }
+}
+
+/// In-memory store persisted as a single JSON file after every change, for the native server.
+#[cfg(feature = "server")]
+pub struct FileStore {
+    path: std::path::PathBuf,
+    memory: Memory,
+}
+
+#[cfg(feature = "server")]
+impl FileStore {
+    pub fn open(path: impl Into<std::path::PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let values = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(err) => return Err(err.to_string().into()),
+        };
+
+        Ok(Self {
+            path,
+            memory: Memory {
+                values: std::cell::RefCell::new(values),
+            },
+        })
+    }
+
+    fn persist(&self) -> Result<()> {
+        let data = serde_json::to_vec(&*self.memory.values.borrow())?;
+        // Write to a temporary file first, a crash never leaves a partially written store
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, data).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "server")]
+impl Store for FileStore {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<Option<String>>> {
+        self.memory.get(key)
+    }
+
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        value: String,
+        metadata: Option<Value>,
+    ) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.memory.put(key, value, metadata).await?;
+            self.persist()
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.memory.delete(key).await?;
+            self.persist()
+        })
+    }
+
+    fn list<'a>(
+        &'a self,
+        prefix: &'a str,
+        cursor: Option<String>,
+        limit: u64,
+    ) -> LocalBoxFuture<'a, Result<ListPage>> {
+        self.memory.list(prefix, cursor, limit)
+    }
+}
@@ -1,3 +1,7 @@
+use crate::{
+    encoding::Encoding,
+    platform::{self, Platform},
+};
 use worker::wasm_bindgen::JsCast;
 use worker::worker_sys::WorkerGlobalScope;
 use worker::{js_sys, worker_sys, Response, Result};
@@ -21,10 +25,17 @@ pub fn basic_auth(username: &str, password: &str) -> Result<String> {
     Ok(result)
 }
 
-// pub fn random_id<const N: usize>() -> Result<String> {
-//     let random = crate::crypto::get_random_values::<N>()?;
-//     Ok(base64::encode_config(random, base64::URL_SAFE_NO_PAD))
-// }
+pub fn random_id<const N: usize>(platform: &dyn Platform) -> crate::Result<String> {
+    let mut random = [0; N];
+    platform.random(&mut random)?;
+    Ok(base64::encode_config(random, base64::URL_SAFE_NO_PAD))
+}
+
+pub fn query_param(url: &worker::Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
 
 pub fn hash_to_short_id(hash: &[u8], bytes: usize) -> Result<String> {
     hash.get(0..bytes)
@@ -45,30 +56,179 @@ pub fn to_path(id: &str) -> Result<String> {
     Ok(result)
 }
 
+/// Compares two secrets in constant time, only leaks the length.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Escapes text for use in HTML content and attribute values.
+pub fn html_escape(s: &str) -> std::borrow::Cow<str> {
+    if !s.contains(&['&', '<', '>', '"', '\''][..]) {
+        return s.into();
+    }
+
+    let mut result = String::with_capacity(s.len() + 16);
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            c => result.push(c),
+        }
+    }
+    result.into()
+}
+
+struct DateTime {
+    year: u64,
+    month: u64,
+    day: u64,
+    weekday: u64,
+    hour: u64,
+    minute: u64,
+    second: u64,
+}
+
+impl DateTime {
+    /// UTC date from milliseconds since the epoch.
+    fn from_millis(ms: u64) -> Self {
+        let secs = ms / 1000;
+        let days = secs / 86_400;
+
+        // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+        Self {
+            year: yoe + era * 400 + (month <= 2) as u64,
+            month,
+            day: doy - (153 * mp + 2) / 5 + 1,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 4) % 7,
+            hour: secs / 3600 % 24,
+            minute: secs / 60 % 60,
+            second: secs % 60,
+        }
+    }
+}
+
+/// Formats milliseconds since the epoch as RFC 3339 timestamp, e.g. for Atom feeds.
+pub fn rfc3339(ms: u64) -> String {
+    let d = DateTime::from_millis(ms);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        d.year, d.month, d.day, d.hour, d.minute, d.second
+    )
+}
+
+/// Formats milliseconds since the epoch as RFC 2822 timestamp, e.g. for RSS feeds.
+pub fn rfc2822(ms: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let d = DateTime::from_millis(ms);
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[d.weekday as usize],
+        d.day,
+        MONTHS[d.month as usize - 1],
+        d.year,
+        d.hour,
+        d.minute,
+        d.second
+    )
+}
+
 pub trait ResponseExt: Sized {
     fn cache_for(self, ttl: u32) -> crate::Result<Self> {
         self.with_header("Cache-Control", &format!("max-age={}", ttl))
     }
+    /// Caches a response, which never changes, e.g. a fingerprinted asset.
+    fn cache_immutable(self, ttl: u32) -> crate::Result<Self> {
+        self.with_header(
+            "Cache-Control",
+            &format!("public, max-age={}, immutable", ttl),
+        )
+    }
+    /// Prevents caching of the response in shared caches, see also [`is_cacheable`].
+    fn cache_private(self) -> crate::Result<Self> {
+        self.with_header("Cache-Control", "private, no-store")
+    }
     fn with_content_type(self, content_type: &str) -> crate::Result<Self> {
         self.with_header("Content-Type", content_type)
     }
+    fn with_content_encoding(self, encoding: Encoding) -> crate::Result<Self> {
+        let response = self.with_header("Vary", "Accept-Encoding")?;
+        match encoding {
+            Encoding::Identity => Ok(response),
+            encoding => response.with_header("Content-Encoding", encoding.name()),
+        }
+    }
     fn with_etag(self, entity_id: &str) -> crate::Result<Self> {
         let entity_id = format!("\"{}\"", entity_id.trim_matches('"'));
         self.with_header("Etag", &entity_id)
     }
 
     fn dup_headers(self) -> Self;
+    fn has_header(&self, name: &str) -> bool;
     fn with_header(self, name: &str, value: &str) -> crate::Result<Self>;
 
     fn cloned(self) -> crate::Result<(Self, Self)>;
 }
 
+/// Whether the response may be stored in the shared edge cache.
+pub fn is_cacheable(response: &Response) -> bool {
+    // Partial and not modified responses depend on the request headers
+    if matches!(response.status_code(), 206 | 304 | 416) {
+        return false;
+    }
+    // The cache key does not contain the encoding, precompressed bodies depend on the request
+    if response.has_header(platform::ENCODED_BODY_HEADER) {
+        return false;
+    }
+
+    let cache_control = response
+        .headers()
+        .get("Cache-Control")
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+
+    !cache_control
+        .split(',')
+        .map(str::trim)
+        .any(|directive| directive == "private" || directive == "no-store")
+}
+
+/// Whether an `If-None-Match` header matches the entity tag, weak tags match as well.
+pub fn etag_matches(if_none_match: Option<&str>, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/").trim_matches('"');
+    if_none_match
+        .unwrap_or_default()
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/").trim_matches('"'))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
 impl ResponseExt for Response {
     fn dup_headers(self) -> Self {
         let headers = self.headers().clone();
         self.with_headers(headers)
     }
 
+    fn has_header(&self, name: &str) -> bool {
+        self.headers().has(name).unwrap_or(false)
+    }
+
     fn with_header(mut self, name: &str, value: &str) -> crate::Result<Self> {
         self.headers_mut().set(name, value)?;
         Ok(self)
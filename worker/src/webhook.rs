@@ -0,0 +1,362 @@
+//! Notifications of new public pastes to configured webhooks.
+//!
+//! Webhooks are configured as a JSON list in the `WEBHOOKS` variable, e.g.
+//! `[{"url": "https://discord.com/api/webhooks/..", "format": "discord"}]`. With a `secret`
+//! the body is signed with HMAC-SHA256 in the `X-Signature-256` header, like GitHub does.
+
+use crate::{
+    consts,
+    fetch::Fetcher,
+    future::{self, LocalBoxFuture},
+    platform::Platform,
+    retry::{self, retry, Backoff, Policy, Retry, Timer},
+    utils, Error, Result,
+};
+use pob::SerdePathOfBuilding;
+use serde::{Deserialize, Serialize};
+use worker::{Env, Url};
+
+const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// Deliveries run concurrently after the response is sent, Cloudflare allows 30 seconds for
+/// all of them.
+const RETRY: Policy = Policy {
+    max_attempts: 4,
+    backoff: Backoff {
+        initial_ms: 1_000,
+        max_ms: 8_000,
+        jitter: 0.5,
+    },
+    deadline_ms: 25_000,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Discord compatible message with an embed.
+    Discord,
+    /// The paste as generic JSON, see [`Paste`].
+    #[default]
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub format: Format,
+    /// Key for the signature of the body, requests are not signed without it.
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+/// Parses the webhook configuration, an invalid configuration disables all webhooks.
+pub fn parse(config: &str) -> Vec<Webhook> {
+    match serde_json::from_str(config) {
+        Ok(webhooks) => webhooks,
+        Err(err) => {
+            log::error!("invalid webhook configuration: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+pub fn from_env(env: &Env) -> Vec<Webhook> {
+    match env.var(consts::ENV_WEBHOOKS) {
+        Ok(config) => parse(&config.to_string()),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// A new public paste, the payload of the generic format.
+#[derive(Debug, Serialize)]
+pub struct Paste {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    pub class: String,
+    pub main_skill: Option<String>,
+    pub stats: Vec<Stat>,
+    /// Upload time in milliseconds since the epoch.
+    pub created: u64,
+    #[serde(skip)]
+    color: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stat {
+    pub name: String,
+    pub value: String,
+}
+
+impl Paste {
+    pub fn new(
+        id: &str,
+        url: String,
+        title: Option<String>,
+        created: u64,
+        pob: &SerdePathOfBuilding,
+    ) -> Self {
+        let info = app::PasteInfo::new(pob);
+
+        // Stats are rendered as e.g. `Life: 5,000 [150%]`
+        let stats = info
+            .stats
+            .iter()
+            .map(|spans| {
+                spans
+                    .iter()
+                    .map(|(_, text)| text.as_str())
+                    .collect::<String>()
+            })
+            .filter_map(|stat| {
+                let (name, value) = stat.split_once(": ")?;
+                Some(Stat {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                })
+            })
+            .collect();
+
+        Self {
+            id: id.to_owned(),
+            url,
+            title: title.unwrap_or(info.title),
+            class: info.ascendancy_or_class,
+            main_skill: info.main_skill,
+            stats,
+            created,
+            color: u32::from_str_radix(info.color.trim_start_matches('#'), 16).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Event<'a> {
+    event: &'static str,
+    paste: &'a Paste,
+}
+
+#[derive(Serialize)]
+struct DiscordMessage<'a> {
+    embeds: [DiscordEmbed<'a>; 1],
+}
+
+#[derive(Serialize)]
+struct DiscordEmbed<'a> {
+    title: &'a str,
+    url: &'a str,
+    description: String,
+    color: u32,
+    fields: Vec<DiscordField<'a>>,
+    timestamp: String,
+}
+
+#[derive(Serialize)]
+struct DiscordField<'a> {
+    name: &'a str,
+    value: &'a str,
+    inline: bool,
+}
+
+/// Body of the webhook request in the format.
+pub fn payload(format: Format, paste: &Paste) -> Result<Vec<u8>> {
+    let payload = match format {
+        Format::Json => serde_json::to_vec(&Event {
+            event: "paste.created",
+            paste,
+        })?,
+        Format::Discord => {
+            let description = match &paste.main_skill {
+                Some(main_skill) => format!("{} · {}", paste.class, main_skill),
+                None => paste.class.clone(),
+            };
+            let fields = paste
+                .stats
+                .iter()
+                .map(|stat| DiscordField {
+                    name: &stat.name,
+                    value: &stat.value,
+                    inline: true,
+                })
+                .collect();
+
+            serde_json::to_vec(&DiscordMessage {
+                embeds: [DiscordEmbed {
+                    title: &paste.title,
+                    url: &paste.url,
+                    description,
+                    color: paste.color,
+                    fields,
+                    timestamp: utils::rfc3339(paste.created),
+                }],
+            })?
+        }
+    };
+
+    Ok(payload)
+}
+
+/// Value of the signature header, the hex encoded HMAC-SHA256 of the body.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mac = hmac_sha256::HMAC::mac(body, secret.as_bytes());
+    format!("sha256={}", utils::hex(&mac).to_ascii_lowercase())
+}
+
+/// Sends the body to the webhook, retries if the webhook is unavailable or rate limited.
+pub async fn deliver(
+    fetcher: &dyn Fetcher,
+    timer: &dyn Timer,
+    webhook: &Webhook,
+    body: &[u8],
+) -> Result<()> {
+    let mut headers = vec![
+        ("Content-Type", "application/json".to_owned()),
+        ("User-Agent", "pobb.in/1.0".to_owned()),
+    ];
+    if let Some(secret) = &webhook.secret {
+        headers.push((SIGNATURE_HEADER, sign(secret, body)));
+    }
+    let headers = &headers;
+
+    retry("webhook", &RETRY, timer, |_| async move {
+        let response = match fetcher.post(&webhook.url, headers, body.to_vec()).await {
+            Ok(response) => response,
+            Err(err) => return Retry::err(err),
+        };
+
+        let err = || Error::RemoteFailed(response.status, "webhook".to_owned());
+        match response.status {
+            200..=299 => Retry::ok(()),
+            408 | 429 | 500..=599 => {
+                let retry_after = response
+                    .header("Retry-After")
+                    .and_then(retry::parse_retry_after);
+                Retry::after(err(), retry_after)
+            }
+            _ => Err(err()),
+        }
+    })
+    .await
+}
+
+/// Notifies all webhooks about a new public paste, without delaying the response.
+pub fn notify(platform: &dyn Platform, paste: &Paste) {
+    let deliveries = platform
+        .webhooks()
+        .iter()
+        .filter_map(|webhook| match payload(webhook.format, paste) {
+            Ok(body) => Some((webhook.clone(), body)),
+            Err(err) => {
+                log::warn!("failed to build webhook payload: {}", err);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    if deliveries.is_empty() {
+        return;
+    }
+
+    let (fetcher, timer) = (platform.fetcher(), platform.timer());
+    let deliveries = deliveries
+        .into_iter()
+        .map(|(webhook, body)| {
+            Box::pin(async move {
+                // Never log the full url, e.g. Discord urls contain the token of the webhook
+                let host = Url::parse(&webhook.url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_owned))
+                    .unwrap_or_default();
+
+                match deliver(fetcher, timer, &webhook, &body).await {
+                    Ok(()) => log::debug!("notified webhook at '{}'", host),
+                    Err(err) => log::warn!("failed to notify webhook at '{}': {}", host, err),
+                }
+            }) as LocalBoxFuture<'static, ()>
+        })
+        .collect();
+
+    // A slow webhook must not use up the time of the others
+    platform.wait_until(Box::pin(async move {
+        future::join_all(deliveries).await;
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fetch::Stub, future::block_on, retry::FakeTimer};
+
+    const URL: &str = "https://example.com/hook";
+
+    fn paste() -> Paste {
+        Paste {
+            id: "abc".to_owned(),
+            url: "https://pobb.in/abc".to_owned(),
+            title: "Cyclone Slayer".to_owned(),
+            class: "Slayer".to_owned(),
+            main_skill: Some("Cyclone".to_owned()),
+            stats: vec![Stat {
+                name: "Life".to_owned(),
+                value: "5,000 [150%]".to_owned(),
+            }],
+            created: 1_640_995_200_000,
+            color: 0x96afc8,
+        }
+    }
+
+    #[test]
+    fn test_payload() {
+        let json: serde_json::Value =
+            serde_json::from_slice(&payload(Format::Json, &paste()).unwrap()).unwrap();
+        assert_eq!(json["event"], "paste.created");
+        assert_eq!(json["paste"]["url"], "https://pobb.in/abc");
+        assert_eq!(json["paste"]["stats"][0]["value"], "5,000 [150%]");
+
+        let discord: serde_json::Value =
+            serde_json::from_slice(&payload(Format::Discord, &paste()).unwrap()).unwrap();
+        let embed = &discord["embeds"][0];
+        assert_eq!(embed["title"], "Cyclone Slayer");
+        assert_eq!(embed["description"], "Slayer · Cyclone");
+        assert_eq!(embed["color"], 0x96afc8);
+        assert_eq!(embed["fields"][0]["name"], "Life");
+        assert_eq!(embed["timestamp"], "2022-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_deliver() {
+        let webhook = Webhook {
+            url: URL.to_owned(),
+            format: Format::Json,
+            secret: Some("key".to_owned()),
+        };
+
+        let fetcher = Stub::default().with(URL, 503, "").with(URL, 204, "");
+        let timer = FakeTimer::default();
+        block_on(deliver(&fetcher, &timer, &webhook, b"{}")).unwrap();
+
+        let posts = fetcher.posts();
+        assert_eq!(posts.len(), 2);
+        assert_eq!(timer.sleeps.borrow().len(), 1);
+        let (url, headers, body) = &posts[1];
+        assert_eq!(url, URL);
+        assert_eq!(body, b"{}");
+        assert!(headers
+            .iter()
+            .any(|(name, value)| name == SIGNATURE_HEADER && *value == sign("key", b"{}")));
+
+        // The webhook no longer exists, retrying does not help
+        let fetcher = Stub::default().with(URL, 404, "");
+        let err = block_on(deliver(&fetcher, &timer, &webhook, b"{}")).unwrap_err();
+        assert!(matches!(err, Error::RemoteFailed(404, _)));
+        assert_eq!(fetcher.posts().len(), 1);
+    }
+}